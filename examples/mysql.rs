@@ -66,15 +66,9 @@ fn run_db() -> Result<Vec<Payment>> {
         }),
     )?;
 
-    // Let's select payments from database. Type inference should do the trick here.
-    let selected_payments = conn.query_map(
-        "SELECT customer_id, amount, account_name from payment",
-        |(customer_id, amount, account_name)| Payment {
-            customer_id,
-            amount,
-            account_name,
-        },
-    )?;
+    // Let's select payments from database. Rows are deserialized by column name.
+    let selected_payments: Vec<Payment> =
+        conn.query_as("SELECT customer_id, amount, account_name from payment")?;
 
     // Let's make sure, that `payments` equals to `selected_payments`.
     // Mysql gives no guaranties on order of returned rows
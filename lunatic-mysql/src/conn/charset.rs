@@ -0,0 +1,329 @@
+// Copyright (c) 2020 rust-mysql-simple contributors
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Decoding of string columns that are not sent as UTF-8.
+//!
+//! The server sends strings in the charset of each column, which is the connection charset
+//! unless `character_set_results` says otherwise (e.g. it is `NULL`, then columns keep the
+//! charset they are stored in). Rust strings are UTF-8, so string columns in another charset
+//! are converted right after the row is read. The charset is looked up by the collation id of
+//! the column, binary columns are left untouched. Reading a column in a charset without a
+//! decoder fails with [`DriverError::UnsupportedCharset`].
+
+use encoding_rs::Encoding;
+use mysql_common::{constants::ColumnType, row::new_row};
+
+use crate::{error::DriverError, Column, Result, Row, Value};
+
+/// Character sets of the built-in collations, as `(first id, last id, charset)`.
+///
+/// Taken from `INFORMATION_SCHEMA.COLLATIONS` of MySQL 8.0 and MariaDB 10.x.
+const COLLATIONS: &[(u16, u16, &str)] = &[
+    (1, 1, "big5"),
+    (2, 2, "latin2"),
+    (3, 3, "dec8"),
+    (4, 4, "cp850"),
+    (5, 5, "latin1"),
+    (6, 6, "hp8"),
+    (7, 7, "koi8r"),
+    (8, 8, "latin1"),
+    (9, 9, "latin2"),
+    (10, 10, "swe7"),
+    (11, 11, "ascii"),
+    (12, 12, "ujis"),
+    (13, 13, "sjis"),
+    (14, 14, "cp1251"),
+    (15, 15, "latin1"),
+    (16, 16, "hebrew"),
+    (18, 18, "tis620"),
+    (19, 19, "euckr"),
+    (20, 20, "latin7"),
+    (21, 21, "latin2"),
+    (22, 22, "koi8u"),
+    (23, 23, "cp1251"),
+    (24, 24, "gb2312"),
+    (25, 25, "greek"),
+    (26, 26, "cp1250"),
+    (27, 27, "latin2"),
+    (28, 28, "gbk"),
+    (29, 29, "cp1257"),
+    (30, 30, "latin5"),
+    (31, 31, "latin1"),
+    (32, 32, "armscii8"),
+    (33, 33, "utf8mb3"),
+    (34, 34, "cp1250"),
+    (35, 35, "ucs2"),
+    (36, 36, "cp866"),
+    (37, 37, "keybcs2"),
+    (38, 38, "macce"),
+    (39, 39, "macroman"),
+    (40, 40, "cp852"),
+    (41, 42, "latin7"),
+    (43, 43, "macce"),
+    (44, 44, "cp1250"),
+    (45, 46, "utf8mb4"),
+    (47, 49, "latin1"),
+    (50, 52, "cp1251"),
+    (53, 53, "macroman"),
+    (54, 55, "utf16"),
+    (56, 56, "utf16le"),
+    (57, 57, "cp1256"),
+    (58, 59, "cp1257"),
+    (60, 61, "utf32"),
+    (62, 62, "utf16le"),
+    (63, 63, "binary"),
+    (64, 64, "armscii8"),
+    (65, 65, "ascii"),
+    (66, 66, "cp1250"),
+    (67, 67, "cp1256"),
+    (68, 68, "cp866"),
+    (69, 69, "dec8"),
+    (70, 70, "greek"),
+    (71, 71, "hebrew"),
+    (72, 72, "hp8"),
+    (73, 73, "keybcs2"),
+    (74, 74, "koi8r"),
+    (75, 75, "koi8u"),
+    (76, 76, "utf8mb3"),
+    (77, 77, "latin2"),
+    (78, 78, "latin5"),
+    (79, 79, "latin7"),
+    (80, 80, "cp850"),
+    (81, 81, "cp852"),
+    (82, 82, "swe7"),
+    (83, 83, "utf8mb3"),
+    (84, 84, "big5"),
+    (85, 85, "euckr"),
+    (86, 86, "gb2312"),
+    (87, 87, "gbk"),
+    (88, 88, "sjis"),
+    (89, 89, "tis620"),
+    (90, 90, "ucs2"),
+    (91, 91, "ujis"),
+    (92, 93, "geostd8"),
+    (94, 94, "latin1"),
+    (95, 96, "cp932"),
+    (97, 98, "eucjpms"),
+    (99, 99, "cp1250"),
+    (101, 124, "utf16"),
+    (128, 159, "ucs2"),
+    (160, 183, "utf32"),
+    (192, 223, "utf8mb3"),
+    (224, 247, "utf8mb4"),
+    (248, 250, "gb18030"),
+    (255, 323, "utf8mb4"),
+];
+
+/// Returns the name of the character set of the collation `id`, if it's a known one.
+fn charset_of(id: u16) -> Option<&'static str> {
+    COLLATIONS
+        .iter()
+        .find(|&&(first, last, _)| first <= id && id <= last)
+        .map(|&(_, _, charset)| charset)
+}
+
+/// Whether the server sends values of `column` as strings, as opposed to binary data,
+/// numbers, dates etc.
+fn is_string_type(column: &Column) -> bool {
+    use ColumnType::*;
+    matches!(
+        column.column_type(),
+        MYSQL_TYPE_STRING
+            | MYSQL_TYPE_VAR_STRING
+            | MYSQL_TYPE_VARCHAR
+            | MYSQL_TYPE_ENUM
+            | MYSQL_TYPE_SET
+            | MYSQL_TYPE_TINY_BLOB
+            | MYSQL_TYPE_MEDIUM_BLOB
+            | MYSQL_TYPE_LONG_BLOB
+            | MYSQL_TYPE_BLOB
+    )
+}
+
+/// Returns the decoder for text in `charset`, if there is one.
+fn encoding_of(charset: &str) -> Option<&'static Encoding> {
+    use encoding_rs::*;
+    Some(match charset {
+        // MySQL's `latin1` is cp1252, not ISO-8859-1.
+        "latin1" => WINDOWS_1252,
+        "latin2" => ISO_8859_2,
+        "latin5" => WINDOWS_1254,
+        "latin7" => ISO_8859_13,
+        "greek" => ISO_8859_7,
+        "hebrew" => ISO_8859_8,
+        "cp1250" => WINDOWS_1250,
+        "cp1251" => WINDOWS_1251,
+        "cp1256" => WINDOWS_1256,
+        "cp1257" => WINDOWS_1257,
+        "cp866" => IBM866,
+        "koi8r" => KOI8_R,
+        "koi8u" => KOI8_U,
+        "macroman" => MACINTOSH,
+        "tis620" => WINDOWS_874,
+        "big5" => BIG5,
+        "sjis" | "cp932" => SHIFT_JIS,
+        "ujis" | "eucjpms" => EUC_JP,
+        "euckr" => EUC_KR,
+        "gb2312" | "gbk" => GBK,
+        "gb18030" => GB18030,
+        "ucs2" | "utf16" => UTF_16BE,
+        "utf16le" => UTF_16LE,
+        _ => return None,
+    })
+}
+
+/// Converts text in `charset` to UTF-8, or returns `None` if there is no decoder for `charset`.
+///
+/// Invalid sequences are replaced with U+FFFD, as the server does when it converts them.
+fn to_utf8(charset: &str, bytes: &[u8]) -> Option<Vec<u8>> {
+    let (text, _) = encoding_of(charset)?.decode_without_bom_handling(bytes);
+    Some(text.into_owned().into_bytes())
+}
+
+/// Returns the charset of `column` if its values have to be converted to UTF-8.
+///
+/// Binary columns, e.g. `BLOB` or `VARBINARY`, have the `binary` charset and are never
+/// converted, so that reading them as `Vec<u8>` returns the raw bytes.
+fn foreign_charset(column: &Column) -> Option<&'static str> {
+    if !is_string_type(column) {
+        return None;
+    }
+    match charset_of(column.character_set())? {
+        "binary" | "ascii" | "utf8mb3" | "utf8mb4" => None,
+        charset => Some(charset),
+    }
+}
+
+/// Converts the values of string columns of `row` that are not in a UTF-8 compatible charset
+/// to UTF-8.
+///
+/// Rows without such columns are returned as is.
+pub(crate) fn decode_row(row: Row) -> Result<Row> {
+    if !row
+        .columns_ref()
+        .iter()
+        .any(|c| foreign_charset(c).is_some())
+    {
+        return Ok(row);
+    }
+    let columns = row.columns();
+    let values = row
+        .unwrap()
+        .into_iter()
+        .zip(columns.iter())
+        .map(|(value, column)| match (value, foreign_charset(column)) {
+            (Value::Bytes(bytes), Some(charset)) => match to_utf8(charset, &bytes) {
+                Some(decoded) => Ok(Value::Bytes(decoded)),
+                None => Err(DriverError::UnsupportedCharset(
+                    column.name_str().into_owned(),
+                    charset.to_string(),
+                )
+                .into()),
+            },
+            (value, _) => Ok(value),
+        })
+        .collect::<Result<_>>()?;
+    Ok(new_row(values, columns))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use mysql_common::{constants::ColumnType, row::new_row};
+
+    use super::{charset_of, decode_row, to_utf8};
+    use crate::{error::DriverError, Column, Error, Value};
+
+    fn column(name: &str, collation: u16) -> Column {
+        Column::new(ColumnType::MYSQL_TYPE_VAR_STRING)
+            .with_name(name.as_bytes())
+            .with_character_set(collation)
+    }
+
+    #[test]
+    fn should_look_up_charset_by_collation() {
+        assert_eq!(charset_of(8), Some("latin1"));
+        assert_eq!(charset_of(48), Some("latin1"));
+        assert_eq!(charset_of(33), Some("utf8mb3"));
+        assert_eq!(charset_of(45), Some("utf8mb4"));
+        assert_eq!(charset_of(255), Some("utf8mb4"));
+        assert_eq!(charset_of(63), Some("binary"));
+        assert_eq!(charset_of(26), Some("cp1250"));
+        assert_eq!(charset_of(17), None);
+        assert_eq!(charset_of(1000), None);
+    }
+
+    #[test]
+    fn should_decode_latin1() {
+        let latin1 = |bytes| to_utf8("latin1", bytes).unwrap();
+        assert_eq!(latin1(b"plain"), b"plain");
+        assert_eq!(latin1(b"caf\xe9"), "café".as_bytes());
+        assert_eq!(latin1(b"\x80 5 \x96 \x93x\x94"), "€ 5 – “x”".as_bytes());
+        assert_eq!(latin1(b"\xff\x81"), "ÿ\u{81}".as_bytes());
+    }
+
+    #[test]
+    fn should_decode_other_charsets() {
+        assert_eq!(
+            to_utf8("cp1251", b"\xcc\xe8\xf0").unwrap(),
+            "Мир".as_bytes()
+        );
+        assert_eq!(
+            to_utf8("sjis", b"\x93\xfa\x96\x7b").unwrap(),
+            "日本".as_bytes()
+        );
+        assert_eq!(to_utf8("ucs2", b"\x00A\x04\x10").unwrap(), "AА".as_bytes());
+        assert_eq!(to_utf8("dec8", b"abc"), None);
+    }
+
+    #[test]
+    fn should_decode_row_with_mixed_charsets() {
+        let columns: Arc<[Column]> = vec![
+            column("name", 8),
+            column("title", 255),
+            Column::new(ColumnType::MYSQL_TYPE_BLOB).with_character_set(63),
+            column("city", 14),
+        ]
+        .into();
+        let row = new_row(
+            vec![
+                Value::Bytes(b"Jos\xe9".to_vec()),
+                Value::Bytes("José".as_bytes().to_vec()),
+                Value::Bytes(b"\xe9\xff".to_vec()),
+                Value::NULL,
+            ],
+            columns,
+        );
+
+        let row = decode_row(row).unwrap();
+        assert_eq!(row.get::<String, _>(0).unwrap(), "José");
+        assert_eq!(row.get::<String, _>(1).unwrap(), "José");
+        assert_eq!(row.get::<Vec<u8>, _>(2).unwrap(), b"\xe9\xff");
+        assert_eq!(row.as_ref(3), Some(&Value::NULL));
+    }
+
+    #[test]
+    fn should_fail_on_charsets_without_decoder() {
+        let columns: Arc<[Column]> = vec![column("name", 8), column("code", 10)].into();
+        let row = new_row(
+            vec![
+                Value::Bytes(b"Jos\xe9".to_vec()),
+                Value::Bytes(b"ab".to_vec()),
+            ],
+            columns,
+        );
+
+        match decode_row(row) {
+            Err(Error::DriverError(DriverError::UnsupportedCharset(column, charset))) => {
+                assert_eq!((column.as_str(), charset.as_str()), ("code", "swe7"));
+            }
+            other => panic!("expected an unsupported charset error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,381 @@
+// Copyright (c) 2020 rust-mysql-simple contributors
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Deserialization of rows into serde types by column name.
+
+use serde::de::{
+    self, value::StringDeserializer, DeserializeOwned, DeserializeSeed, Deserializer,
+    IntoDeserializer, MapAccess, SeqAccess, Visitor,
+};
+
+use std::{fmt, vec};
+
+use crate::{
+    error::{DriverError::RowDeserializationFailed, Error::DriverError},
+    from_value_opt, FromValueError, Result, Row, Value,
+};
+
+/// Deserializes a row into `T`.
+///
+/// Structs and maps are filled by column name, so the order of the columns
+/// in the query does not matter, while tuples and sequences are filled by
+/// position.  A NULL is only accepted by an `Option` field, and text columns
+/// holding JSON can be read into nested structs.
+pub(crate) fn deserialize_row<T: DeserializeOwned>(mut row: Row) -> Result<T> {
+    let columns = row.columns();
+    let fields = (0..row.len())
+        .map(|i| {
+            let name = columns[i].name_str().into_owned();
+            (name, row.take(i).unwrap_or(Value::NULL))
+        })
+        .collect::<Vec<_>>();
+    T::deserialize(RowDeserializer(fields))
+        .map_err(|DeError(desc)| DriverError(RowDeserializationFailed(desc)))
+}
+
+#[derive(Debug)]
+struct DeError(String);
+
+impl de::Error for DeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        DeError(msg.to_string())
+    }
+}
+
+impl std::error::Error for DeError {}
+
+impl fmt::Display for DeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+fn conversion_error(value: Value, expected: &str) -> DeError {
+    if value == Value::NULL {
+        DeError("unexpected NULL for non-Option field".into())
+    } else {
+        DeError(format!("could not convert {:?} into {}", value, expected))
+    }
+}
+
+struct RowDeserializer(Vec<(String, Value)>);
+
+impl<'de> Deserializer<'de> for RowDeserializer {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> StdResult<V::Value> {
+        visitor.visit_map(RowMap {
+            fields: self.0.into_iter(),
+            value: None,
+        })
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> StdResult<V::Value> {
+        let values = self
+            .0
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect::<Vec<_>>();
+        visitor.visit_seq(ValueSeq(values.into_iter()))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> StdResult<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> StdResult<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct map struct enum
+        identifier ignored_any
+    }
+}
+
+type StdResult<T> = std::result::Result<T, DeError>;
+
+struct RowMap {
+    fields: vec::IntoIter<(String, Value)>,
+    value: Option<(String, Value)>,
+}
+
+impl<'de> MapAccess<'de> for RowMap {
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> StdResult<Option<K::Value>> {
+        match self.fields.next() {
+            Some((name, value)) => {
+                let key: StringDeserializer<DeError> = name.clone().into_deserializer();
+                self.value = Some((name, value));
+                seed.deserialize(key).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> StdResult<V::Value> {
+        let (name, value) = self
+            .value
+            .take()
+            .ok_or_else(|| DeError("value requested before key".into()))?;
+        seed.deserialize(ValueDeserializer(value))
+            .map_err(|DeError(desc)| DeError(format!("column `{}`: {}", name, desc)))
+    }
+}
+
+struct ValueSeq(vec::IntoIter<Value>);
+
+impl<'de> SeqAccess<'de> for ValueSeq {
+    type Error = DeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> StdResult<Option<T::Value>> {
+        self.0
+            .next()
+            .map(|value| seed.deserialize(ValueDeserializer(value)))
+            .transpose()
+    }
+}
+
+struct ValueDeserializer(Value);
+
+macro_rules! deserialize_via_from_value {
+    ($($method:ident => $visit:ident: $t:ty,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> StdResult<V::Value> {
+                match from_value_opt::<$t>(self.0) {
+                    Ok(value) => visitor.$visit(value),
+                    Err(FromValueError(value)) => Err(conversion_error(value, stringify!($t))),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> StdResult<V::Value> {
+        match self.0 {
+            Value::NULL => visitor.visit_unit(),
+            Value::Bytes(bytes) => match String::from_utf8(bytes) {
+                Ok(string) => visitor.visit_string(string),
+                Err(err) => visitor.visit_byte_buf(err.into_bytes()),
+            },
+            Value::Int(value) => visitor.visit_i64(value),
+            Value::UInt(value) => visitor.visit_u64(value),
+            Value::Float(value) => visitor.visit_f32(value),
+            Value::Double(value) => visitor.visit_f64(value),
+            // Dates and times are read in their SQL form, e.g.
+            // `2020-01-01 12:00:00`, the same as the text protocol sends them.
+            value @ Value::Date(..) | value @ Value::Time(..) => {
+                visitor.visit_string(value.as_sql(true).trim_matches('\'').to_string())
+            }
+        }
+    }
+
+    deserialize_via_from_value! {
+        deserialize_bool => visit_bool: bool,
+        deserialize_i8 => visit_i8: i8,
+        deserialize_i16 => visit_i16: i16,
+        deserialize_i32 => visit_i32: i32,
+        deserialize_i64 => visit_i64: i64,
+        deserialize_u8 => visit_u8: u8,
+        deserialize_u16 => visit_u16: u16,
+        deserialize_u32 => visit_u32: u32,
+        deserialize_u64 => visit_u64: u64,
+        deserialize_f32 => visit_f32: f32,
+        deserialize_f64 => visit_f64: f64,
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> StdResult<V::Value> {
+        match self.0 {
+            Value::NULL => Err(conversion_error(Value::NULL, "String")),
+            value => ValueDeserializer(value).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> StdResult<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> StdResult<V::Value> {
+        match self.0 {
+            Value::Bytes(bytes) => visitor.visit_byte_buf(bytes),
+            value => Err(conversion_error(value, "bytes")),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> StdResult<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> StdResult<V::Value> {
+        match self.0 {
+            Value::NULL => visitor.visit_none(),
+            value => visitor.visit_some(ValueDeserializer(value)),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> StdResult<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> StdResult<V::Value> {
+        // `ENUM` columns map to unit variants by name.
+        match self.0 {
+            Value::Bytes(bytes) => match String::from_utf8(bytes) {
+                Ok(string) => {
+                    let variant: StringDeserializer<DeError> = string.into_deserializer();
+                    visitor.visit_enum(variant)
+                }
+                Err(err) => Err(conversion_error(Value::Bytes(err.into_bytes()), "enum")),
+            },
+            value => Err(conversion_error(value, "enum")),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> StdResult<V::Value> {
+        self.deserialize_json(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> StdResult<V::Value> {
+        self.deserialize_json(visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> StdResult<V::Value> {
+        self.deserialize_json(visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 char unit unit_struct tuple tuple_struct identifier ignored_any
+    }
+}
+
+impl ValueDeserializer {
+    /// Reads a nested value from a column holding JSON.
+    fn deserialize_json<'de, V: Visitor<'de>>(self, visitor: V) -> StdResult<V::Value> {
+        match self.0 {
+            Value::Bytes(bytes) => serde_json::from_slice::<serde_json::Value>(&bytes)
+                .and_then(|json| json.deserialize_any(visitor))
+                .map_err(de::Error::custom),
+            value => Err(conversion_error(value, "JSON")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mysql_common::{constants::ColumnType, packets::Column, row::new_row};
+
+    use std::sync::Arc;
+
+    use super::deserialize_row;
+    use crate::{DriverError::RowDeserializationFailed, Error::DriverError, Row, Value};
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Payment {
+        customer_id: i32,
+        amount: i32,
+        account_name: Option<String>,
+    }
+
+    fn row(columns: &[&str], values: Vec<Value>) -> Row {
+        let columns = columns
+            .iter()
+            .map(|name| Column::new(ColumnType::MYSQL_TYPE_VAR_STRING).with_name(name.as_bytes()))
+            .collect::<Vec<_>>();
+        new_row(values, Arc::from(columns))
+    }
+
+    #[test]
+    fn should_deserialize_by_column_name() {
+        // The text protocol sends every value as bytes
+        let text = row(
+            &["account_name", "amount", "customer_id"],
+            vec![
+                Value::Bytes(b"foo".to_vec()),
+                Value::Bytes(b"4".to_vec()),
+                Value::Bytes(b"3".to_vec()),
+            ],
+        );
+        let payment: Payment = deserialize_row(text).unwrap();
+        assert_eq!(
+            payment,
+            Payment {
+                customer_id: 3,
+                amount: 4,
+                account_name: Some("foo".into()),
+            }
+        );
+
+        let binary = row(
+            &["customer_id", "account_name", "amount"],
+            vec![Value::Int(1), Value::NULL, Value::Int(2)],
+        );
+        let payment: Payment = deserialize_row(binary).unwrap();
+        assert_eq!(
+            payment,
+            Payment {
+                customer_id: 1,
+                amount: 2,
+                account_name: None,
+            }
+        );
+
+        let tuple: (i32, String) = deserialize_row(row(
+            &["a", "b"],
+            vec![Value::Int(1), Value::Bytes(b"x".to_vec())],
+        ))
+        .unwrap();
+        assert_eq!(tuple, (1, "x".into()));
+    }
+
+    #[test]
+    fn should_report_failed_columns() {
+        let null_amount = row(
+            &["customer_id", "amount", "account_name"],
+            vec![Value::Int(1), Value::NULL, Value::NULL],
+        );
+        match deserialize_row::<Payment>(null_amount) {
+            Err(DriverError(RowDeserializationFailed(desc))) => {
+                assert_eq!(
+                    desc,
+                    "column `amount`: unexpected NULL for non-Option field"
+                )
+            }
+            other => panic!("expected a deserialization error, got {:?}", other),
+        }
+
+        let missing = row(&["customer_id"], vec![Value::Int(1)]);
+        assert!(deserialize_row::<Payment>(missing).is_err());
+    }
+}
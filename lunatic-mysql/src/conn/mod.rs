@@ -6,7 +6,7 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
-use bytes::{Buf, BufMut};
+use bytes::{Buf, BufMut, BytesMut};
 use mysql_common::{
     crypto,
     io::{ParseBuf, ReadMysqlExt},
@@ -18,7 +18,11 @@ use mysql_common::{
         HandshakePacket, HandshakeResponse, OkPacket, OkPacketDeserializer, OkPacketKind,
         OldAuthSwitchRequest, ResultSetTerminator, SessionStateInfo,
     },
-    proto::{codec::Compression, sync_framed::MySyncFramed, MySerialize},
+    proto::{
+        codec::{Compression, PacketCodec},
+        sync_framed::MySyncFramed,
+        MySerialize,
+    },
     row::{Row, RowDeserializer},
     value::ServerSide,
 };
@@ -45,23 +49,24 @@ use crate::{
     buffer_pool::{get_buffer, Buffer},
     conn::{
         local_infile::LocalInfile,
+        pagination::Pages,
         pool::{Pool, PooledConn},
         query_result::{Binary, Or, Text},
         stmt::{InnerStmt, Statement},
         stmt_cache::StmtCache,
-        transaction::{AccessMode, TxOpts},
+        transaction::{AccessMode, RetryPolicy, TxOpts},
     },
     consts::{CapabilityFlags, Command, StatusFlags, MAX_PAYLOAD_LEN},
     from_value, from_value_opt,
     io::Stream,
     prelude::*,
     DriverError::{
-        MismatchedStmtParams, NamedParamsForPositionalQuery, OldMysqlPasswordDisabled,
-        Protocol41NotSet, ReadOnlyTransNotSupported, SetupError, UnexpectedPacket,
-        UnknownAuthPlugin, UnsupportedProtocol,
+        MaxAllowedPacketExceeded, MismatchedStmtParams, NamedParamsForPositionalQuery,
+        OldMysqlPasswordDisabled, Protocol41NotSet, ReadOnlyTransNotSupported, SetupError,
+        UnexpectedPacket, UnknownAuthPlugin, UnsupportedProtocol,
     },
     Error::{self, DriverError, MySqlError},
-    LocalInfileHandler, Opts, OptsBuilder, Params, QueryResult, Result, Transaction,
+    LocalInfileHandler, Opts, OptsBuilder, Params, QueryResult, Result, ServerError, Transaction,
     Value::{self, Bytes, NULL},
 };
 
@@ -71,10 +76,15 @@ use crate::SslOpts;
 use self::binlog_stream::BinlogStream;
 
 pub mod binlog_stream;
+mod charset;
+mod de;
 pub mod local_infile;
 pub mod opts;
+pub mod pagination;
 pub mod pool;
 pub mod query;
+#[cfg(feature = "query-log")]
+pub mod query_log;
 pub mod query_result;
 pub mod queryable;
 pub mod stmt;
@@ -157,6 +167,8 @@ impl DerefMut for ConnMut<'_, '_, '_> {
 struct ConnInner {
     opts: Opts,
     stream: Option<MySyncFramed<Stream>>,
+    /// The buffers and codec of the stream while its socket is parked, see `Conn::park`.
+    parked: Option<(BytesMut, BytesMut, PacketCodec)>,
     stmt_cache: StmtCache,
 
     // TODO: clean this up
@@ -171,8 +183,12 @@ struct ConnInner {
     character_set: u8,
     last_command: u8,
     connected: bool,
+    /// Set once the server has closed the connection, until it's reset.
+    broken: bool,
     has_results: bool,
     local_infile_handler: Option<LocalInfileHandler>,
+    #[cfg(feature = "query-log")]
+    query_log_hook: Option<crate::QueryLogHook>,
 }
 
 impl ConnInner {
@@ -181,6 +197,7 @@ impl ConnInner {
             stmt_cache: StmtCache::new(opts.get_stmt_cache_size()),
             opts,
             stream: None,
+            parked: None,
             capability_flags: CapabilityFlags::empty(),
             status_flags: StatusFlags::empty(),
             connection_id: 0u32,
@@ -188,10 +205,13 @@ impl ConnInner {
             ok_packet: None,
             last_command: 0u8,
             connected: false,
+            broken: false,
             has_results: false,
             server_version: None,
             mariadb_server_version: None,
             local_infile_handler: None,
+            #[cfg(feature = "query-log")]
+            query_log_hook: None,
         }
     }
 }
@@ -211,6 +231,18 @@ impl Conn {
         self.0.connection_id
     }
 
+    /// Returns `true` if the connection is inside a transaction.
+    ///
+    /// This is the transaction state reported by the server with the last query, so it is
+    /// `true` between [`Conn::start_transaction`] (or a `START TRANSACTION` query) and the
+    /// commit or rollback. With `autocommit` disabled, a transaction starts implicitly with the
+    /// first statement that uses a transactional table.
+    pub fn in_transaction(&self) -> bool {
+        self.0
+            .status_flags
+            .contains(StatusFlags::SERVER_STATUS_IN_TRANS)
+    }
+
     /// Returns number of rows affected by the last query.
     pub fn affected_rows(&self) -> u64 {
         self.0
@@ -275,6 +307,17 @@ impl Conn {
             .map(Option::unwrap_or_default)
     }
 
+    /// Returns the largest packet this connection will send, that is the server's
+    /// `max_allowed_packet` or the smaller limit set with
+    /// [`OptsBuilder::max_allowed_packet`](crate::OptsBuilder::max_allowed_packet).
+    ///
+    /// Queries and parameters that don't fit fail with
+    /// [`DriverError::MaxAllowedPacketExceeded`](crate::DriverError::MaxAllowedPacketExceeded)
+    /// before anything is sent, and the connection stays usable.
+    pub fn max_allowed_packet(&self) -> usize {
+        self.stream_ref().codec().max_allowed_packet
+    }
+
     fn stream_ref(&self) -> &MySyncFramed<Stream> {
         self.0.stream.as_ref().expect("incomplete connection")
     }
@@ -363,6 +406,7 @@ impl Conn {
         self.0.ok_packet = None;
         self.0.last_command = 0;
         self.0.connected = false;
+        self.0.broken = false;
         self.0.has_results = false;
         self.connect_stream()?;
         self.connect()
@@ -438,6 +482,12 @@ impl Conn {
                     match ParseBuf(&*buffer).parse(self.0.capability_flags)? {
                         ErrPacket::Error(server_error) => {
                             self.handle_err();
+                            if server_error.error_code()
+                                == ServerError::ER_NET_PACKET_TOO_LARGE as u16
+                            {
+                                // The server closes the connection after this error.
+                                self.0.broken = true;
+                            }
                             return Err(MySqlError(From::from(server_error)));
                         }
                         ErrPacket::Progress(_progress_report) => {
@@ -466,6 +516,9 @@ impl Conn {
     }
 
     fn write_packet<T: Buf>(&mut self, data: &mut T) -> Result<()> {
+        if self.0.broken {
+            return Err(Error::server_disconnected());
+        }
         self.stream_mut().send(data)?;
         Ok(())
     }
@@ -492,7 +545,9 @@ impl Conn {
     }
 
     fn handle_err(&mut self) {
-        self.0.status_flags = StatusFlags::empty();
+        // A failed statement doesn't end the transaction, unless the server rolled it back, which
+        // the next OK packet will tell.
+        self.0.status_flags &= StatusFlags::SERVER_STATUS_IN_TRANS;
         self.0.has_results = false;
         self.0.ok_packet = None;
     }
@@ -789,9 +844,20 @@ impl Conn {
         self.stream_mut().codec_mut().sync_seq_id();
     }
 
+    /// Fails with `MaxAllowedPacketExceeded` if a command of `len` bytes won't be accepted by the
+    /// server.
+    fn check_packet_size(&self, len: usize) -> Result<()> {
+        let max = self.max_allowed_packet();
+        if len > max {
+            return Err(DriverError(MaxAllowedPacketExceeded(len, max)));
+        }
+        Ok(())
+    }
+
     fn write_command_raw<T: MySerialize>(&mut self, cmd: &T) -> Result<()> {
         let mut buf = get_buffer();
         cmd.serialize(buf.as_mut());
+        self.check_packet_size(buf.len())?;
         self.reset_seq_id();
         debug_assert!(buf.len() > 0);
         self.0.last_command = buf[0];
@@ -803,15 +869,25 @@ impl Conn {
         buf.as_mut().put_u8(cmd as u8);
         buf.as_mut().extend_from_slice(data);
 
+        self.check_packet_size(buf.len())?;
         self.reset_seq_id();
         self.0.last_command = buf[0];
         self.write_packet(&mut &*buf)
     }
 
     fn send_long_data(&mut self, stmt_id: u32, params: &[Value]) -> Result<()> {
+        // The server refuses to accumulate more than `max_allowed_packet` bytes for
+        // a single parameter, so check all of them before sending anything.
+        for value in params {
+            if let Bytes(bytes) = value {
+                self.check_packet_size(bytes.len())?;
+            }
+        }
+        // COM_STMT_SEND_LONG_DATA header is 7 bytes long
+        let chunk_len = cmp::min(MAX_PAYLOAD_LEN, self.max_allowed_packet()) - 7;
         for (i, value) in params.iter().enumerate() {
             if let Bytes(bytes) = value {
-                let chunks = bytes.chunks(MAX_PAYLOAD_LEN - 6);
+                let chunks = bytes.chunks(chunk_len);
                 let chunks = chunks.chain(if bytes.is_empty() {
                     Some(&[][..])
                 } else {
@@ -965,12 +1041,13 @@ impl Conn {
     }
 
     /// Executes [`COM_PING`](http://dev.mysql.com/doc/internals/en/com-ping.html)
-    /// on `Conn`. Return `true` on success or `false` on error.
-    pub fn ping(&mut self) -> bool {
-        match self.write_command(Command::COM_PING, &[]) {
-            Ok(_) => self.drop_packet().is_ok(),
-            _ => false,
-        }
+    /// on `Conn`.
+    ///
+    /// This is the cheapest way to check that the connection is alive, it fails if the
+    /// server can't be reached or closed the connection.
+    pub fn ping(&mut self) -> Result<()> {
+        self.write_command(Command::COM_PING, &[])?;
+        self.drop_packet()
     }
 
     /// Executes [`COM_INIT_DB`](https://dev.mysql.com/doc/internals/en/com-init-db.html)
@@ -989,10 +1066,83 @@ impl Conn {
         Ok(Transaction::new(self.into()))
     }
 
+    /// Fetches the rows of `query` in pages of `page_size` rows using keyset pagination.
+    ///
+    /// `query` may take `params` and must return a column named `key_column` with unique
+    /// values, e.g. the primary key, otherwise `DriverError::MissingKeyColumn` is returned.
+    /// Rows are returned in the order of this column. Every page is fetched with a prepared
+    /// statement selecting the rows whose key is greater than the last key of the previous page,
+    /// so, given an index on the key, a page far into a large table is as cheap as the first one,
+    /// unlike with `LIMIT .. OFFSET ..`.
+    ///
+    /// Pages are not fetched in a single snapshot, so rows inserted or deleted meanwhile may or
+    /// may not show up, but no row is returned twice.
+    ///
+    /// ```rust
+    /// # mysql::doctest_wrapper!(__result, {
+    /// # use mysql::*;
+    /// # use mysql::prelude::*;
+    /// # let mut conn = Conn::new(get_opts())?;
+    /// # conn.query_drop("CREATE TEMPORARY TABLE users (id INT PRIMARY KEY, name TEXT)")?;
+    /// # conn.exec_batch("INSERT INTO users VALUES (?, ?)", (1..=250).map(|id| (id, "user")))?;
+    /// let mut pages = 0;
+    /// let query = "SELECT id, name FROM users WHERE id > ?";
+    /// for page in conn.paginate::<(u32, String), _>(query, (60,), "id", 100)? {
+    ///     assert!(page?.len() <= 100);
+    ///     pages += 1;
+    /// }
+    /// assert_eq!(pages, 2);
+    /// # });
+    /// ```
+    pub fn paginate<T: FromRow, P: Into<Params>>(
+        &mut self,
+        query: &str,
+        params: P,
+        key_column: &str,
+        page_size: usize,
+    ) -> Result<Pages<'_, T>> {
+        Pages::new(self, query, params.into(), key_column, page_size)
+    }
+
+    /// Runs `f` in a new transaction and commits it if `f` succeeds.
+    ///
+    /// If `f` or the commit fails with a deadlock or a lock wait timeout, the transaction
+    /// is rolled back and `f` is run again in a new one, as allowed by `retry`
+    /// (see [`RetryPolicy`]). `f` must therefore be safe to run more than once.
+    ///
+    /// ```rust
+    /// # mysql::doctest_wrapper!(__result, {
+    /// # use mysql::*;
+    /// # use mysql::prelude::*;
+    /// # let mut conn = Conn::new(get_opts())?;
+    /// # conn.query_drop("CREATE TEMPORARY TABLE accounts (id INT, balance INT)")?;
+    /// let retry = RetryPolicy::new(3);
+    /// conn.transaction(TxOpts::default(), retry, |tx| {
+    ///     tx.exec_drop("UPDATE accounts SET balance = balance - ? WHERE id = ?", (10, 1))?;
+    ///     tx.exec_drop("UPDATE accounts SET balance = balance + ? WHERE id = ?", (10, 2))
+    /// })?;
+    /// # });
+    /// ```
+    pub fn transaction<T, F>(&mut self, tx_opts: TxOpts, retry: RetryPolicy, mut f: F) -> Result<T>
+    where
+        F: FnMut(&mut Transaction<'_>) -> Result<T>,
+    {
+        retry.run(|| {
+            let mut tx = self.start_transaction(tx_opts)?;
+            let value = f(&mut tx)?;
+            tx.commit()?;
+            Ok(value)
+        })
+    }
+
     fn _true_prepare(&mut self, query: &str) -> Result<InnerStmt> {
         self.write_command(Command::COM_STMT_PREPARE, query.as_bytes())?;
         let pld = self.read_packet()?;
         let mut stmt = ParseBuf(&*pld).parse::<InnerStmt>(self.connection_id())?;
+        #[cfg(feature = "query-log")]
+        {
+            stmt = stmt.with_query(query);
+        }
         if stmt.num_params() > 0 {
             let mut params: Vec<Column> = Vec::with_capacity(stmt.num_params() as usize);
             for _ in 0..stmt.num_params() {
@@ -1047,6 +1197,10 @@ impl Conn {
                 if max_allowed_packet == 0 {
                     Err(DriverError(SetupError))
                 } else {
+                    let max_allowed_packet = match self.0.opts.get_max_allowed_packet() {
+                        Some(limit) => cmp::min(limit, max_allowed_packet),
+                        None => max_allowed_packet,
+                    };
                     self.stream_mut().codec_mut().max_allowed_packet = max_allowed_packet;
                     self.0.connected = true;
                     Ok(())
@@ -1069,7 +1223,7 @@ impl Conn {
             return Ok(None);
         }
         let row = ParseBuf(&*pld).parse::<RowDeserializer<ServerSide, Binary>>(columns)?;
-        Ok(Some(row.into()))
+        charset::decode_row(row.into()).map(Some)
     }
 
     fn next_text(&mut self, columns: Arc<[Column]>) -> Result<Option<Row>> {
@@ -1083,13 +1237,41 @@ impl Conn {
             return Ok(None);
         }
         let row = ParseBuf(&*pld).parse::<RowDeserializer<(), Text>>(columns)?;
-        Ok(Some(row.into()))
+        charset::decode_row(row.into()).map(Some)
     }
 
     fn has_stmt(&self, query: &str) -> bool {
         self.0.stmt_cache.contains_query(query)
     }
 
+    /// Takes the socket out of an idle connection, so that another process can hold it while
+    /// the connection sits in a pool. The connection is unusable until the socket is given
+    /// back with [`Conn::unpark`].
+    ///
+    /// Returns `None` and keeps the connection as it is if the socket can't be moved, e.g.
+    /// because it is wrapped in TLS.
+    pub(crate) fn park(&mut self) -> Option<lunatic::net::TcpStream> {
+        let (in_buf, out_buf, codec, stream) = self.0.stream.take()?.destruct();
+        match stream.into_socket() {
+            Ok(socket) => {
+                self.0.parked = Some((in_buf, out_buf, codec));
+                Some(socket)
+            }
+            Err(stream) => {
+                self.0.stream = Some(MySyncFramed::construct(in_buf, out_buf, codec, stream));
+                None
+            }
+        }
+    }
+
+    /// Gives a connection the socket taken with [`Conn::park`] back.
+    pub(crate) fn unpark(&mut self, socket: lunatic::net::TcpStream) {
+        if let Some((in_buf, out_buf, codec)) = self.0.parked.take() {
+            let stream = Stream::from_socket(socket);
+            self.0.stream = Some(MySyncFramed::construct(in_buf, out_buf, codec, stream));
+        }
+    }
+
     /// Sets a callback to handle requests for local files. These are
     /// caused by using `LOAD DATA LOCAL INFILE` queries. The
     /// callback is passed the filename, and a `Write`able object
@@ -1100,6 +1282,14 @@ impl Conn {
         self.0.local_infile_handler = handler;
     }
 
+    /// Sets a callback that is called once for every query or statement
+    /// execution on this connection, see [`QueryLogHook`](crate::QueryLogHook).
+    /// Specifying `None` removes the hook.
+    #[cfg(feature = "query-log")]
+    pub fn set_query_log_hook(&mut self, hook: Option<crate::QueryLogHook>) {
+        self.0.query_log_hook = hook;
+    }
+
     pub fn no_backslash_escape(&self) -> bool {
         self.0
             .status_flags
@@ -1137,7 +1327,14 @@ impl Conn {
 
 impl Queryable for Conn {
     fn query_iter<T: AsRef<str>>(&mut self, query: T) -> Result<QueryResult<'_, '_, '_, Text>> {
+        #[cfg(feature = "query-log")]
+        let started = std::time::Instant::now();
         let meta = self._query(query.as_ref())?;
+        #[cfg(feature = "query-log")]
+        if let Some(hook) = self.0.query_log_hook.clone() {
+            let log = query_log::PendingQueryLog::new(hook, query.as_ref(), 0, started);
+            return Ok(QueryResult::new(ConnMut::Mut(self), meta).with_log(log));
+        }
         Ok(QueryResult::new(ConnMut::Mut(self), meta))
     }
 
@@ -1170,22 +1367,40 @@ impl Queryable for Conn {
         P: Into<Params>,
     {
         let statement = stmt.as_statement(self)?;
-        let meta = self._execute(&*statement, params.into())?;
+        let params = params.into();
+        #[cfg(feature = "query-log")]
+        let (started, num_params) = (
+            std::time::Instant::now(),
+            match params {
+                Params::Empty => 0,
+                Params::Named(ref params) => params.len(),
+                Params::Positional(ref params) => params.len(),
+            },
+        );
+        let meta = self._execute(&statement, params)?;
+        #[cfg(feature = "query-log")]
+        if let Some(hook) = self.0.query_log_hook.clone() {
+            let log =
+                query_log::PendingQueryLog::new(hook, statement.inner.query(), num_params, started);
+            return Ok(QueryResult::new(ConnMut::Mut(self), meta).with_log(log));
+        }
         Ok(QueryResult::new(ConnMut::Mut(self), meta))
     }
 }
 
 impl Drop for Conn {
     fn drop(&mut self) {
+        if self.0.stream.is_none() {
+            // A parked connection, its socket is closed by whoever holds it.
+            return;
+        }
         let stmt_cache = mem::replace(&mut self.0.stmt_cache, StmtCache::new(0));
 
         for (_, entry) in stmt_cache.into_iter() {
             let _ = self.close(Statement::new(entry.stmt, None));
         }
 
-        if self.0.stream.is_some() {
-            let _ = self.write_command(Command::COM_QUIT, &[]);
-        }
+        let _ = self.write_command(Command::COM_QUIT, &[]);
     }
 }
 
@@ -1211,9 +1426,9 @@ mod test {
             prelude::*,
             test_misc::get_opts,
             Conn,
-            DriverError::{MissingNamedParameter, NamedParamsForPositionalQuery},
-            Error::DriverError,
-            LocalInfileHandler, Opts, OptsBuilder, Pool, TxOpts,
+            DriverError::{MissingKeyColumn, MissingNamedParameter, NamedParamsForPositionalQuery},
+            Error::{DriverError, MySqlError},
+            LocalInfileHandler, Opts, OptsBuilder, Pool, RetryPolicy, ServerError, TxOpts,
             Value::{self, Bytes, Date, Float, Int, NULL},
         };
 
@@ -1236,7 +1451,7 @@ mod test {
                 .unwrap()
                 .unwrap();
             assert!(mode.contains("TRADITIONAL"));
-            assert!(conn.ping());
+            conn.ping().unwrap();
 
             if crate::test_misc::test_compression() {
                 assert!(format!("{:?}", conn.0.stream).contains("Compression"));
@@ -1247,6 +1462,18 @@ mod test {
             }
         }
 
+        #[test]
+        fn should_ping() {
+            let mut conn = Conn::new(get_opts()).unwrap();
+            conn.ping().unwrap();
+
+            let mut killer = Conn::new(get_opts()).unwrap();
+            killer
+                .query_drop(format!("KILL CONNECTION {}", conn.connection_id()))
+                .unwrap();
+            assert!(conn.ping().is_err());
+        }
+
         #[test]
         fn mysql_async_issue_107() -> crate::Result<()> {
             let mut conn = Conn::new(get_opts())?;
@@ -1385,7 +1612,7 @@ mod test {
         fn should_connect_by_hostname() {
             let opts = OptsBuilder::from_opts(get_opts()).ip_or_hostname(Some("localhost"));
             let mut conn = Conn::new(opts).unwrap();
-            assert!(conn.ping());
+            conn.ping().unwrap();
         }
 
         #[test]
@@ -1641,6 +1868,130 @@ mod test {
             );
         }
         #[test]
+        fn should_report_transaction_state() {
+            let mut conn = Conn::new(get_opts()).unwrap();
+            conn.query_drop("CREATE TEMPORARY TABLE mysql.tbl(a INT) ENGINE=InnoDB")
+                .unwrap();
+            assert!(!conn.in_transaction());
+
+            let mut tx = conn.start_transaction(TxOpts::default()).unwrap();
+            assert!(tx.conn.in_transaction());
+            tx.query_drop("INSERT INTO mysql.tbl(a) VALUES (1)")
+                .unwrap();
+            // A failed statement doesn't end the transaction.
+            tx.query_drop("SELECT * FROM mysql.no_such_table")
+                .unwrap_err();
+            assert!(tx.conn.in_transaction());
+            tx.commit().unwrap();
+            assert!(!conn.in_transaction());
+
+            let tx = conn.start_transaction(TxOpts::default()).unwrap();
+            drop(tx);
+            assert!(!conn.in_transaction());
+
+            conn.query_drop("SET autocommit = 0").unwrap();
+            conn.query_drop("INSERT INTO mysql.tbl(a) VALUES (2)")
+                .unwrap();
+            assert!(conn.in_transaction());
+            conn.query_drop("ROLLBACK").unwrap();
+            assert!(!conn.in_transaction());
+            conn.query_drop("SET autocommit = 1").unwrap();
+        }
+
+        #[test]
+        fn should_retry_deadlocked_transaction() {
+            let mut conn = Conn::new(get_opts()).unwrap();
+            conn.query_drop("CREATE TEMPORARY TABLE mysql.tbl(a INT)")
+                .unwrap();
+
+            let mut attempts = 0;
+            let retry = RetryPolicy::new(1).set_backoff(Duration::from_millis(1));
+            let count = conn
+                .transaction(TxOpts::default(), retry, |tx| {
+                    attempts += 1;
+                    tx.exec_drop("INSERT INTO mysql.tbl(a) VALUES(?)", (attempts,))?;
+                    if attempts == 1 {
+                        // As if InnoDB chose this transaction as the deadlock victim.
+                        return Err(crate::Error::MySqlError(crate::MySqlError {
+                            state: "40001".into(),
+                            message: "Deadlock found when trying to get lock".into(),
+                            code: ServerError::ER_LOCK_DEADLOCK as u16,
+                        }));
+                    }
+                    tx.query_first::<usize, _>("SELECT COUNT(a) FROM mysql.tbl")
+                })
+                .unwrap();
+            assert_eq!(attempts, 2);
+            // The first attempt was rolled back.
+            assert_eq!(count, Some(1));
+            assert_eq!(
+                conn.query::<i32, _>("SELECT a FROM mysql.tbl").unwrap(),
+                vec![2]
+            );
+
+            // Other errors are not retried.
+            let mut attempts = 0;
+            let result = conn.transaction(TxOpts::default(), retry, |tx| {
+                attempts += 1;
+                tx.query_drop("SELECT * FROM mysql.no_such_table")
+            });
+            assert!(result.is_err());
+            assert_eq!(attempts, 1);
+        }
+        #[test]
+        fn should_paginate_by_key() {
+            let mut conn = Conn::new(get_opts()).unwrap();
+            conn.query_drop("CREATE TEMPORARY TABLE mysql.tbl(id INT PRIMARY KEY, a TEXT)")
+                .unwrap();
+            // Inserted out of order, with gaps in the keys.
+            conn.exec_batch(
+                "INSERT INTO mysql.tbl(id, a) VALUES (?, ?)",
+                (0..1050).rev().map(|i| (i * 3, format!("row {}", i))),
+            )
+            .unwrap();
+
+            let pages = conn
+                .paginate::<(u32, String), _>("SELECT id, a FROM mysql.tbl", (), "id", 100)
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(pages.len(), 11);
+            assert!(pages[..10].iter().all(|page| page.len() == 100));
+            assert_eq!(pages[10].len(), 50);
+
+            // Every row exactly once, in key order.
+            let ids = pages
+                .into_iter()
+                .flatten()
+                .map(|(id, _)| id)
+                .collect::<Vec<_>>();
+            assert_eq!(ids, (0..1050).map(|i| i * 3).collect::<Vec<_>>());
+
+            // The base query may filter using parameters.
+            let ids = (1..11).map(|i| (i * 3,)).collect::<Vec<_>>();
+            let expected = ids.chunks(4).map(<[_]>::to_vec).collect::<Vec<_>>();
+            let query = "SELECT id FROM mysql.tbl WHERE id >= ? AND id < ?";
+            let pages = conn
+                .paginate::<(u32,), _>(query, (3, 33), "id", 4)
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(pages, expected);
+            let query = "SELECT id FROM mysql.tbl WHERE id >= :min AND id < :max";
+            let pages = conn
+                .paginate::<(u32,), _>(query, params! { "min" => 3, "max" => 33 }, "id", 4)
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(pages, expected);
+
+            // A missing key column is reported before anything is fetched.
+            let err = conn
+                .paginate::<(String,), _>("SELECT a FROM mysql.tbl", (), "id", 10)
+                .unwrap_err();
+            assert!(matches!(err, DriverError(MissingKeyColumn(ref name)) if name == "id"));
+        }
+        #[test]
         fn should_handle_LOCAL_INFILE_with_custom_handler() {
             let mut conn = Conn::new(get_opts()).unwrap();
             conn.query_drop("CREATE TEMPORARY TABLE mysql.tbl(a TEXT)")
@@ -1961,7 +2312,7 @@ mod test {
             let opts = OptsBuilder::from_opts(get_opts())
                 .prefer_socket(false)
                 .tcp_connect_timeout(Some(::std::time::Duration::from_millis(1000)));
-            assert!(Conn::new(opts).unwrap().ping());
+            Conn::new(opts).unwrap().ping().unwrap();
 
             let opts = OptsBuilder::from_opts(get_opts())
                 .prefer_socket(false)
@@ -2018,7 +2369,7 @@ mod test {
                 .bind_address(Some(([127, 0, 0, 1], port)))
                 .tcp_connect_timeout(Some(::std::time::Duration::from_millis(1000)));
             let mut conn = Conn::new(opts).unwrap();
-            assert!(conn.ping());
+            conn.ping().unwrap();
             let debug_format: String = format!("{:?}", conn);
             let expected_1 = format!("addr: V4(127.0.0.1:{})", port);
             let expected_2 = format!("addr: 127.0.0.1:{}", port);
@@ -2080,6 +2431,282 @@ mod test {
             assert_eq!(order, &["DO 3", "DO 5", "DO 6"]);
         }
 
+        #[test]
+        fn should_use_text_protocol_for_parameterless_queries() {
+            use crate::consts::Command;
+
+            let mut conn = Conn::new(get_opts()).unwrap();
+            let prepared = |conn: &mut Conn| -> usize {
+                let status: (String, usize) = conn
+                    .query_first("SHOW SESSION STATUS LIKE 'Com_stmt_prepare'")
+                    .unwrap()
+                    .unwrap();
+                status.1
+            };
+            let before = prepared(&mut conn);
+
+            conn.query_drop("CREATE TEMPORARY TABLE mysql.tbl(a INT)")
+                .unwrap();
+            assert_eq!(conn.0.last_command, Command::COM_QUERY as u8);
+            let rows: Vec<u8> = conn.query("SELECT 1").unwrap();
+            assert_eq!(rows, vec![1]);
+            assert_eq!(conn.0.last_command, Command::COM_QUERY as u8);
+
+            // Nothing was prepared or cached on the way
+            assert_eq!(prepared(&mut conn), before);
+            assert_eq!(conn.0.stmt_cache.iter().count(), 0);
+        }
+
+        #[test]
+        fn should_map_null_to_none_and_fail_for_non_option() {
+            let mut conn = Conn::new(get_opts()).unwrap();
+
+            let rows: Vec<(u8, Option<String>)> =
+                conn.query("SELECT 1, NULL AS account_name").unwrap();
+            assert_eq!(rows, vec![(1, None)]);
+
+            let err = conn
+                .query::<(u8, String), _>("SELECT 1, NULL AS account_name")
+                .unwrap_err();
+            assert!(matches!(err, crate::Error::FromRowError(_)));
+            assert!(
+                err.to_string()
+                    .contains("unexpected NULL for non-Option field in column(s) `account_name`"),
+                "{}",
+                err
+            );
+
+            let err = conn
+                .exec_first::<String, _, _>("SELECT NULL", ())
+                .unwrap_err();
+            assert!(matches!(err, crate::Error::FromRowError(_)));
+        }
+
+        #[test]
+        fn should_fetch_a_single_value() {
+            let mut conn = Conn::new(get_opts()).unwrap();
+            conn.query_drop("CREATE TEMPORARY TABLE mysql.tbl(a INT, b TEXT)")
+                .unwrap();
+            conn.query_drop("INSERT INTO mysql.tbl VALUES (1, 'foo'), (2, 'bar'), (3, NULL)")
+                .unwrap();
+
+            let count: Option<u64> = conn.query_first("SELECT COUNT(*) FROM mysql.tbl").unwrap();
+            assert_eq!(count, Some(3));
+            let count: Option<u64> = conn
+                .exec_first("SELECT COUNT(*) FROM mysql.tbl WHERE a > ?", (1,))
+                .unwrap();
+            assert_eq!(count, Some(2));
+
+            let none: Option<String> = conn
+                .exec_first("SELECT b FROM mysql.tbl WHERE a > ?", (10,))
+                .unwrap();
+            assert_eq!(none, None);
+
+            let err = conn
+                .query_first::<u8, _>("SELECT b FROM mysql.tbl WHERE a = 1")
+                .unwrap_err();
+            assert!(matches!(err, crate::Error::FromRowError(_)), "{}", err);
+            let err = conn
+                .exec_first::<i64, _, _>("SELECT b FROM mysql.tbl WHERE a = ?", (2,))
+                .unwrap_err();
+            assert!(matches!(err, crate::Error::FromRowError(_)), "{}", err);
+
+            // The connection is still usable after a conversion error
+            let b: Option<Option<String>> = conn
+                .query_first("SELECT b FROM mysql.tbl WHERE a = 3")
+                .unwrap();
+            assert_eq!(b, Some(None));
+        }
+
+        #[test]
+        fn should_deserialize_rows_by_column_name() {
+            #[derive(Debug, PartialEq, Deserialize)]
+            struct Payment {
+                customer_id: i32,
+                amount: i32,
+                account_name: Option<String>,
+            }
+
+            let mut conn = Conn::new(get_opts()).unwrap();
+            conn.query_drop(
+                "CREATE TEMPORARY TABLE mysql.payment (
+                    customer_id INT NOT NULL,
+                    amount INT NOT NULL,
+                    account_name TEXT
+                )",
+            )
+            .unwrap();
+            conn.query_drop("INSERT INTO mysql.payment VALUES (1, 2, NULL), (3, 4, 'foo')")
+                .unwrap();
+
+            let expected = vec![
+                Payment {
+                    customer_id: 1,
+                    amount: 2,
+                    account_name: None,
+                },
+                Payment {
+                    customer_id: 3,
+                    amount: 4,
+                    account_name: Some("foo".into()),
+                },
+            ];
+            // The columns are in a different order than the fields
+            let payments: Vec<Payment> = conn
+                .query_as(
+                    "SELECT account_name, amount, customer_id FROM mysql.payment \
+                     ORDER BY customer_id",
+                )
+                .unwrap();
+            assert_eq!(payments, expected);
+            let payments: Vec<Payment> = conn
+                .exec_as(
+                    "SELECT amount, account_name, customer_id FROM mysql.payment \
+                     WHERE customer_id > ? ORDER BY customer_id",
+                    (0,),
+                )
+                .unwrap();
+            assert_eq!(payments, expected);
+
+            let err = conn
+                .query_as::<Payment, _>("SELECT 1 AS customer_id, NULL AS amount")
+                .unwrap_err();
+            assert!(err.to_string().contains("column `amount`"), "{}", err);
+        }
+
+        #[test]
+        fn should_decode_columns_by_their_charset() {
+            let mut conn = Conn::new(get_opts()).unwrap();
+            conn.query_drop("SET NAMES utf8mb4").unwrap();
+            conn.query_drop(
+                "CREATE TEMPORARY TABLE mysql.tbl (
+                    l TEXT CHARACTER SET latin1,
+                    u TEXT CHARACTER SET utf8mb4,
+                    b BLOB
+                )",
+            )
+            .unwrap();
+            conn.exec_drop(
+                "INSERT INTO mysql.tbl (l, u, b) VALUES (?, ?, ?)",
+                ("café €", "日本語 🦀", vec![0xe9u8, 0x80]),
+            )
+            .unwrap();
+
+            let expected = (
+                "café €".to_string(),
+                "日本語 🦀".to_string(),
+                vec![0xe9u8, 0x80],
+            );
+            let query = "SELECT l, u, b FROM mysql.tbl";
+            let row: Option<(String, String, Vec<u8>)> = conn.query_first(query).unwrap();
+            assert_eq!(row.as_ref(), Some(&expected));
+
+            // Now the server sends every column in the charset it is stored in
+            conn.query_drop("SET character_set_results = NULL").unwrap();
+            let row: Option<(String, String, Vec<u8>)> = conn.query_first(query).unwrap();
+            assert_eq!(row.as_ref(), Some(&expected));
+            let row: Option<(String, String, Vec<u8>)> = conn.exec_first(query, ()).unwrap();
+            assert_eq!(row, Some(expected));
+        }
+
+        #[test]
+        fn should_reject_packets_over_max_allowed_packet() {
+            use crate::DriverError::MaxAllowedPacketExceeded;
+
+            let opts = OptsBuilder::from_opts(get_opts()).max_allowed_packet(Some(1024));
+            let mut conn = Conn::new(opts).unwrap();
+            assert_eq!(conn.max_allowed_packet(), 1024);
+            conn.query_drop("CREATE TEMPORARY TABLE mysql.tbl(a BLOB)")
+                .unwrap();
+
+            let blob = vec![0u8; 4096];
+            let err = conn
+                .exec_drop("INSERT INTO mysql.tbl (a) VALUES (?)", (&blob,))
+                .unwrap_err();
+            assert!(
+                matches!(err, DriverError(MaxAllowedPacketExceeded(len, 1024)) if len > 4096),
+                "{}",
+                err
+            );
+            assert!(err.to_string().contains("max_allowed_packet"), "{}", err);
+
+            let query = format!("SELECT '{}'", "x".repeat(2048));
+            let err = conn.query_drop(query).unwrap_err();
+            assert!(
+                matches!(err, DriverError(MaxAllowedPacketExceeded(2058, 1024))),
+                "{}",
+                err
+            );
+
+            // Nothing was sent, so the connection is still in sync
+            conn.exec_drop(
+                "INSERT INTO mysql.tbl (a) VALUES (?)",
+                (blob[..10].to_vec(),),
+            )
+            .unwrap();
+            let count: Option<u8> = conn.query_first("SELECT COUNT(*) FROM mysql.tbl").unwrap();
+            assert_eq!(count, Some(1));
+        }
+
+        #[test]
+        fn should_mark_conn_broken_if_server_rejects_packet() {
+            let mut conn = Conn::new(get_opts()).unwrap();
+            let max = conn.max_allowed_packet();
+            // Let the query through so that the server is the one to reject it
+            conn.stream_mut().codec_mut().max_allowed_packet = max * 2;
+
+            let query = format!("SELECT '{}'", "x".repeat(max));
+            match conn.query_drop(query).unwrap_err() {
+                MySqlError(err) => {
+                    assert_eq!(err.code, ServerError::ER_NET_PACKET_TOO_LARGE as u16)
+                }
+                err => panic!("unexpected error: {}", err),
+            }
+
+            let err = conn.query_drop("SELECT 1").unwrap_err();
+            assert!(err.is_connectivity_error(), "{}", err);
+            assert!(conn.ping().is_err());
+
+            conn.reset().unwrap();
+            assert_eq!(conn.query_first("SELECT 1").unwrap(), Some(1u8));
+        }
+
+        #[test]
+        #[cfg(feature = "query-log")]
+        fn should_call_query_log_hook() {
+            use crate::QueryLogHook;
+            use std::sync::{Arc, Mutex};
+
+            let logged = Arc::new(Mutex::new(Vec::new()));
+            let mut conn = Conn::new(get_opts()).unwrap();
+            conn.set_query_log_hook(Some({
+                let logged = logged.clone();
+                QueryLogHook::new(move |log| {
+                    logged.lock().unwrap().push((
+                        log.query.to_string(),
+                        log.params,
+                        log.rows,
+                        log.duration,
+                    ))
+                })
+            }));
+
+            let rows: Vec<u8> = conn.query("SELECT 1 UNION ALL SELECT 2").unwrap();
+            assert_eq!(rows, vec![1, 2]);
+            let _: Option<u8> = conn.exec_first("SELECT ? + ?", (1, 2)).unwrap();
+
+            conn.set_query_log_hook(None);
+            conn.query_drop("DO 1").unwrap();
+
+            let logged = logged.lock().unwrap();
+            assert_eq!(logged.len(), 2);
+            assert_eq!(logged[0].0, "SELECT 1 UNION ALL SELECT 2");
+            assert_eq!((logged[0].1, logged[0].2), (0, 2));
+            assert_eq!(logged[1].0, "SELECT ? + ?");
+            assert_eq!((logged[1].1, logged[1].2), (2, 1));
+            assert!(logged.iter().all(|log| log.3 > std::time::Duration::ZERO));
+        }
+
         #[test]
         fn should_handle_json_columns() {
             use crate::{Deserialized, Serialized};
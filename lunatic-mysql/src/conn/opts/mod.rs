@@ -18,6 +18,9 @@ use crate::{consts::CapabilityFlags, Compression, LocalInfileHandler, UrlError};
 /// Default value for client side per-connection statement cache.
 pub const DEFAULT_STMT_CACHE_SIZE: usize = 32;
 
+/// Smallest client side `max_allowed_packet`, the same as the server's minimum.
+pub const MIN_MAX_ALLOWED_PACKET: usize = 1024;
+
 mod native_tls_opts;
 mod rustls_opts;
 
@@ -167,6 +170,14 @@ pub(crate) struct InnerOpts {
     /// Can be defined using `stmt_cache_size` connection url parameter.
     stmt_cache_size: usize,
 
+    /// Client side limit for the size of a packet sent to the server (defaults to `None`).
+    ///
+    /// The effective limit is the smaller of this value and the server's `max_allowed_packet`.
+    ///
+    /// Can be defined using `max_allowed_packet` connection url parameter.
+    /// Never smaller than [`MIN_MAX_ALLOWED_PACKET`].
+    max_allowed_packet: Option<usize>,
+
     /// If not `None`, then client will ask for compression if server supports it
     /// (defaults to `None`).
     ///
@@ -221,6 +232,7 @@ impl Default for InnerOpts {
             tcp_connect_timeout: None,
             bind_address: None,
             stmt_cache_size: DEFAULT_STMT_CACHE_SIZE,
+            max_allowed_packet: None,
             compress: None,
             additional_capabilities: CapabilityFlags::empty(),
             connect_attrs: HashMap::new(),
@@ -354,6 +366,15 @@ impl Opts {
         self.0.stmt_cache_size
     }
 
+    /// Client side limit for the size of a packet sent to the server (defaults to `None`).
+    ///
+    /// The effective limit is the smaller of this value and the server's `max_allowed_packet`.
+    ///
+    /// Can be defined using `max_allowed_packet` connection url parameter.
+    pub fn get_max_allowed_packet(&self) -> Option<usize> {
+        self.0.max_allowed_packet
+    }
+
     /// If not `None`, then client will ask for compression if server supports it
     /// (defaults to `None`).
     ///
@@ -490,7 +511,11 @@ impl OptsBuilder {
     /// - compress = Compression level(defaults to `None`)
     /// - tcp_connect_timeout_ms = Tcp connect timeout (defaults to `None`)
     /// - stmt_cache_size = Number of prepared statements cached on the client side (per connection)
+    /// - max_allowed_packet = Client side limit for the size of a sent packet, at least 1024
+    ///   (defaults to `None`)
     /// - secure_auth = Disable `mysql_old_password` auth plugin
+    /// - ssl-mode = `disabled`, `required` (encrypt without verifying the server certificate),
+    ///   `verify_ca` (verify the certificate but not the host name) or `verify_identity`
     ///
     /// Login .cnf file parsing lib <https://github.com/rjcortese/myloginrs> returns a HashMap for client configs
     ///
@@ -568,6 +593,29 @@ impl OptsBuilder {
                         return Err(UrlError::InvalidValue(key.to_string(), value.to_string()))
                     }
                 },
+                "max_allowed_packet" => match value.parse::<usize>() {
+                    Ok(parsed) if parsed >= MIN_MAX_ALLOWED_PACKET => {
+                        self.opts.0.max_allowed_packet = Some(parsed)
+                    }
+                    _ => return Err(UrlError::InvalidValue(key.to_string(), value.to_string())),
+                },
+                "ssl-mode" => {
+                    self.opts.0.ssl_opts = match value.to_ascii_lowercase().as_str() {
+                        "disabled" => None,
+                        "required" => Some(
+                            SslOpts::default()
+                                .with_danger_accept_invalid_certs(true)
+                                .with_danger_skip_domain_validation(true),
+                        ),
+                        "verify_ca" => {
+                            Some(SslOpts::default().with_danger_skip_domain_validation(true))
+                        }
+                        "verify_identity" => Some(SslOpts::default()),
+                        _ => {
+                            return Err(UrlError::InvalidValue(key.to_string(), value.to_string()))
+                        }
+                    }
+                }
                 _ => {
                     //throw an error if there is an unrecognized param
                     return Err(UrlError::UnknownParameter(key.to_string()));
@@ -729,6 +777,22 @@ impl OptsBuilder {
         self
     }
 
+    /// Client side limit for the size of a packet sent to the server (defaults to `None`).
+    ///
+    /// The effective limit is the smaller of this value and the server's `max_allowed_packet`.
+    /// Queries and parameters that don't fit are rejected with
+    /// [`DriverError::MaxAllowedPacketExceeded`](crate::DriverError::MaxAllowedPacketExceeded)
+    /// without being sent.
+    ///
+    /// Values below [`MIN_MAX_ALLOWED_PACKET`] are raised to it.
+    ///
+    /// Can be defined using `max_allowed_packet` connection url parameter.
+    pub fn max_allowed_packet(mut self, max_allowed_packet: Option<usize>) -> Self {
+        self.opts.0.max_allowed_packet =
+            max_allowed_packet.map(|max| max.max(MIN_MAX_ALLOWED_PACKET));
+        self
+    }
+
     /// If not `None`, then client will ask for compression if server supports it
     /// (defaults to `None`).
     ///
@@ -915,7 +979,7 @@ mod test {
     use mysql_common::proto::codec::Compression;
     use std::time::Duration;
 
-    use super::{InnerOpts, Opts, OptsBuilder};
+    use super::{InnerOpts, Opts, OptsBuilder, MIN_MAX_ALLOWED_PACKET};
 
     #[allow(dead_code)]
     fn assert_conn_from_url_opts_optsbuilder(url: &str, opts: Opts, opts_builder: OptsBuilder) {
@@ -976,6 +1040,21 @@ mod test {
         Opts::from_url(opts).unwrap();
     }
 
+    #[test]
+    fn should_parse_max_allowed_packet() {
+        let opts = Opts::from_url("mysql://localhost/foo?max_allowed_packet=1024").unwrap();
+        assert_eq!(opts.get_max_allowed_packet(), Some(1024));
+        assert_eq!(Opts::default().get_max_allowed_packet(), None);
+        assert!(Opts::from_url("mysql://localhost/foo?max_allowed_packet=0").is_err());
+        assert!(Opts::from_url("mysql://localhost/foo?max_allowed_packet=7").is_err());
+        assert!(Opts::from_url("mysql://localhost/foo?max_allowed_packet=1023").is_err());
+
+        let opts: Opts = OptsBuilder::new().max_allowed_packet(Some(7)).into();
+        assert_eq!(opts.get_max_allowed_packet(), Some(MIN_MAX_ALLOWED_PACKET));
+        let opts: Opts = OptsBuilder::new().max_allowed_packet(Some(4096)).into();
+        assert_eq!(opts.get_max_allowed_packet(), Some(4096));
+    }
+
     #[test]
     fn should_read_hashmap_into_opts() {
         use crate::OptsBuilder;
@@ -1062,4 +1141,35 @@ mod test {
             ))
         );
     }
+
+    #[test]
+    fn should_parse_ssl_mode_from_url() {
+        use crate::UrlError;
+
+        let opts = Opts::from_url("mysql://localhost/?ssl-mode=required").unwrap();
+        let ssl_opts = opts.get_ssl_opts().unwrap();
+        assert!(ssl_opts.accept_invalid_certs());
+        assert!(ssl_opts.skip_domain_validation());
+
+        let opts = Opts::from_url("mysql://localhost/?ssl-mode=VERIFY_CA").unwrap();
+        let ssl_opts = opts.get_ssl_opts().unwrap();
+        assert!(!ssl_opts.accept_invalid_certs());
+        assert!(ssl_opts.skip_domain_validation());
+
+        let opts = Opts::from_url("mysql://localhost/?ssl-mode=verify_identity").unwrap();
+        let ssl_opts = opts.get_ssl_opts().unwrap();
+        assert!(!ssl_opts.accept_invalid_certs());
+        assert!(!ssl_opts.skip_domain_validation());
+
+        let opts = Opts::from_url("mysql://localhost/?ssl-mode=disabled").unwrap();
+        assert!(opts.get_ssl_opts().is_none());
+
+        assert_eq!(
+            Opts::from_url("mysql://localhost/?ssl-mode=sometimes"),
+            Err(UrlError::InvalidValue(
+                "ssl-mode".to_string(),
+                "sometimes".to_string()
+            ))
+        );
+    }
 }
@@ -0,0 +1,137 @@
+// Copyright (c) 2020 rust-mysql-simple contributors
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Keyset pagination.
+//!
+//! Instead of `LIMIT n OFFSET m`, which makes the server read and discard `m` rows, every page
+//! is fetched with `WHERE key > ? ORDER BY key LIMIT n`, where `?` is the key of the last row of
+//! the previous page. With an index on the key every page costs the same.
+
+use std::marker::PhantomData;
+
+use crate::{prelude::*, Conn, DriverError, Error, Params, Result, Statement, Value};
+
+/// Name of the parameter that takes the last key if the base query uses named parameters.
+const LAST_KEY_PARAM: &str = "__page_last_key";
+
+/// Iterator over the pages of a query, created with [`Conn::paginate`].
+///
+/// Every item is a page of at most `page_size` rows, ordered by the key column. The iterator
+/// ends after the first page with fewer rows, so there is no empty page at the end unless
+/// the query returns no rows at all or their number is a multiple of `page_size`.
+#[derive(Debug)]
+pub struct Pages<'a, T> {
+    conn: &'a mut Conn,
+    first: Statement,
+    next: Statement,
+    params: Params,
+    key_column: String,
+    page_size: usize,
+    last_key: Option<Value>,
+    done: bool,
+    _row: PhantomData<T>,
+}
+
+impl<'a, T: FromRow> Pages<'a, T> {
+    pub(crate) fn new(
+        conn: &'a mut Conn,
+        query: &str,
+        params: Params,
+        key_column: &str,
+        page_size: usize,
+    ) -> Result<Self> {
+        // The server would only complain about an unknown column in `ORDER BY`.
+        let base = conn.prep(query)?;
+        if !base
+            .columns()
+            .iter()
+            .any(|column| column.name_str() == key_column)
+        {
+            return Err(Error::DriverError(DriverError::MissingKeyColumn(
+                key_column.into(),
+            )));
+        }
+        let key = format!("page.`{}`", key_column.replace('`', "``"));
+        let placeholder = match params {
+            Params::Named(_) => format!(":{}", LAST_KEY_PARAM),
+            Params::Empty | Params::Positional(_) => "?".into(),
+        };
+        let first = conn.prep(format!(
+            "SELECT * FROM ({}) AS page ORDER BY {} LIMIT {}",
+            query, key, page_size
+        ))?;
+        let next = conn.prep(format!(
+            "SELECT * FROM ({}) AS page WHERE {} > {} ORDER BY {} LIMIT {}",
+            query, key, placeholder, key, page_size
+        ))?;
+        Ok(Pages {
+            conn,
+            first,
+            next,
+            params,
+            key_column: key_column.into(),
+            page_size,
+            last_key: None,
+            done: page_size == 0,
+            _row: PhantomData,
+        })
+    }
+
+    /// Returns the parameters of the base query followed by `key`.
+    fn params_after(&self, key: Value) -> Params {
+        match self.params.clone() {
+            Params::Empty => Params::Positional(vec![key]),
+            Params::Positional(mut params) => {
+                params.push(key);
+                Params::Positional(params)
+            }
+            Params::Named(mut params) => {
+                params.insert(LAST_KEY_PARAM.into(), key);
+                Params::Named(params)
+            }
+        }
+    }
+
+    fn fetch(&mut self) -> Result<Vec<T>> {
+        let mut result = match self.last_key.take() {
+            Some(key) => {
+                let params = self.params_after(key);
+                self.conn.exec_iter(&self.next, params)?
+            }
+            None => self.conn.exec_iter(&self.first, self.params.clone())?,
+        };
+        let mut page = Vec::with_capacity(self.page_size);
+        let mut last_key = None;
+        for row in result.by_ref() {
+            let row = row?;
+            last_key = row.get::<Value, _>(&*self.key_column);
+            page.push(T::from_row_opt(row)?);
+        }
+        drop(result);
+        if page.len() < self.page_size {
+            self.done = true;
+        }
+        self.last_key = last_key;
+        Ok(page)
+    }
+}
+
+impl<T: FromRow> Iterator for Pages<'_, T> {
+    type Item = Result<Vec<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let page = self.fetch();
+        if page.is_err() {
+            self.done = true;
+        }
+        Some(page)
+    }
+}
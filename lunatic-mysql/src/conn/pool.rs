@@ -8,7 +8,7 @@
 
 use std::{
     collections::VecDeque,
-    fmt,
+    fmt, mem,
     ops::Deref,
     sync::{
         atomic::{AtomicUsize, Ordering},
@@ -17,17 +17,46 @@ use std::{
     time::{Duration, Instant},
 };
 
+use lunatic::{
+    abstract_process,
+    net::TcpStream,
+    process::{ProcessRef, StartProcess},
+};
+
 use crate::{
-    conn::query_result::{Binary, Text},
+    conn::{
+        pagination::Pages,
+        query_result::{Binary, Text},
+    },
     prelude::*,
-    Conn, DriverError, Error, LocalInfileHandler, Opts, Params, QueryResult, Result, Statement,
-    Transaction, TxOpts,
+    Conn, DriverError, Error, LocalInfileHandler, Opts, Params, QueryResult, Result, RetryPolicy,
+    Statement, Transaction, TxOpts,
 };
 
+#[derive(Debug)]
+struct IdleConn {
+    conn: Conn,
+    since: Instant,
+    /// The key the socket of the connection is held by the reaper with, if it is parked.
+    parked: Option<u64>,
+}
+
+impl IdleConn {
+    fn new(conn: Conn) -> IdleConn {
+        IdleConn {
+            conn,
+            since: Instant::now(),
+            parked: None,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct InnerPool {
     opts: Opts,
-    pool: VecDeque<Conn>,
+    pool: VecDeque<IdleConn>,
+    reaper: Option<ProcessRef<Reaper>>,
+    next_key: u64,
 }
 
 impl InnerPool {
@@ -38,21 +67,158 @@ impl InnerPool {
         let mut pool = InnerPool {
             opts,
             pool: VecDeque::with_capacity(max),
+            reaper: None,
+            next_key: 0,
         };
         for _ in 0..min {
             pool.new_conn()?;
         }
         Ok(pool)
     }
+    /// Drops at most `limit` connections that stayed in the pool longer than `max_idle`
+    /// and returns how many were dropped.
+    ///
+    /// Parked connections are left to the reaper.
+    fn reap(&mut self, max_idle: Duration, limit: usize) -> usize {
+        let mut reaped = 0;
+        self.pool.retain(|idle| {
+            if reaped < limit && idle.parked.is_none() && idle.since.elapsed() > max_idle {
+                reaped += 1;
+                false
+            } else {
+                true
+            }
+        });
+        reaped
+    }
     fn new_conn(&mut self) -> Result<()> {
         match Conn::new(self.opts.clone()) {
             Ok(conn) => {
-                self.pool.push_back(conn);
+                self.put(conn);
                 Ok(())
             }
             Err(err) => Err(err),
         }
     }
+    /// Puts an idle connection into the pool, handing its socket to the reaper if there is one.
+    fn put(&mut self, conn: Conn) {
+        let mut idle = IdleConn::new(conn);
+        self.park(&mut idle);
+        self.pool.push_back(idle);
+    }
+    fn park(&mut self, idle: &mut IdleConn) {
+        if let (Some(reaper), None) = (&self.reaper, idle.parked) {
+            if let Some(socket) = idle.conn.park() {
+                reaper.park((self.next_key, socket));
+                idle.parked = Some(self.next_key);
+                self.next_key += 1;
+            }
+        }
+    }
+    /// Takes the connection out of `idle`, or returns `None` if the reaper closed it.
+    fn take(&mut self, idle: IdleConn) -> Option<Conn> {
+        let mut conn = idle.conn;
+        if let (Some(reaper), Some(key)) = (&self.reaper, idle.parked) {
+            conn.unpark(reaper.unpark(key)?);
+        }
+        Some(conn)
+    }
+}
+
+impl Drop for InnerPool {
+    fn drop(&mut self) {
+        // The sockets of the parked connections are closed with the reaper.
+        if let Some(reaper) = self.reaper.take() {
+            reaper.shutdown();
+        }
+    }
+}
+
+/// The process holding the sockets of idle pooled connections, see [`Pool::max_idle`].
+///
+/// A `Conn` can't be sent to another process, but while it sits in the pool its socket
+/// can. The reaper wakes up regularly and closes the sockets that were parked for longer
+/// than `max_idle`, keeping at least `min` of them.
+struct Reaper {
+    this: ProcessRef<Reaper>,
+    max_idle: Option<Duration>,
+    min: usize,
+    ticking: bool,
+    /// The parked sockets, oldest first.
+    sockets: VecDeque<(u64, TcpStream, Instant)>,
+}
+
+#[abstract_process]
+impl Reaper {
+    #[init]
+    fn init(this: ProcessRef<Self>, arg: (Option<Duration>, usize)) -> Self {
+        let (max_idle, min) = arg;
+        let mut reaper = Reaper {
+            this,
+            max_idle: None,
+            min,
+            ticking: false,
+            sockets: VecDeque::new(),
+        };
+        reaper.set_max_idle(max_idle);
+        reaper
+    }
+
+    #[handle_message]
+    fn set_max_idle(&mut self, max_idle: Option<Duration>) {
+        self.max_idle = max_idle;
+        if max_idle.is_some() && !self.ticking {
+            self.ticking = true;
+            self.schedule();
+        }
+    }
+
+    #[handle_message]
+    fn park(&mut self, arg: (u64, TcpStream)) {
+        let (key, socket) = arg;
+        self.sockets.push_back((key, socket, Instant::now()));
+    }
+
+    /// Returns the socket parked with `key`, or `None` if it was closed.
+    #[handle_request]
+    fn unpark(&mut self, key: u64) -> Option<TcpStream> {
+        let i = self
+            .sockets
+            .iter()
+            .position(|(parked, ..)| *parked == key)?;
+        self.sockets.remove(i).map(|(_, socket, _)| socket)
+    }
+
+    #[handle_message]
+    fn reap(&mut self) {
+        let max_idle = match self.max_idle {
+            Some(max_idle) => max_idle,
+            None => {
+                self.ticking = false;
+                return;
+            }
+        };
+        while self.sockets.len() > self.min {
+            match self.sockets.front() {
+                Some((_, _, since)) if since.elapsed() > max_idle => {
+                    self.sockets.pop_front();
+                }
+                _ => break,
+            }
+        }
+        self.schedule();
+    }
+}
+
+impl Reaper {
+    /// Wakes the reaper up again after half of `max_idle`, so that no socket stays parked
+    /// for much longer than that.
+    fn schedule(&self) {
+        if let Some(max_idle) = self.max_idle {
+            let interval = (max_idle / 2).max(Duration::from_millis(1));
+            self.this.after(interval).reap();
+        }
+    }
 }
 
 struct ArcedPool {
@@ -102,6 +268,7 @@ pub struct Pool {
     arced_pool: Arc<ArcedPool>,
     check_health: bool,
     use_cache: bool,
+    max_idle: Option<Duration>,
 }
 
 impl Pool {
@@ -128,13 +295,17 @@ impl Pool {
             if let Some(query) = stmt {
                 let mut id = None;
                 let mut pool = inner_pool.lock()?;
-                for (i, conn) in pool.pool.iter().rev().enumerate() {
-                    if conn.has_stmt(query.as_ref()) {
+                self.reap_locked(&mut pool);
+                for (i, idle) in pool.pool.iter().rev().enumerate() {
+                    if idle.conn.has_stmt(query.as_ref()) {
                         id = Some(i);
                         break;
                     }
                 }
-                id.and_then(|id| pool.pool.swap_remove_back(id))
+                match id.and_then(|id| pool.pool.swap_remove_back(id)) {
+                    Some(idle) => self.take_locked(&mut pool, idle),
+                    None => None,
+                }
             } else {
                 None
             }
@@ -146,10 +317,13 @@ impl Pool {
             conn
         } else {
             let mut pool = inner_pool.lock()?;
+            self.reap_locked(&mut pool);
             loop {
-                if let Some(conn) = pool.pool.pop_front() {
-                    drop(pool);
-                    break conn;
+                if let Some(idle) = pool.pool.pop_front() {
+                    if let Some(conn) = self.take_locked(&mut pool, idle) {
+                        drop(pool);
+                        break conn;
+                    }
                 } else if self.arced_pool.count.load(Ordering::Relaxed) < self.arced_pool.max {
                     pool.new_conn()?;
                     self.arced_pool.count.fetch_add(1, Ordering::SeqCst);
@@ -166,7 +340,7 @@ impl Pool {
             }
         };
 
-        if call_ping && self.check_health && !conn.ping() {
+        if call_ping && self.check_health && conn.ping().is_err() {
             if let Err(err) = conn.reset() {
                 self.arced_pool.count.fetch_sub(1, Ordering::SeqCst);
                 return Err(err);
@@ -204,6 +378,7 @@ impl Pool {
             }),
             use_cache: true,
             check_health: true,
+            max_idle: None,
         })
     }
 
@@ -218,6 +393,58 @@ impl Pool {
         self.check_health = check_health;
     }
 
+    /// Closes connections that stayed unused in the pool for longer than `max_idle`
+    /// (off by default).
+    ///
+    /// Set it below the server's `wait_timeout` so that the pool never hands out a connection
+    /// the server has already closed. The pool never shrinks below `min` connections, the
+    /// over-idle ones it keeps are only revived by the health check (see
+    /// [`Pool::check_health`]).
+    ///
+    /// The first call starts a reaper process linked to this one. While a connection is idle
+    /// its socket is held by the reaper, which closes it in the background once it is over
+    /// the limit, so a pool that sits unused doesn't keep connections the server is about to
+    /// drop. TLS connections can't be handed over and are closed when a connection is
+    /// requested instead.
+    pub fn max_idle(&mut self, max_idle: Option<Duration>) {
+        self.max_idle = max_idle;
+        let mut pool = self.arced_pool.inner.0.lock().unwrap();
+        match pool.reaper {
+            Some(ref reaper) => reaper.set_max_idle(max_idle),
+            None if max_idle.is_some() => {
+                pool.reaper = Some(Reaper::start_link((max_idle, self.arced_pool.min), None));
+                let mut idle = mem::take(&mut pool.pool);
+                for conn in idle.iter_mut() {
+                    pool.park(conn);
+                }
+                pool.pool = idle;
+            }
+            None => (),
+        }
+    }
+
+    /// Takes the connection out of `idle`, forgetting it if the reaper closed it.
+    fn take_locked(&self, pool: &mut InnerPool, idle: IdleConn) -> Option<Conn> {
+        let conn = pool.take(idle);
+        if conn.is_none() {
+            self.arced_pool.count.fetch_sub(1, Ordering::SeqCst);
+        }
+        conn
+    }
+
+    fn reap_locked(&self, pool: &mut InnerPool) -> usize {
+        let count = self.arced_pool.count.load(Ordering::Relaxed);
+        let reaped = match self.max_idle {
+            Some(max_idle) => pool.reap(max_idle, count.saturating_sub(self.arced_pool.min)),
+            None => 0,
+        };
+        if reaped > 0 {
+            self.arced_pool.count.fetch_sub(reaped, Ordering::SeqCst);
+            self.arced_pool.inner.1.notify_all();
+        }
+        reaped
+    }
+
     /// Gives you a [`PooledConn`](struct.PooledConn.html).
     ///
     /// `Pool` will check that connection is alive via
@@ -237,6 +464,14 @@ impl Pool {
         self._get_conn(None::<String>, Some(timeout_ms), true)
     }
 
+    /// Shortcut for `pool.get_conn()?.transaction(..)`.
+    pub fn transaction<T, F>(&self, tx_opts: TxOpts, retry: RetryPolicy, f: F) -> Result<T>
+    where
+        F: FnMut(&mut Transaction<'_>) -> Result<T>,
+    {
+        self.get_conn()?.transaction(tx_opts, retry, f)
+    }
+
     /// Shortcut for `pool.get_conn()?.start_transaction(..)`.
     pub fn start_transaction(&self, tx_opts: TxOpts) -> Result<Transaction<'static>> {
         let conn = self._get_conn(None::<String>, None, false)?;
@@ -318,8 +553,10 @@ impl Drop for PooledConn {
             self.pool.arced_pool.count.fetch_sub(1, Ordering::SeqCst);
         } else {
             self.conn.as_mut().unwrap().set_local_infile_handler(None);
+            #[cfg(feature = "query-log")]
+            self.conn.as_mut().unwrap().set_query_log_hook(None);
             let mut pool = (self.pool.arced_pool.inner).0.lock().unwrap();
-            pool.pool.push_back(self.conn.take().unwrap());
+            pool.put(self.conn.take().unwrap());
             drop(pool);
             (self.pool.arced_pool.inner).1.notify_one();
         }
@@ -333,6 +570,30 @@ impl PooledConn {
         self.conn.as_mut().unwrap().start_transaction(tx_opts)
     }
 
+    /// Redirects to
+    /// [`Conn#paginate`](struct.Conn.html#method.paginate)
+    pub fn paginate<T: FromRow, P: Into<Params>>(
+        &mut self,
+        query: &str,
+        params: P,
+        key_column: &str,
+        page_size: usize,
+    ) -> Result<Pages<'_, T>> {
+        self.conn
+            .as_mut()
+            .unwrap()
+            .paginate(query, params, key_column, page_size)
+    }
+
+    /// Redirects to
+    /// [`Conn#transaction`](struct.Conn.html#method.transaction)
+    pub fn transaction<T, F>(&mut self, tx_opts: TxOpts, retry: RetryPolicy, f: F) -> Result<T>
+    where
+        F: FnMut(&mut Transaction<'_>) -> Result<T>,
+    {
+        self.conn.as_mut().unwrap().transaction(tx_opts, retry, f)
+    }
+
     /// Turns this connection into a binlog stream (see [`Conn::get_binlog_stream`]).
     pub fn get_binlog_stream(
         mut self,
@@ -372,6 +633,14 @@ impl PooledConn {
             .unwrap()
             .set_local_infile_handler(handler);
     }
+
+    /// Sets a query log hook for this pooled connection. Destructor will remove it before
+    /// returning connection to a pool.
+    /// See [`Conn::set_query_log_hook`](struct.Conn.html#method.set_query_log_hook).
+    #[cfg(feature = "query-log")]
+    pub fn set_query_log_hook(&mut self, hook: Option<crate::QueryLogHook>) {
+        self.conn.as_mut().unwrap().set_query_log_hook(hook);
+    }
 }
 
 impl Queryable for PooledConn {
@@ -399,12 +668,57 @@ impl Queryable for PooledConn {
 #[cfg(test)]
 #[allow(non_snake_case)]
 mod test {
+    mod reaper {
+        use std::{io::Read, time::Duration};
+
+        use lunatic::{
+            net::{TcpListener, TcpStream},
+            process::StartProcess,
+        };
+
+        use super::super::{Reaper, ReaperHandler};
+
+        #[test]
+        fn should_close_sockets_idle_too_long() {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+            let mut servers = Vec::new();
+            let reaper = Reaper::start_link((Some(Duration::from_millis(50)), 1), None);
+            for key in 0..2 {
+                reaper.park((key, TcpStream::connect(addr).unwrap()));
+                servers.push(listener.accept().unwrap().0);
+            }
+
+            lunatic::sleep(Duration::from_millis(200));
+            // The older socket is closed, the other one is kept for `min`.
+            let mut buf = [0; 1];
+            servers[0]
+                .set_read_timeout(Some(Duration::from_secs(1)))
+                .unwrap();
+            assert_eq!(servers[0].read(&mut buf).unwrap(), 0);
+            assert!(reaper.unpark(0).is_none());
+            assert!(reaper.unpark(1).is_some());
+        }
+
+        #[test]
+        fn should_keep_sockets_without_max_idle() {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+            let reaper = Reaper::start_link((Some(Duration::from_millis(50)), 0), None);
+            reaper.set_max_idle(None);
+            reaper.park((0, TcpStream::connect(addr).unwrap()));
+
+            lunatic::sleep(Duration::from_millis(200));
+            assert!(reaper.unpark(0).is_some());
+        }
+    }
+
     mod pool {
-        use std::{thread, time::Duration};
+        use std::{collections::HashSet, thread, time::Duration};
 
         use crate::{
-            from_value, prelude::*, test_misc::get_opts, DriverError, Error, OptsBuilder, Pool,
-            TxOpts,
+            from_value, prelude::*, test_misc::get_opts, Conn, DriverError, Error, OptsBuilder,
+            Pool, TxOpts,
         };
 
         #[test]
@@ -648,6 +962,38 @@ mod test {
             Ok(())
         }
 
+        #[test]
+        fn should_recycle_idle_connections() -> crate::Result<()> {
+            let mut pool = Pool::new_manual(1, 3, get_opts())?;
+            pool.max_idle(Some(Duration::from_millis(100)));
+            let get_ids = |pool: &Pool| -> crate::Result<HashSet<u32>> {
+                let conns = (0..3)
+                    .map(|_| pool.get_conn())
+                    .collect::<crate::Result<Vec<_>>>()?;
+                Ok(conns.iter().map(|conn| conn.connection_id()).collect())
+            };
+            let ids = get_ids(&pool)?;
+            assert_eq!(ids.len(), 3);
+            assert_eq!(get_ids(&pool)?, ids);
+
+            // The reaper closes the connections above `min` without the pool being used.
+            thread::sleep(Duration::from_millis(300));
+            let mut conn = Conn::new(get_opts())?;
+            let open: usize = conn
+                .query_first(format!(
+                    "SELECT COUNT(*) FROM information_schema.PROCESSLIST WHERE ID IN ({})",
+                    ids.iter().map(u32::to_string).collect::<Vec<_>>().join(",")
+                ))?
+                .unwrap();
+            assert_eq!(open, 1);
+
+            let new_ids = get_ids(&pool)?;
+            assert_eq!(new_ids.len(), 3);
+            assert_eq!(new_ids.intersection(&ids).count(), 1);
+
+            Ok(())
+        }
+
         #[test]
         fn should_start_transaction_on_PooledConn() {
             let pool = Pool::new(get_opts()).unwrap();
@@ -0,0 +1,171 @@
+// Copyright (c) 2020 rust-mysql-simple contributors
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+pub(crate) type QueryLogInner = Arc<Mutex<dyn FnMut(&QueryLog<'_>) + Send>>;
+
+/// A record of a finished query, passed to a [`QueryLogHook`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryLog<'a> {
+    /// The SQL text as given to `query_iter` or `prep`.
+    pub query: &'a str,
+    /// Number of parameters sent with the statement. Always `0` for text queries.
+    pub params: usize,
+    /// Number of rows returned, summed over all result sets.
+    pub rows: u64,
+    /// Number of rows affected, summed over all result sets.
+    pub affected_rows: u64,
+    /// Time from sending the query until its last result set was consumed.
+    pub duration: Duration,
+}
+
+/// Callback called once for every query run on a connection.
+///
+/// The hook is called when the `QueryResult` of a query is dropped, so the row
+/// count and the duration include reading all the rows. Queries that fail before
+/// the server sends a result are not logged.
+///
+/// Only available with the `query-log` feature.
+///
+/// ```rust
+/// # mysql::doctest_wrapper!(__result, {
+/// # use mysql::*;
+/// # use mysql::prelude::*;
+/// # let mut conn = Conn::new(get_opts())?;
+/// conn.set_query_log_hook(Some(QueryLogHook::new(|log| {
+///     if log.duration.as_millis() > 100 {
+///         println!("slow query ({:?}, {} rows): {}", log.duration, log.rows, log.query);
+///     }
+/// })));
+///
+/// conn.query_drop("DO SLEEP(0.2)")?;
+/// # });
+/// ```
+#[derive(Clone)]
+pub struct QueryLogHook(pub(crate) QueryLogInner);
+
+impl QueryLogHook {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: FnMut(&QueryLog<'_>) + Send + 'static,
+    {
+        QueryLogHook(Arc::new(Mutex::new(f)))
+    }
+
+    fn call(&self, log: &QueryLog<'_>) {
+        // A hook that panicked once is still called, the log is best effort.
+        let mut hook = match self.0.lock() {
+            Ok(hook) => hook,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        (&mut *hook)(log)
+    }
+}
+
+impl PartialEq for QueryLogHook {
+    fn eq(&self, other: &QueryLogHook) -> bool {
+        (&*self.0 as *const _) == (&*other.0 as *const _)
+    }
+}
+
+impl Eq for QueryLogHook {}
+
+impl fmt::Debug for QueryLogHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "QueryLogHook(...)")
+    }
+}
+
+/// A query whose result is still being read.
+#[derive(Debug)]
+pub(crate) struct PendingQueryLog {
+    hook: QueryLogHook,
+    query: String,
+    params: usize,
+    rows: u64,
+    affected_rows: u64,
+    started: Instant,
+}
+
+impl PendingQueryLog {
+    pub(crate) fn new(hook: QueryLogHook, query: &str, params: usize, started: Instant) -> Self {
+        PendingQueryLog {
+            hook,
+            query: query.into(),
+            params,
+            rows: 0,
+            affected_rows: 0,
+            started,
+        }
+    }
+
+    pub(crate) fn add_row(&mut self) {
+        self.rows += 1;
+    }
+
+    pub(crate) fn add_affected_rows(&mut self, n: u64) {
+        self.affected_rows += n;
+    }
+
+    pub(crate) fn finish(self) {
+        self.hook.call(&QueryLog {
+            query: &self.query,
+            params: self.params,
+            rows: self.rows,
+            affected_rows: self.affected_rows,
+            duration: self.started.elapsed(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{Arc, Mutex},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::{PendingQueryLog, QueryLogHook};
+
+    #[test]
+    fn should_report_counts_and_duration() {
+        let logged = Arc::new(Mutex::new(Vec::new()));
+        let hook = {
+            let logged = logged.clone();
+            QueryLogHook::new(move |log| {
+                logged.lock().unwrap().push((
+                    log.query.to_string(),
+                    log.params,
+                    log.rows,
+                    log.affected_rows,
+                    log.duration,
+                ))
+            })
+        };
+
+        let mut pending = PendingQueryLog::new(hook, "SELECT ?, ?", 2, Instant::now());
+        thread::sleep(Duration::from_millis(5));
+        pending.add_row();
+        pending.add_row();
+        pending.add_affected_rows(3);
+        pending.finish();
+
+        let logged = logged.lock().unwrap();
+        assert_eq!(logged.len(), 1);
+        let (ref query, params, rows, affected_rows, duration) = logged[0];
+        assert_eq!(query, "SELECT ?, ?");
+        assert_eq!((params, rows, affected_rows), (2, 2, 3));
+        assert!(duration >= Duration::from_millis(5));
+    }
+}
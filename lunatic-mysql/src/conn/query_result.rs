@@ -108,6 +108,8 @@ pub struct QueryResult<'c, 't, 'tc, T: crate::prelude::Protocol> {
     state: SetIteratorState,
     set_index: usize,
     protocol: PhantomData<T>,
+    #[cfg(feature = "query-log")]
+    log: Option<crate::conn::query_log::PendingQueryLog>,
 }
 
 impl<'c, 't, 'tc, T: crate::prelude::Protocol> QueryResult<'c, 't, 'tc, T> {
@@ -120,6 +122,24 @@ impl<'c, 't, 'tc, T: crate::prelude::Protocol> QueryResult<'c, 't, 'tc, T> {
             state,
             set_index: 0,
             protocol: PhantomData,
+            #[cfg(feature = "query-log")]
+            log: None,
+        }
+    }
+
+    /// Reports this result to the query log hook once it is dropped.
+    #[cfg(feature = "query-log")]
+    pub(crate) fn with_log(mut self, log: crate::conn::query_log::PendingQueryLog) -> Self {
+        self.log = Some(log);
+        self.log_affected_rows();
+        self
+    }
+
+    #[cfg(feature = "query-log")]
+    fn log_affected_rows(&mut self) {
+        let affected_rows = self.affected_rows();
+        if let Some(ref mut log) = self.log {
+            log.add_affected_rows(affected_rows);
         }
     }
 
@@ -147,6 +167,8 @@ impl<'c, 't, 'tc, T: crate::prelude::Protocol> QueryResult<'c, 't, 'tc, T> {
                 Err(err) => self.state = err.into(),
             }
             self.set_index += 1;
+            #[cfg(feature = "query-log")]
+            self.log_affected_rows();
         } else {
             self.state = SetIteratorState::Done;
         }
@@ -291,6 +313,10 @@ impl<'c, 't, 'tc, T: crate::prelude::Protocol> QueryResult<'c, 't, 'tc, T> {
 impl<'c, 't, 'tc, T: crate::prelude::Protocol> Drop for QueryResult<'c, 't, 'tc, T> {
     fn drop(&mut self) {
         while self.iter().is_some() {}
+        #[cfg(feature = "query-log")]
+        if let Some(log) = self.log.take() {
+            log.finish();
+        }
     }
 }
 
@@ -332,6 +358,10 @@ impl<T: crate::prelude::Protocol> Iterator for QueryResult<'_, '_, '_, T> {
             InSet(cols) => match T::next(&mut *self.conn, cols.clone()) {
                 Ok(Some(row)) => {
                     self.state = InSet(cols);
+                    #[cfg(feature = "query-log")]
+                    if let Some(ref mut log) = self.log {
+                        log.add_row();
+                    }
                     Some(Ok(row))
                 }
                 Ok(None) => {
@@ -7,16 +7,27 @@
 // modified, or distributed except according to those terms.
 
 use mysql_common::row::convert::FromRowError;
+use serde::de::DeserializeOwned;
 
 use std::{borrow::Cow, result::Result as StdResult};
 
 use crate::{
-    conn::query_result::{Binary, Text},
-    from_row, from_row_opt,
+    conn::{
+        de::deserialize_row,
+        query_result::{Binary, Text},
+    },
+    from_row_opt,
     prelude::FromRow,
-    Params, QueryResult, Result, Statement,
+    Error, Params, QueryResult, Result, Row, Statement,
 };
 
+/// Converts a row like `from_row`, but returns an error instead of panicking
+/// if the row does not fit `T`, e.g. because of a NULL in a column that is
+/// not read into an `Option`.
+fn convert_row<T: FromRow>(row: Row) -> Result<T> {
+    from_row_opt(row).map_err(Error::from)
+}
+
 /// Something, that eventually is a `Statement` in the context of a `T: Queryable`.
 pub trait AsStatement {
     /// Make a statement out of `Self`.
@@ -26,15 +37,23 @@ pub trait AsStatement {
 /// Queryable object.
 pub trait Queryable {
     /// Perfoms text query.
+    ///
+    /// The query is sent as is with `COM_QUERY`, so unlike the `exec*`
+    /// methods it takes a single round trip and does not prepare or cache
+    /// a statement.  Use it for queries without parameters.
     fn query_iter<Q: AsRef<str>>(&mut self, query: Q) -> Result<QueryResult<'_, '_, '_, Text>>;
 
     /// Performs text query and collects the first result set.
+    ///
+    /// A row that does not convert into `T`, e.g. because of a NULL in a
+    /// column that is not read into an `Option`, fails with
+    /// [`Error::FromRowError`].  This holds for all the methods that convert rows.
     fn query<T, Q>(&mut self, query: Q) -> Result<Vec<T>>
     where
         Q: AsRef<str>,
         T: FromRow,
     {
-        self.query_map(query, from_row)
+        self.query_map(query, |row: T| row)
     }
 
     /// Same as [`Queryable::query`] but useful when you not sure what your schema is.
@@ -47,6 +66,23 @@ pub trait Queryable {
     }
 
     /// Performs text query and returns the first row of the first result set.
+    ///
+    /// With a scalar `T` this fetches a single value, `None` means there was no row:
+    ///
+    /// ```
+    /// # mysql::doctest_wrapper!(__result, {
+    /// use mysql::*;
+    /// use mysql::prelude::*;
+    ///
+    /// let mut conn = Conn::new(get_opts())?;
+    /// let count: Option<u64> = conn.query_first("SELECT COUNT(*) FROM mysql.user")?;
+    /// assert!(count.unwrap() > 0);
+    /// let none: Option<u64> = conn.query_first("SELECT 1 FROM mysql.user WHERE FALSE")?;
+    /// assert_eq!(none, None);
+    /// # });
+    /// ```
+    ///
+    /// A row that does not fit `T` fails with [`Error::FromRowError`].
     fn query_first<T, Q>(&mut self, query: Q) -> Result<Option<T>>
     where
         Q: AsRef<str>,
@@ -54,7 +90,7 @@ pub trait Queryable {
     {
         self.query_iter(query)?
             .next()
-            .map(|row| row.map(from_row))
+            .map(|row| row.and_then(convert_row))
             .transpose()
     }
 
@@ -104,7 +140,7 @@ pub trait Queryable {
         F: FnMut(U, T) -> U,
     {
         self.query_iter(query)?
-            .map(|row| row.map(from_row::<T>))
+            .map(|row| row.and_then(convert_row::<T>))
             .try_fold(init, |acc, row: Result<T>| row.map(|row| f(acc, row)))
     }
 
@@ -122,6 +158,44 @@ pub trait Queryable {
             })
     }
 
+    /// Performs text query and deserializes each row of the first result set
+    /// into `T`.
+    ///
+    /// Structs are filled by column name, regardless of the order of the
+    /// columns in the query:
+    ///
+    /// ```
+    /// # mysql::doctest_wrapper!(__result, {
+    /// use mysql::*;
+    /// use mysql::prelude::*;
+    /// use mysql::serde::Deserialize;
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// struct Payment {
+    ///     customer_id: i32,
+    ///     amount: i32,
+    ///     account_name: Option<String>,
+    /// }
+    ///
+    /// let mut conn = Conn::new(get_opts())?;
+    /// let payments: Vec<Payment> =
+    ///     conn.query_as("SELECT NULL AS account_name, 2 AS amount, 1 AS customer_id")?;
+    /// assert_eq!(payments[0].customer_id, 1);
+    /// # });
+    /// ```
+    ///
+    /// A row that does not fit `T` fails with
+    /// [`DriverError::RowDeserializationFailed`](crate::DriverError::RowDeserializationFailed).
+    fn query_as<T, Q>(&mut self, query: Q) -> Result<Vec<T>>
+    where
+        Q: AsRef<str>,
+        T: DeserializeOwned,
+    {
+        self.query_iter(query)?
+            .map(|row| row.and_then(deserialize_row))
+            .collect()
+    }
+
     /// Performs text query and drops the query result.
     fn query_drop<Q>(&mut self, query: Q) -> Result<()>
     where
@@ -165,7 +239,7 @@ pub trait Queryable {
         P: Into<Params>,
         T: FromRow,
     {
-        self.exec_map(stmt, params, from_row)
+        self.exec_map(stmt, params, |row: T| row)
     }
 
     /// Same as [`Queryable::exec`] but useful when you not sure what your schema is.
@@ -179,6 +253,8 @@ pub trait Queryable {
     }
 
     /// Executes the given `stmt` and returns the first row of the first result set.
+    ///
+    /// See [`Queryable::query_first`] for fetching a single value.
     fn exec_first<T, S, P>(&mut self, stmt: S, params: P) -> Result<Option<T>>
     where
         S: AsStatement,
@@ -187,7 +263,7 @@ pub trait Queryable {
     {
         self.exec_iter(stmt, params)?
             .next()
-            .map(|row| row.map(crate::from_row))
+            .map(|row| row.and_then(convert_row))
             .transpose()
     }
 
@@ -245,7 +321,9 @@ pub trait Queryable {
         F: FnMut(U, T) -> U,
     {
         let mut result = self.exec_iter(stmt, params)?;
-        result.try_fold(init, |init, row| row.map(|row| f(init, from_row(row))))
+        result.try_fold(init, |init, row| {
+            row.and_then(convert_row).map(|row| f(init, row))
+        })
     }
 
     /// Same as [`Queryable::exec_fold`] but useful when you not sure what your schema is.
@@ -260,6 +338,19 @@ pub trait Queryable {
         result.try_fold(init, |init, row| row.map(|row| f(init, from_row_opt(row))))
     }
 
+    /// Executes the given `stmt` and deserializes each row of the first result
+    /// set into `T`, see [`Queryable::query_as`].
+    fn exec_as<T, S, P>(&mut self, stmt: S, params: P) -> Result<Vec<T>>
+    where
+        S: AsStatement,
+        P: Into<Params>,
+        T: DeserializeOwned,
+    {
+        self.exec_iter(stmt, params)?
+            .map(|row| row.and_then(deserialize_row))
+            .collect()
+    }
+
     /// Executes the given `stmt` and drops the result.
     fn exec_drop<S, P>(&mut self, stmt: S, params: P) -> Result<()>
     where
@@ -18,6 +18,8 @@ pub struct InnerStmt {
     params: Option<Vec<Column>>,
     stmt_packet: StmtPacket,
     connection_id: u32,
+    #[cfg(feature = "query-log")]
+    query: Arc<str>,
 }
 
 impl<'de> MyDeserialize<'de> for InnerStmt {
@@ -32,6 +34,8 @@ impl<'de> MyDeserialize<'de> for InnerStmt {
             params: None,
             stmt_packet,
             connection_id,
+            #[cfg(feature = "query-log")]
+            query: "".into(),
         })
     }
 }
@@ -47,6 +51,18 @@ impl InnerStmt {
         self
     }
 
+    #[cfg(feature = "query-log")]
+    pub fn with_query(mut self, query: &str) -> Self {
+        self.query = query.into();
+        self
+    }
+
+    /// The SQL text this statement was prepared from.
+    #[cfg(feature = "query-log")]
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
     pub fn columns(&self) -> &[Column] {
         self.columns.as_ref().map(AsRef::as_ref).unwrap_or(&[])
     }
@@ -8,7 +8,7 @@
 
 use mysql_common::packets::OkPacket;
 
-use std::{borrow::Cow, fmt};
+use std::{borrow::Cow, fmt, time::Duration};
 
 use crate::{
     conn::{
@@ -16,7 +16,7 @@ use crate::{
         ConnMut,
     },
     prelude::*,
-    LocalInfileHandler, Params, QueryResult, Result, Statement,
+    Error, LocalInfileHandler, Params, QueryResult, Result, ServerError, Statement,
 };
 
 /// MySql transaction options.
@@ -62,6 +62,109 @@ impl TxOpts {
     }
 }
 
+/// Retry policy for transactions run with `Conn::transaction` and friends.
+///
+/// InnoDB resolves a deadlock by rolling back one of the transactions involved
+/// (`ER_LOCK_DEADLOCK`, error 1213), and gives up waiting for a lock after
+/// `innodb_lock_wait_timeout` (`ER_LOCK_WAIT_TIMEOUT`, error 1205). Both are
+/// transient, so a transaction that failed with one of them may succeed if it is
+/// run again. Other errors are never retried.
+///
+/// The default policy does not retry.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// # use mysql::RetryPolicy;
+/// // Up to three more attempts, after 10ms, 20ms and 40ms.
+/// let policy = RetryPolicy::new(3).set_backoff(Duration::from_millis(10));
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Creates a policy that runs a transaction up to `max_retries` more times.
+    pub fn new(max_retries: u32) -> Self {
+        RetryPolicy {
+            max_retries,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the maximum number of retries.
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    /// Returns the delay before the first retry.
+    pub fn backoff(&self) -> Duration {
+        self.backoff
+    }
+
+    /// Returns the maximum delay between two attempts.
+    pub fn max_backoff(&self) -> Duration {
+        self.max_backoff
+    }
+
+    /// Defines the delay before the first retry (defaults to 10ms).
+    ///
+    /// The delay is doubled after every retry, up to `max_backoff`.
+    pub fn set_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Defines the maximum delay between two attempts (defaults to 1s).
+    pub fn set_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Returns `true` if the error is a deadlock or a lock wait timeout.
+    pub fn is_retryable(err: &Error) -> bool {
+        match err {
+            Error::MySqlError(err) => {
+                err.code == ServerError::ER_LOCK_DEADLOCK as u16
+                    || err.code == ServerError::ER_LOCK_WAIT_TIMEOUT as u16
+            }
+            _ => false,
+        }
+    }
+
+    /// Calls `attempt` until it succeeds, fails with an error that is not
+    /// retryable, or the retries are used up.
+    pub(crate) fn run<T, F>(&self, mut attempt: F) -> Result<T>
+    where
+        F: FnMut() -> Result<T>,
+    {
+        let mut backoff = self.backoff;
+        let mut retries = 0;
+        loop {
+            match attempt() {
+                Err(ref err) if retries < self.max_retries && Self::is_retryable(err) => {
+                    lunatic::sleep(backoff);
+                    backoff = std::cmp::min(backoff * 2, self.max_backoff);
+                    retries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 0,
+            backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
 /// MySql transaction access mode.
 #[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
 #[repr(u8)]
@@ -200,3 +303,66 @@ impl<'a> Drop for Transaction<'a> {
         self.conn.0.local_infile_handler = self.restore_local_infile_handler.take();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::RetryPolicy;
+    use crate::{Error, MySqlError, ServerError};
+
+    fn server_error(code: ServerError) -> Error {
+        Error::MySqlError(MySqlError {
+            state: "40001".into(),
+            message: "Deadlock found when trying to get lock; try restarting transaction".into(),
+            code: code as u16,
+        })
+    }
+
+    #[test]
+    fn should_retry_after_deadlock() {
+        let policy = RetryPolicy::new(2).set_backoff(Duration::from_millis(1));
+        let mut attempts = 0;
+        let result = policy.run(|| {
+            attempts += 1;
+            if attempts == 1 {
+                Err(server_error(ServerError::ER_LOCK_DEADLOCK))
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(result.unwrap(), 2);
+    }
+
+    #[test]
+    fn should_give_up_after_max_retries() {
+        let policy = RetryPolicy::new(2).set_backoff(Duration::from_millis(1));
+        let mut attempts = 0;
+        let result = policy.run(|| -> crate::Result<()> {
+            attempts += 1;
+            Err(server_error(ServerError::ER_LOCK_WAIT_TIMEOUT))
+        });
+        assert!(RetryPolicy::is_retryable(&result.unwrap_err()));
+        assert_eq!(attempts, 3);
+
+        // Retries are opt-in.
+        let mut attempts = 0;
+        let _ = RetryPolicy::default().run(|| -> crate::Result<()> {
+            attempts += 1;
+            Err(server_error(ServerError::ER_LOCK_DEADLOCK))
+        });
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn should_not_retry_other_errors() {
+        let policy = RetryPolicy::new(2).set_backoff(Duration::from_millis(1));
+        let mut attempts = 0;
+        let result = policy.run(|| -> crate::Result<()> {
+            attempts += 1;
+            Err(server_error(ServerError::ER_DUP_ENTRY))
+        });
+        assert!(!RetryPolicy::is_retryable(&result.unwrap_err()));
+        assert_eq!(attempts, 1);
+    }
+}
@@ -60,7 +60,7 @@ pub enum Error {
     MySqlError(MySqlError),
     DriverError(DriverError),
     UrlError(UrlError),
-    #[cfg(any(feature = "native-tls", feature = "rustls"))]
+    #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
     TlsError(tls::TlsError),
     FromValueError(Value),
     FromRowError(Row),
@@ -70,7 +70,7 @@ impl Error {
     #[doc(hidden)]
     pub fn is_connectivity_error(&self) -> bool {
         match self {
-            #[cfg(any(feature = "native-tls", feature = "rustls"))]
+            #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
             Error::TlsError(_) => true,
             Error::IoError(_) | Error::DriverError(_) | Error::CodecError(_) => true,
             Error::MySqlError(_)
@@ -96,7 +96,7 @@ impl error::Error for Error {
             Error::DriverError(ref err) => Some(err),
             Error::MySqlError(ref err) => Some(err),
             Error::UrlError(ref err) => Some(err),
-            #[cfg(any(feature = "native-tls", feature = "rustls"))]
+            #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
             Error::TlsError(ref err) => Some(err),
             _ => None,
         }
@@ -179,9 +179,28 @@ impl fmt::Display for Error {
             Error::MySqlError(ref err) => write!(f, "MySqlError {{ {} }}", err),
             Error::DriverError(ref err) => write!(f, "DriverError {{ {} }}", err),
             Error::UrlError(ref err) => write!(f, "UrlError {{ {} }}", err),
-            #[cfg(any(feature = "native-tls", feature = "rustls"))]
+            #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
             Error::TlsError(ref err) => write!(f, "TlsError {{ {} }}", err),
-            Error::FromRowError(_) => "from row conversion error".fmt(f),
+            Error::FromRowError(ref row) => {
+                "from row conversion error".fmt(f)?;
+                // A NULL read into a non-`Option` type is the usual cause,
+                // so point at the NULL columns.
+                let nulls = (0..row.len())
+                    .filter(|&i| row.as_ref(i) == Some(&Value::NULL))
+                    .map(|i| format!("`{}`", row.columns_ref()[i].name_str()))
+                    .collect::<Vec<_>>();
+                if !nulls.is_empty() {
+                    write!(
+                        f,
+                        ": unexpected NULL for non-Option field in column(s) {}",
+                        nulls.join(", ")
+                    )?;
+                }
+                Ok(())
+            }
+            Error::FromValueError(Value::NULL) => {
+                "from value conversion error: unexpected NULL for non-Option field".fmt(f)
+            }
             Error::FromValueError(_) => "from value conversion error".fmt(f),
         }
     }
@@ -207,6 +226,8 @@ pub enum DriverError {
     InvalidPoolConstraints,
     SetupError,
     TlsNotSupported,
+    TlsHandshakeFailed(String),
+    TlsFeatureDisabled,
     CouldNotParseVersion,
     ReadOnlyTransNotSupported,
     PoisonedPoolMutex,
@@ -216,6 +237,11 @@ pub enum DriverError {
     MixedParams,
     UnknownAuthPlugin(String),
     OldMysqlPasswordDisabled,
+    RowDeserializationFailed(String),
+    MaxAllowedPacketExceeded(usize, usize),
+    MissingKeyColumn(String),
+    // (column, charset)
+    UnsupportedCharset(String, String),
 }
 
 impl error::Error for DriverError {
@@ -253,6 +279,14 @@ impl fmt::Display for DriverError {
                 "Client requires secure connection but server \
                  does not have this capability"
             ),
+            DriverError::TlsHandshakeFailed(ref desc) => {
+                write!(f, "TLS handshake failed: {}", desc)
+            }
+            DriverError::TlsFeatureDisabled => write!(
+                f,
+                "TLS requested but no TLS feature enabled. \
+                 Please enable one of the following features: [\"native-tls\", \"rustls-tls\"]"
+            ),
             DriverError::CouldNotParseVersion => write!(f, "Could not parse MySQL version"),
             DriverError::ReadOnlyTransNotSupported => write!(
                 f,
@@ -279,6 +313,22 @@ impl fmt::Display for DriverError {
                     "`old_mysql_password` plugin is insecure and disabled by default",
                 )
             }
+            DriverError::RowDeserializationFailed(ref desc) => {
+                write!(f, "Could not deserialize row: {}", desc)
+            }
+            DriverError::MaxAllowedPacketExceeded(size, max) => write!(
+                f,
+                "Packet of {} bytes exceeds max_allowed_packet ({} bytes), nothing was sent",
+                size, max
+            ),
+            DriverError::MissingKeyColumn(ref name) => {
+                write!(f, "Key column `{}` is not in the result set", name)
+            }
+            DriverError::UnsupportedCharset(ref column, ref charset) => write!(
+                f,
+                "Column `{}` is in charset `{}`, which can't be converted to UTF-8",
+                column, charset
+            ),
         }
     }
 }
@@ -1078,3 +1128,19 @@ pub enum ServerError {
     ER_UNSUPPORTED_ENGINE = 1726u16,
     ER_BINLOG_UNSAFE_AUTOINC_NOT_FIRST = 1727u16,
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{Error, Value};
+
+    #[test]
+    fn should_explain_unexpected_null() {
+        let err = Error::FromValueError(Value::NULL);
+        assert_eq!(
+            err.to_string(),
+            "from value conversion error: unexpected NULL for non-Option field"
+        );
+        let err = Error::FromValueError(Value::Int(1));
+        assert_eq!(err.to_string(), "from value conversion error");
+    }
+}
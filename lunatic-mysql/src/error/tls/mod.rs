@@ -1,4 +1,4 @@
-#![cfg(any(feature = "native-tls", feature = "rustls"))]
+#![cfg(any(feature = "native-tls", feature = "rustls-tls"))]
 
 pub mod native_tls_error;
 pub mod rustls_error;
@@ -6,5 +6,5 @@ pub mod rustls_error;
 #[cfg(feature = "native-tls")]
 pub use native_tls_error::TlsError;
 
-#[cfg(feature = "rustls")]
+#[cfg(feature = "rustls-tls")]
 pub use rustls_error::TlsError;
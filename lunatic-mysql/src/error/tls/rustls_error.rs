@@ -1,4 +1,4 @@
-#![cfg(feature = "rustls")]
+#![cfg(feature = "rustls-tls")]
 
 use std::fmt::Display;
 
@@ -57,6 +57,22 @@ impl Stream {
             })
     }
 
+    /// Takes the socket out of an insecure stream, or gives the stream back if
+    /// it is a TLS one. Data that was buffered but not read is dropped.
+    pub fn into_socket(self) -> Result<net::TcpStream, Stream> {
+        match self {
+            Stream::TcpStream(TcpStream::Insecure(stream)) => stream
+                .into_inner()
+                .map_err(|err| Stream::TcpStream(TcpStream::Insecure(err.into_inner()))),
+            #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+            stream => Err(stream),
+        }
+    }
+
+    pub fn from_socket(socket: net::TcpStream) -> Stream {
+        Stream::TcpStream(TcpStream::Insecure(BufStream::new(socket)))
+    }
+
     pub fn is_insecure(&self) -> bool {
         matches!(self, Stream::TcpStream(TcpStream::Insecure(_)))
     }
@@ -65,12 +81,9 @@ impl Stream {
         false
     }
 
-    #[cfg(all(not(feature = "native-tls"), not(feature = "rustls")))]
+    #[cfg(all(not(feature = "native-tls"), not(feature = "rustls-tls")))]
     pub fn make_secure(self, _host: url::Host, _ssl_opts: crate::SslOpts) -> MyResult<Stream> {
-        panic!(
-            "Client had asked for TLS connection but TLS support is disabled. \
-            Please enable one of the following features: [\"native-tls\", \"rustls\"]"
-        )
+        Err(DriverError(crate::DriverError::TlsFeatureDisabled))
     }
 }
 
@@ -78,7 +91,7 @@ impl Stream {
 pub enum TcpStream {
     #[cfg(feature = "native-tls")]
     Secure(BufStream<native_tls::TlsStream<net::TcpStream>>),
-    #[cfg(feature = "rustls")]
+    #[cfg(feature = "rustls-tls")]
     Secure(BufStream<rustls::StreamOwned<rustls::ClientConnection, net::TcpStream>>),
     Insecure(BufStream<net::TcpStream>),
 }
@@ -88,9 +101,29 @@ impl fmt::Debug for TcpStream {
         match *self {
             #[cfg(feature = "native-tls")]
             TcpStream::Secure(ref s) => write!(f, "Secure stream {:?}", s),
-            #[cfg(feature = "rustls")]
+            #[cfg(feature = "rustls-tls")]
             TcpStream::Secure(ref s) => write!(f, "Secure stream {:?}", s),
             TcpStream::Insecure(ref s) => write!(f, "Insecure stream {:?}", s),
         }
     }
 }
+
+#[cfg(all(test, not(feature = "native-tls"), not(feature = "rustls-tls")))]
+mod test {
+    use lunatic::net::TcpListener;
+
+    use crate::{io::Stream, DriverError::TlsFeatureDisabled, Error::DriverError, SslOpts};
+
+    #[test]
+    fn should_return_error_if_tls_is_disabled() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let stream = Stream::connect_tcp("127.0.0.1", port, None, None, None, None, None).unwrap();
+
+        let host = url::Host::Domain("localhost".into());
+        match stream.make_secure(host, SslOpts::default()) {
+            Err(DriverError(TlsFeatureDisabled)) => (),
+            other => panic!("expected a disabled TLS error, got {:?}", other),
+        }
+    }
+}
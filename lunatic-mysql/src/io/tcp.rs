@@ -70,7 +70,11 @@ impl<T: ToSocketAddrs> MyTcpBuilder<T> {
         let MyTcpBuilder {
             address,
             bind_address,
-            ..
+            connect_timeout,
+            read_timeout,
+            write_timeout,
+            // lunatic sockets expose no keepalive setting, the OS default applies.
+            keepalive_time_ms: _,
         } = self;
         let err_msg = if bind_address.is_none() {
             "could not connect to any address"
@@ -81,11 +85,16 @@ impl<T: ToSocketAddrs> MyTcpBuilder<T> {
 
         let addrs = address.to_socket_addrs()?.collect::<Vec<_>>();
 
+        // Each failed attempt replaces the error, so a total failure reports
+        // what went wrong with the last candidate.
+        let fold_fun = |prev: io::Result<TcpStream>, sock_addr: &SocketAddr| match prev {
+            Ok(socket) => Ok(socket),
+            Err(_) => connect_addr(*sock_addr, connect_timeout),
+        };
+
         let socket = if let Some(bind_address) = bind_address {
-            let fold_fun = |prev, _sock_addr: &SocketAddr| match prev {
-                Ok(socket) => Ok(socket),
-                Err(_) => Ok(TcpStream::connect(bind_address).unwrap()),
-            };
+            // lunatic can't bind the local end of a connection, so the bind
+            // address only decides which address family is tried first.
 
             if bind_address.is_ipv4() {
                 // client wants to bind to ipv4, so let's look for ipv4 addresses first
@@ -104,22 +113,60 @@ impl<T: ToSocketAddrs> MyTcpBuilder<T> {
             }
         } else {
             // no bind address
-            addrs
-                .into_iter()
-                .fold(Err(err), |prev, sock_addr| match prev {
-                    Ok(socket) => Ok(socket),
-                    Err(_) => Ok(TcpStream::connect(sock_addr).unwrap()),
-                })
+            addrs.iter().fold(Err(err), fold_fun)
         }?;
 
-        // socket.set_read_timeout(read_timeout)?;
-        // socket.set_write_timeout(write_timeout)?;
-        // if let Some(duration) = keepalive_time_ms {
-        //     let conf =
-        //         socket2::TcpKeepalive::new().with_time(Duration::from_millis(duration as u64));
-        //     socket.set_tcp_keepalive(&conf)?;
-        // }
-        // socket.set_nodelay(nodelay)?;
+        let mut socket = socket;
+        socket.set_read_timeout(read_timeout)?;
+        socket.set_write_timeout(write_timeout)?;
         Ok(socket)
     }
 }
+
+fn connect_addr(addr: SocketAddr, timeout: Option<Duration>) -> io::Result<TcpStream> {
+    match timeout {
+        Some(timeout) => TcpStream::connect_timeout(addr, timeout),
+        None => TcpStream::connect(addr),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::Read, time::Duration};
+
+    use lunatic::net::TcpListener;
+
+    use super::MyTcpBuilder;
+
+    #[test]
+    fn should_time_out_reading_from_idle_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut builder = MyTcpBuilder::new(addr);
+        builder
+            .connect_timeout(Some(Duration::from_secs(1)))
+            .read_timeout(Some(Duration::from_secs(1)));
+        let mut socket = builder.connect().unwrap();
+        let _peer = listener.accept().unwrap();
+
+        let err = socket.read(&mut [0; 1]).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+        ));
+    }
+
+    #[test]
+    fn should_return_error_for_closed_port() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        assert!(MyTcpBuilder::new(addr).connect().is_err());
+
+        let mut builder = MyTcpBuilder::new(addr);
+        builder.bind_address(Some(([127, 0, 0, 1], 0)));
+        assert!(builder.connect().is_err());
+    }
+}
@@ -1,4 +1,28 @@
-#![cfg(any(feature = "native-tls", feature = "rustls"))]
+#![cfg(any(feature = "native-tls", feature = "rustls-tls"))]
 
 mod native_tls_io;
 mod rustls_io;
+
+#[cfg(test)]
+mod test {
+    use lunatic::net::TcpListener;
+
+    use crate::{io::Stream, DriverError::TlsHandshakeFailed, Error::DriverError, SslOpts};
+
+    #[test]
+    fn should_report_failed_handshake_as_driver_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let stream = Stream::connect_tcp("127.0.0.1", port, None, None, None, None, None).unwrap();
+        assert!(stream.is_insecure());
+
+        // The server hangs up instead of answering the client hello.
+        drop(listener.accept().unwrap());
+
+        let host = url::Host::Domain("localhost".into());
+        match stream.make_secure(host, SslOpts::default()) {
+            Err(DriverError(TlsHandshakeFailed(_))) => (),
+            other => panic!("expected a handshake error, got {:?}", other),
+        }
+    }
+}
@@ -10,6 +10,8 @@ use native_tls::{Certificate, TlsConnector};
 
 use crate::{
     io::{Stream, TcpStream},
+    DriverError::TlsHandshakeFailed,
+    Error::DriverError,
     Result, SslOpts,
 };
 
@@ -58,7 +60,9 @@ impl Stream {
             Stream::TcpStream(tcp_stream) => match tcp_stream {
                 TcpStream::Insecure(insecure_stream) => {
                     let inner = insecure_stream.into_inner().map_err(io::Error::from)?;
-                    let secure_stream = tls_connector.connect(&domain, inner)?;
+                    let secure_stream = tls_connector
+                        .connect(&domain, inner)
+                        .map_err(|err| DriverError(TlsHandshakeFailed(err.to_string())))?;
                     Ok(Stream::TcpStream(TcpStream::Secure(BufStream::new(
                         secure_stream,
                     ))))
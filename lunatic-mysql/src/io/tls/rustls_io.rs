@@ -16,6 +16,8 @@ use rustls_pemfile::certs;
 
 use crate::{
     io::{Stream, TcpStream},
+    DriverError::TlsHandshakeFailed,
+    Error::DriverError,
     Result, SslOpts,
 };
 
@@ -87,12 +89,14 @@ impl Stream {
         match self {
             Stream::TcpStream(tcp_stream) => match tcp_stream {
                 TcpStream::Insecure(insecure_stream) => {
-                    let inner = insecure_stream
-                        .into_inner()
-                        .map_err(io::Error::from)
-                        .unwrap();
-                    let conn =
-                        rustls::ClientConnection::new(Arc::new(config), server_name).unwrap();
+                    let mut inner = insecure_stream.into_inner().map_err(io::Error::from)?;
+                    let mut conn = rustls::ClientConnection::new(Arc::new(config), server_name)?;
+                    // Drive the handshake here rather than on the first read, so that
+                    // its failure is reported by the connect call.
+                    while conn.is_handshaking() {
+                        conn.complete_io(&mut inner)
+                            .map_err(|err| DriverError(TlsHandshakeFailed(err.to_string())))?;
+                    }
                     let secure_stream = rustls::StreamOwned::new(conn, inner);
                     Ok(Stream::TcpStream(TcpStream::Secure(BufStream::new(
                         secure_stream,
@@ -9,21 +9,20 @@ use lunatic_db::redis::{self, Commands};
 fn main(_: Mailbox<()>) {
     let client = redis::Client::open("redis://127.0.0.1/").unwrap();
     let mut publish_conn = client.get_connection().unwrap();
-    // this process can keep reading the various subscriptions and process them
-    let _sub = lunatic::spawn_link!(|| {
-        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
-        let mut subscribe_conn = client.get_connection().unwrap().as_pubsub();
-
-        subscribe_conn.subscribe("wavephone").unwrap();
+    // `subscribe` waits for the server to confirm the subscription, so once it
+    // returns nothing published afterwards can be missed
+    let mut subscribe_conn = client.get_connection().unwrap().as_pubsub();
+    subscribe_conn.subscribe("wavephone").unwrap();
 
+    // this process can keep reading the various subscriptions and process them
+    let _sub = lunatic::spawn_link!(@task |subscribe_conn| {
+        let mut subscribe_conn = subscribe_conn;
         let pubsub_msg = subscribe_conn.receive().unwrap();
         let pubsub_msg: String = pubsub_msg.get_payload().unwrap();
         assert_eq!(&pubsub_msg, "banana");
         println!("[subscriber] GOT MESSAGE {:?}", pubsub_msg);
     });
 
-    // allow for some time to establish the connection
-    lunatic::sleep(Duration::from_secs(1));
     publish_conn
         .publish::<&str, &str, ()>("wavephone", "banana")
         .unwrap();
@@ -46,20 +45,18 @@ fn main(_: Mailbox<()>) {
         .unwrap();
 
     // do some pattern subscription
-    let _sub = lunatic::spawn_link!(|| {
-        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
-        let mut subscribe_conn = client.get_connection().unwrap().as_pubsub();
-        subscribe_conn.psubscribe("hello").unwrap();
-        subscribe_conn.psubscribe("w*rld").unwrap();
-        println!("SUBBED TO TOPICS");
+    let mut subscribe_conn = client.get_connection().unwrap().as_pubsub();
+    subscribe_conn.psubscribe("hello").unwrap();
+    subscribe_conn.psubscribe("w*rld").unwrap();
+    println!("SUBBED TO TOPICS");
 
-        loop {
-            let pubsub_msg = subscribe_conn.receive();
+    let _sub = lunatic::spawn_link!(@task |subscribe_conn| {
+        let mut subscribe_conn = subscribe_conn;
+        while let Ok(pubsub_msg) = subscribe_conn.receive() {
             println!("[subscriber] GOT PMESSAGE {:?}", pubsub_msg);
         }
     });
 
-    sleep(Duration::from_secs(1));
     // publish to pattern
     publish_conn
         .publish::<&str, &str, ()>("hello", "first")
@@ -11,7 +11,11 @@ fn main(_: Mailbox<()>) {
 return tonumber(ARGV[1]) + tonumber(ARGV[2]);
 ",
     );
-    let result: isize = script.arg(1).arg(2).invoke(&mut con).unwrap();
-    println!("GOT SCRIPT RESULT {:?}", result);
-    assert_eq!(result, 3);
+    // load it once, every invocation only sends its hash
+    script.prepare(&mut con).unwrap();
+    for i in 0..3 {
+        let result: isize = script.arg(i).arg(2).invoke(&mut con).unwrap();
+        println!("GOT SCRIPT RESULT {:?}", result);
+        assert_eq!(result, i + 2);
+    }
 }
@@ -1,12 +1,21 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    connection::{connect, Connection, ConnectionInfo, ConnectionLike, IntoConnectionInfo},
+    cmd::cmd,
+    connection::{
+        connect, Connection, ConnectionInfo, ConnectionLike, IntoConnectionInfo, OnConnect,
+    },
+    multiplexed::MultiplexedConnection,
     types::{RedisResult, Value},
 };
 
+/// The first pause between attempts of `wait_until_ready`.
+const READY_BACKOFF_START: Duration = Duration::from_millis(10);
+/// The longest pause between attempts of `wait_until_ready`.
+const READY_BACKOFF_MAX: Duration = Duration::from_millis(500);
+
 /// The client type.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Client {
@@ -39,6 +48,37 @@ impl Client {
         })
     }
 
+    /// Sets a hook that runs on every new connection before it is handed
+    /// out, e.g. to send `CLIENT SETNAME` or load scripts.
+    ///
+    /// If the hook fails the connection is dropped and its error is
+    /// returned instead.  The hook is stored in the connection info, so it
+    /// also runs when a connection reconnects and for a `RedisPool` created
+    /// from it.  It is a function pointer and is not serialized, a client
+    /// sent to another process has to get its hook set again there.
+    ///
+    /// ```rust,no_run
+    /// let client = redis::Client::open("redis://127.0.0.1/")
+    ///     .unwrap()
+    ///     .on_connect(|con| redis::cmd("CLIENT").arg("SETNAME").arg("worker").query(con));
+    /// ```
+    pub fn on_connect(mut self, hook: fn(&mut Connection) -> RedisResult<()>) -> Client {
+        self.connection_info.redis.on_connect = Some(OnConnect(hook));
+        self
+    }
+
+    /// Like `open` but also connects once to verify the server is reachable
+    /// and that authentication and database selection succeed.
+    ///
+    /// The probe connection is closed again before returning.  This is
+    /// meant for fail-fast startup, so that e.g. a wrong password is
+    /// reported right away instead of on the first `get_connection()`.
+    pub fn open_verified<T: IntoConnectionInfo>(params: T) -> RedisResult<Client> {
+        let client = Client::open(params)?;
+        client.get_connection()?;
+        Ok(client)
+    }
+
     /// Instructs the client to actually connect to redis and returns a
     /// connection object.  The connection object can be used to send
     /// commands to the server.  This can fail with a variety of errors
@@ -57,6 +97,58 @@ impl Client {
         connect(&self.connection_info, Some(timeout))
     }
 
+    /// Connects like `get_connection` and moves the connection into a
+    /// process of its own, so that it can be shared by many processes.
+    ///
+    /// See [`MultiplexedConnection`] for what can be sent through it.
+    pub fn get_multiplexed_connection(&self) -> RedisResult<MultiplexedConnection> {
+        Ok(MultiplexedConnection::new(self.get_connection()?))
+    }
+
+    /// Waits until the server accepts connections and answers `PING`, and
+    /// returns the connection that did.
+    ///
+    /// This is meant for test harnesses that start a server and have to
+    /// wait for it to come up.  Failed attempts, including a server that
+    /// is still loading its dataset, are retried with a backoff starting at
+    /// 10ms and doubling up to 500ms.  Once `timeout` has passed the error
+    /// of the last attempt is returned.
+    ///
+    /// ```rust,no_run
+    /// # use std::time::Duration;
+    /// let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    /// let con = client.wait_until_ready(Duration::from_secs(5)).unwrap();
+    /// ```
+    pub fn wait_until_ready(&self, timeout: Duration) -> RedisResult<Connection> {
+        let deadline = Instant::now() + timeout;
+        let mut backoff = READY_BACKOFF_START;
+        loop {
+            let err = match self.try_ping(deadline) {
+                Ok(con) => return Ok(con),
+                Err(err) => err,
+            };
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(err);
+            }
+            lunatic::sleep(backoff.min(deadline - now));
+            backoff = (backoff * 2).min(READY_BACKOFF_MAX);
+        }
+    }
+
+    fn try_ping(&self, deadline: Instant) -> RedisResult<Connection> {
+        // Neither connecting nor the reply may take us past the deadline.
+        let remaining = deadline
+            .saturating_duration_since(Instant::now())
+            .max(Duration::from_millis(1));
+        let mut con = self.get_connection_with_timeout(remaining)?;
+        let previous = con.read_timeout();
+        con.set_read_timeout(Some(remaining))?;
+        cmd("PING").query::<()>(&mut con)?;
+        con.set_read_timeout(previous)?;
+        Ok(con)
+    }
+
     /// Returns a reference of client connection info object.
     pub fn get_connection_info(&self) -> &ConnectionInfo {
         &self.connection_info
@@ -90,6 +182,14 @@ impl ConnectionLike for Client {
         }
     }
 
+    fn check_connection_timeout(&mut self, dur: Duration) -> bool {
+        if let Ok(mut conn) = self.get_connection() {
+            conn.check_connection_timeout(dur)
+        } else {
+            false
+        }
+    }
+
     fn is_open(&self) -> bool {
         if let Ok(conn) = self.get_connection() {
             conn.is_open()
@@ -102,9 +202,50 @@ impl ConnectionLike for Client {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::fake_server::FakeServer;
 
     #[test]
     fn regression_293_parse_ipv6_with_interface() {
         assert!(Client::open(("fe80::cafe:beef%eno1", 6379)).is_ok());
     }
+
+    #[test]
+    fn test_wait_until_ready() {
+        use lunatic::net::TcpListener;
+        use std::io::{Read, Write};
+
+        // Nothing listens on the port yet.
+        let port = FakeServer::bind().port();
+
+        // A server that only starts listening after a while
+        let server = lunatic::spawn_link!(@task |port| {
+            lunatic::sleep(Duration::from_millis(200));
+            let listener = TcpListener::bind(format!("127.0.0.1:{}", port).as_str()).unwrap();
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 64];
+            let len = stream.read(&mut buf).unwrap();
+            stream.write_all(b"+PONG\r\n").unwrap();
+            stream.flush().unwrap();
+            buf[..len].to_vec()
+        });
+
+        let client = Client::open(("127.0.0.1", port)).unwrap();
+        let start = Instant::now();
+        assert!(client.wait_until_ready(Duration::from_secs(5)).is_ok());
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert_eq!(server.result(), cmd("PING").get_packed_command());
+    }
+
+    #[test]
+    fn test_wait_until_ready_timeout() {
+        // Nothing listens there anymore.
+        let client = Client::open(FakeServer::bind().info()).unwrap();
+        let start = Instant::now();
+        let err = client
+            .wait_until_ready(Duration::from_millis(200))
+            .err()
+            .unwrap();
+        assert!(err.is_io_error(), "{}", err);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
 }
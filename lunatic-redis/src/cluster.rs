@@ -50,16 +50,17 @@ use rand::{
 };
 
 use super::{
-    cmd, parse_redis_value,
+    cmd, from_redis_value, parse_redis_value,
     types::{HashMap, HashSet},
-    Cmd, Connection, ConnectionAddr, ConnectionInfo, ConnectionLike, ErrorKind, IntoConnectionInfo,
-    RedisError, RedisResult, Value,
+    Cmd, Connection, ConnectionAddr, ConnectionInfo, ConnectionLike, ErrorKind, FromRedisValue,
+    IntoConnectionInfo, RedisError, RedisResult, Value,
 };
 
 pub use crate::cluster_client::{ClusterClient, ClusterClientBuilder};
 use crate::cluster_pipeline::UNROUTABLE_ERROR;
 pub use crate::cluster_pipeline::{cluster_pipe, ClusterPipeline};
-use crate::cluster_routing::{Routable, RoutingInfo, Slot, SLOT_SIZE};
+use crate::cluster_routing::{check_watched_keys, Routable, RoutingInfo, Slot, SLOT_SIZE};
+pub use crate::cluster_routing::{get_slot, hashtag, MultiKey};
 
 type SlotMap = BTreeMap<u16, [String; 2]>;
 
@@ -433,6 +434,7 @@ impl ClusterConnection {
         T: MergeResults + std::fmt::Debug,
         F: FnMut(&mut Connection) -> RedisResult<T>,
     {
+        check_watched_keys(cmd)?;
         let route = match RoutingInfo::for_routable(cmd) {
             Some(RoutingInfo::Random) => None,
             Some(RoutingInfo::MasterSlot(slot)) => Some((slot, 0)),
@@ -733,6 +735,16 @@ impl ConnectionLike for ClusterConnection {
         }
         true
     }
+
+    fn check_connection_timeout(&mut self, dur: Duration) -> bool {
+        let mut connections = self.connections.borrow_mut();
+        for conn in connections.values_mut() {
+            if !conn.check_connection_timeout(dur) {
+                return false;
+            }
+        }
+        true
+    }
 }
 
 fn connect<T: IntoConnectionInfo>(
@@ -846,6 +858,188 @@ fn get_slots(connection: &mut Connection, tls_mode: Option<TlsMode>) -> RedisRes
     Ok(result)
 }
 
+/// A single node as reported by `CLUSTER NODES`.
+///
+/// [`cluster_nodes`] replies with one line per node, which is parsed by
+/// reading the reply into a `Vec<ClusterNode>`:
+///
+/// ```rust,no_run
+/// use redis::Commands;
+/// use redis::cluster::ClusterNode;
+/// # let client = redis::Client::open("redis://127.0.0.1:6379/").unwrap();
+/// # let mut con = client.get_connection().unwrap();
+/// let nodes: Vec<ClusterNode> = con.cluster_nodes().unwrap();
+/// for node in nodes.iter().filter(|node| node.is_master()) {
+///     println!("{} serves {:?}", node.addr, node.slots);
+/// }
+/// ```
+///
+/// [`cluster_nodes`]: ../trait.Commands.html#method.cluster_nodes
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ClusterNode {
+    /// The node id.
+    pub id: String,
+    /// The client address of the node in the `ip:port` form.
+    pub addr: String,
+    /// The cluster bus port of the node.
+    pub cluster_port: Option<u16>,
+    /// The announced hostname of the node, if any.
+    pub hostname: Option<String>,
+    /// The `key=value` fields redis 7.2 and later append to the address,
+    /// e.g. `shard-id` or `tls-port`, in the order they were sent.
+    pub aux_fields: Vec<(String, String)>,
+    /// Flags such as `myself`, `master`, `slave`, `fail?` or `fail`.
+    pub flags: Vec<String>,
+    /// The id of the master if this node is a replica.
+    pub master: Option<String>,
+    /// Milliseconds unix time at which the currently active ping was sent.
+    pub ping_sent: u64,
+    /// Milliseconds unix time the last pong was received.
+    pub pong_recv: u64,
+    /// The configuration epoch of the node.
+    pub config_epoch: u64,
+    /// Whether the link to the cluster bus is `connected` or `disconnected`.
+    pub link_state: String,
+    /// The inclusive slot ranges served by this node.
+    pub slots: Vec<(u16, u16)>,
+}
+
+impl ClusterNode {
+    /// Parses the full text reply of `CLUSTER NODES`.
+    pub fn parse_nodes(nodes: &str) -> RedisResult<Vec<ClusterNode>> {
+        nodes
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(ClusterNode::parse_line)
+            .collect()
+    }
+
+    /// Parses a single line of a `CLUSTER NODES` reply.
+    pub fn parse_line(line: &str) -> RedisResult<ClusterNode> {
+        let mut parts = line.split_ascii_whitespace();
+        let mut next = || {
+            parts.next().ok_or_else(|| {
+                RedisError::from((
+                    ErrorKind::TypeError,
+                    "Truncated CLUSTER NODES line",
+                    line.to_string(),
+                ))
+            })
+        };
+        let invalid = || {
+            RedisError::from((
+                ErrorKind::TypeError,
+                "Invalid CLUSTER NODES line",
+                line.to_string(),
+            ))
+        };
+
+        let id = next()?.to_string();
+
+        // ip:port@cport[,hostname[,key=value...]]
+        let mut fields = next()?.split(',');
+        let address = fields.next().unwrap_or_default();
+        let hostname = fields
+            .next()
+            .filter(|hostname| !hostname.is_empty())
+            .map(str::to_string);
+        let aux_fields = fields
+            .map(|field| match field.split_once('=') {
+                Some((key, value)) => Ok((key.to_string(), value.to_string())),
+                None => Err(invalid()),
+            })
+            .collect::<RedisResult<_>>()?;
+        let (addr, cluster_port) = match address.split_once('@') {
+            Some((addr, cport)) => (
+                addr.to_string(),
+                Some(cport.parse().map_err(|_| invalid())?),
+            ),
+            None => (address.to_string(), None),
+        };
+
+        let flags = next()?
+            .split(',')
+            .filter(|flag| !flag.is_empty() && *flag != "noflags")
+            .map(str::to_string)
+            .collect();
+        let master = match next()? {
+            "-" => None,
+            master => Some(master.to_string()),
+        };
+        let ping_sent = next()?.parse().map_err(|_| invalid())?;
+        let pong_recv = next()?.parse().map_err(|_| invalid())?;
+        let config_epoch = next()?.parse().map_err(|_| invalid())?;
+        let link_state = next()?.to_string();
+
+        let mut slots = vec![];
+        for slot in parts {
+            // Slots being imported or migrated look like `[slot->-node]`
+            // and are not served by the node yet.
+            if slot.starts_with('[') {
+                continue;
+            }
+            let range = match slot.split_once('-') {
+                Some((start, end)) => (
+                    start.parse().map_err(|_| invalid())?,
+                    end.parse().map_err(|_| invalid())?,
+                ),
+                None => {
+                    let slot = slot.parse().map_err(|_| invalid())?;
+                    (slot, slot)
+                }
+            };
+            slots.push(range);
+        }
+
+        Ok(ClusterNode {
+            id,
+            addr,
+            cluster_port,
+            hostname,
+            aux_fields,
+            flags,
+            master,
+            ping_sent,
+            pong_recv,
+            config_epoch,
+            link_state,
+            slots,
+        })
+    }
+
+    /// Returns true if the node has the given flag.
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|f| f == flag)
+    }
+
+    /// Returns true if this is the node the reply was fetched from.
+    pub fn is_myself(&self) -> bool {
+        self.has_flag("myself")
+    }
+
+    /// Returns true if the node is a master.
+    pub fn is_master(&self) -> bool {
+        self.has_flag("master")
+    }
+
+    /// Returns true if the node is a replica.
+    pub fn is_replica(&self) -> bool {
+        self.has_flag("slave")
+    }
+}
+
+impl FromRedisValue for ClusterNode {
+    fn from_redis_value(v: &Value) -> RedisResult<ClusterNode> {
+        let line: String = from_redis_value(v)?;
+        ClusterNode::parse_line(&line)
+    }
+
+    fn from_byte_vec(vec: &[u8]) -> Option<Vec<ClusterNode>> {
+        // `CLUSTER NODES` replies with all nodes in a single bulk string.
+        ClusterNode::parse_nodes(std::str::from_utf8(vec).ok()?).ok()
+    }
+}
+
 fn build_connection_string(host: &str, port: Option<u16>, tls_mode: Option<TlsMode>) -> String {
     let host_port = match port {
         Some(port) => format!("{}:{}", host, port),
@@ -859,3 +1053,101 @@ fn build_connection_string(host: &str, port: Option<u16>, tls_mode: Option<TlsMo
         Some(TlsMode::Secure) => format!("rediss://{}", host_port),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NODES: &str = "\
+07c37dfeb235213a872192d90877d0cd55635b91 127.0.0.1:30004@31004,hostname4 slave e7d1eecce10fd6bb5eb35b9f99a514335d9ba9ca 0 1426238317239 4 connected
+67ed2db8d677e59ec4a4cefb06858cf2a1a89fa1 127.0.0.1:30002@31002,hostname2 master - 0 1426238316232 2 connected 5461-10922
+292f8b365bb7edb5e285caf0b7e6ddc7265d2f4f 127.0.0.1:30003@31003,hostname3 master - 0 1426238318243 3 connected 10923-16383 [1234->-67ed2db8d677e59ec4a4cefb06858cf2a1a89fa1]
+e7d1eecce10fd6bb5eb35b9f99a514335d9ba9ca 127.0.0.1:30001@31001,hostname1 myself,master - 0 0 1 connected 0-5459 5460
+";
+
+    #[test]
+    fn test_parse_cluster_nodes() {
+        let nodes = ClusterNode::parse_nodes(NODES).unwrap();
+        assert_eq!(nodes.len(), 4);
+
+        let replica = &nodes[0];
+        assert_eq!(replica.id, "07c37dfeb235213a872192d90877d0cd55635b91");
+        assert_eq!(replica.addr, "127.0.0.1:30004");
+        assert_eq!(replica.cluster_port, Some(31004));
+        assert_eq!(replica.hostname.as_deref(), Some("hostname4"));
+        assert!(replica.is_replica());
+        assert_eq!(
+            replica.master.as_deref(),
+            Some("e7d1eecce10fd6bb5eb35b9f99a514335d9ba9ca")
+        );
+        assert_eq!(replica.pong_recv, 1426238317239);
+        assert_eq!(replica.config_epoch, 4);
+        assert_eq!(replica.link_state, "connected");
+        assert!(replica.slots.is_empty());
+
+        // migrating slots are skipped
+        assert_eq!(nodes[2].slots, vec![(10923, 16383)]);
+
+        let myself = &nodes[3];
+        assert!(myself.is_myself());
+        assert!(myself.is_master());
+        assert_eq!(myself.master, None);
+        assert_eq!(myself.slots, vec![(0, 5459), (5460, 5460)]);
+    }
+
+    #[test]
+    fn test_cluster_nodes_from_redis_value() {
+        let nodes: Vec<ClusterNode> =
+            from_redis_value(&Value::Data(NODES.as_bytes().to_vec())).unwrap();
+        assert_eq!(nodes.len(), 4);
+        assert_eq!(nodes[1].slots, vec![(5461, 10922)]);
+
+        // addresses without the cluster bus port as sent by older servers
+        let node = ClusterNode::parse_line(
+            "67ed2db8d677e59ec4a4cefb06858cf2a1a89fa1 127.0.0.1:30002 master - 0 0 2 connected",
+        )
+        .unwrap();
+        assert_eq!(node.addr, "127.0.0.1:30002");
+        assert_eq!(node.cluster_port, None);
+
+        let err = ClusterNode::parse_line("67ed2db8d677e59ec4a4cefb06858cf2a1a89fa1").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TypeError);
+    }
+
+    #[test]
+    fn test_parse_cluster_nodes_aux_fields() {
+        // Redis 7.2 appends auxiliary fields after the hostname.
+        let node = ClusterNode::parse_line(
+            "e7d1eecce10fd6bb5eb35b9f99a514335d9ba9ca 127.0.0.1:30001@31001,hostname1,\
+             shard-id=5f3a7c7b38e58b8f8a4e1ba1d6b9f4a5c7e5d9c1,tls-port=0 \
+             myself,master - 0 0 1 connected 0-5460",
+        )
+        .unwrap();
+        assert_eq!(node.addr, "127.0.0.1:30001");
+        assert_eq!(node.cluster_port, Some(31001));
+        assert_eq!(node.hostname.as_deref(), Some("hostname1"));
+        assert_eq!(
+            node.aux_fields,
+            vec![
+                (
+                    "shard-id".to_string(),
+                    "5f3a7c7b38e58b8f8a4e1ba1d6b9f4a5c7e5d9c1".to_string()
+                ),
+                ("tls-port".to_string(), "0".to_string()),
+            ]
+        );
+        assert_eq!(node.slots, vec![(0, 5460)]);
+
+        // Without a hostname the field is left empty.
+        let node = ClusterNode::parse_line(
+            "e7d1eecce10fd6bb5eb35b9f99a514335d9ba9ca 127.0.0.1:30001@31001,,tls-port=30101 \
+             master - 0 0 1 connected",
+        )
+        .unwrap();
+        assert_eq!(node.hostname, None);
+        assert_eq!(
+            node.aux_fields,
+            vec![("tls-port".to_string(), "30101".to_string())]
+        );
+    }
+}
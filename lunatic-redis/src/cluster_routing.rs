@@ -2,7 +2,7 @@ use std::iter::Iterator;
 
 use crate::cmd::{Arg, Cmd};
 use crate::commands::is_readonly_cmd;
-use crate::types::Value;
+use crate::types::{ErrorKind, RedisResult, RedisWrite, ToRedisArgs, Value};
 
 pub(crate) const SLOT_SIZE: u16 = 16384;
 
@@ -39,6 +39,15 @@ impl RoutingInfo {
                     r.arg_idx(3).and_then(|key| RoutingInfo::for_key(cmd, key))
                 }
             }
+            b"CLUSTER" => match r.arg_idx(1).map(|x| x.to_ascii_uppercase()).as_deref() {
+                Some(b"COUNTKEYSINSLOT") | Some(b"GETKEYSINSLOT") => r
+                    .arg_idx(2)
+                    .and_then(|x| std::str::from_utf8(x).ok())
+                    .and_then(|x| x.parse::<u16>().ok())
+                    .filter(|slot| *slot < SLOT_SIZE)
+                    .map(RoutingInfo::MasterSlot),
+                _ => Some(RoutingInfo::Random),
+            },
             b"XGROUP" | b"XINFO" => r.arg_idx(2).and_then(|key| RoutingInfo::for_key(cmd, key)),
             b"XREAD" | b"XREADGROUP" => {
                 let streams_position = r.position(b"STREAMS")?;
@@ -53,12 +62,7 @@ impl RoutingInfo {
     }
 
     pub fn for_key(cmd: &[u8], key: &[u8]) -> Option<RoutingInfo> {
-        let key = match get_hashtag(key) {
-            Some(tag) => tag,
-            None => key,
-        };
-
-        let slot = crc16::State::<crc16::XMODEM>::calculate(key) % SLOT_SIZE;
+        let slot = get_slot(key);
         if is_readonly_cmd(cmd) {
             Some(RoutingInfo::ReplicaSlot(slot))
         } else {
@@ -67,6 +71,25 @@ impl RoutingInfo {
     }
 }
 
+/// Fails with `ErrorKind::CrossSlot` if `r` is a `WATCH` of keys that hash
+/// to different slots.
+///
+/// A cluster node refuses such a `WATCH`, but only after the transaction
+/// has been prepared, so it is checked before anything is sent.
+pub(crate) fn check_watched_keys<R>(r: &R) -> RedisResult<()>
+where
+    R: Routable + ?Sized,
+{
+    if r.command().as_deref() != Some(&b"WATCH"[..]) {
+        return Ok(());
+    }
+    let mut keys = MultiKey::new();
+    for key in (1..).map_while(|idx| r.arg_idx(idx)) {
+        keys = keys.key(key);
+    }
+    keys.slot().map(|_| ())
+}
+
 pub(crate) trait Routable {
     // Convenience function to return ascii uppercase version of the
     // the first argument (i.e., the command).
@@ -151,6 +174,112 @@ impl Slot {
     }
 }
 
+/// Returns the hash slot a key is stored in.
+///
+/// If the key contains a hash tag only the tag is hashed, so keys sharing a
+/// tag always end up in the same slot.
+pub fn get_slot(key: &[u8]) -> u16 {
+    let key = match get_hashtag(key) {
+        Some(tag) => tag,
+        None => key,
+    };
+
+    crc16::State::<crc16::XMODEM>::calculate(key) % SLOT_SIZE
+}
+
+/// Prefixes `key` with the hash tag `tag`.
+///
+/// All keys built with the same tag hash to the same slot, which is
+/// required for multi-key operations in a cluster.
+///
+/// ```rust
+/// use redis::cluster::hashtag;
+///
+/// assert_eq!(hashtag("user:1000", "followers"), "{user:1000}followers");
+/// ```
+pub fn hashtag(tag: &str, key: &str) -> String {
+    format!("{{{}}}{}", tag, key)
+}
+
+/// A set of keys that is checked to hash to a single slot.
+///
+/// Cluster nodes reject multi-key operations on keys from different slots
+/// with a `CROSSSLOT` error.  `MultiKey` catches these co-location bugs on
+/// the client before anything is sent.  It can be passed anywhere a list of
+/// keys is expected.
+///
+/// ```rust
+/// use redis::cluster::{hashtag, MultiKey};
+///
+/// let keys = MultiKey::new()
+///     .key(hashtag("user:1000", "followers"))
+///     .key(hashtag("user:1000", "following"));
+/// assert!(keys.slot().is_ok());
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct MultiKey {
+    keys: Vec<Vec<u8>>,
+}
+
+impl MultiKey {
+    /// Creates an empty set of keys.
+    pub fn new() -> MultiKey {
+        MultiKey::default()
+    }
+
+    /// Adds a key (or several, if `key` expands to multiple arguments).
+    pub fn key<K: ToRedisArgs>(mut self, key: K) -> MultiKey {
+        key.write_redis_args(&mut self.keys);
+        self
+    }
+
+    /// Returns the keys added so far.
+    pub fn keys(&self) -> &[Vec<u8>] {
+        &self.keys
+    }
+
+    /// Returns the slot shared by all keys, or `None` if there are no keys.
+    ///
+    /// Fails with `ErrorKind::CrossSlot` if the keys hash to different slots.
+    pub fn slot(&self) -> RedisResult<Option<u16>> {
+        let mut keys = self.keys.iter();
+        let first = match keys.next() {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        let slot = get_slot(first);
+        for key in keys {
+            if get_slot(key) != slot {
+                fail!((
+                    ErrorKind::CrossSlot,
+                    "Keys in request don't hash to the same slot",
+                    format!(
+                        "{:?} and {:?}",
+                        String::from_utf8_lossy(first),
+                        String::from_utf8_lossy(key)
+                    )
+                ));
+            }
+        }
+        Ok(Some(slot))
+    }
+}
+
+impl ToRedisArgs for MultiKey {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        for key in &self.keys {
+            out.write_arg(key);
+        }
+    }
+
+    fn is_single_arg(&self) -> bool {
+        self.keys.len() == 1
+    }
+}
+
 fn get_hashtag(key: &[u8]) -> Option<&[u8]> {
     let open = key.iter().position(|v| *v == b'{');
     let open = match open {
@@ -174,8 +303,8 @@ fn get_hashtag(key: &[u8]) -> Option<&[u8]> {
 
 #[cfg(test)]
 mod tests {
-    use super::{get_hashtag, RoutingInfo};
-    use crate::{cmd, parser::parse_redis_value};
+    use super::{check_watched_keys, get_hashtag, get_slot, hashtag, MultiKey, RoutingInfo};
+    use crate::{cmd, parser::parse_redis_value, ErrorKind};
 
     #[test]
     fn test_get_hashtag() {
@@ -184,6 +313,52 @@ mod tests {
         assert_eq!(get_hashtag(&b"foo{{bar}}zap"[..]), Some(&b"{bar"[..]));
     }
 
+    #[test]
+    fn test_hashtag_shares_slot() {
+        let followers = hashtag("user:1000", "followers");
+        let following = hashtag("user:1000", "following");
+        assert_eq!(followers, "{user:1000}followers");
+        assert_eq!(get_slot(followers.as_bytes()), get_slot(b"user:1000"));
+
+        let keys = MultiKey::new().key(&followers).key(&following);
+        assert_eq!(keys.slot().unwrap(), Some(get_slot(b"user:1000")));
+        assert_eq!(MultiKey::new().slot().unwrap(), None);
+    }
+
+    #[test]
+    fn test_multi_key_cross_slot() {
+        // "foo" and "bar" hash to different slots
+        assert_ne!(get_slot(b"foo"), get_slot(b"bar"));
+        let keys = MultiKey::new().key(&["foo", "bar"][..]);
+        assert_eq!(keys.keys().len(), 2);
+        assert_eq!(keys.slot().unwrap_err().kind(), ErrorKind::CrossSlot);
+    }
+
+    #[test]
+    fn test_watch_cross_slot() {
+        let mut same_slot = cmd("WATCH");
+        same_slot
+            .arg(hashtag("user:1000", "followers"))
+            .arg(hashtag("user:1000", "following"));
+        assert!(check_watched_keys(&same_slot).is_ok());
+
+        let mut cross_slot = cmd("watch");
+        cross_slot.arg("foo").arg("bar");
+        let err = check_watched_keys(&cross_slot).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::CrossSlot);
+        assert_eq!(
+            check_watched_keys(&parse_redis_value(&cross_slot.get_packed_command()).unwrap())
+                .unwrap_err()
+                .kind(),
+            ErrorKind::CrossSlot
+        );
+
+        // Other multi-key commands are left to the server.
+        let mut mget = cmd("MGET");
+        mget.arg("foo").arg("bar");
+        assert!(check_watched_keys(&mget).is_ok());
+    }
+
     #[test]
     fn test_routing_info_mixed_capatalization() {
         let mut upper = cmd("XREAD");
@@ -206,6 +381,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_routing_info_keys_in_slot() {
+        let slot = get_slot(b"foo");
+
+        let mut count = cmd("CLUSTER");
+        count.arg("COUNTKEYSINSLOT").arg(slot);
+        assert_eq!(
+            RoutingInfo::for_routable(&count),
+            Some(RoutingInfo::MasterSlot(slot))
+        );
+
+        let mut get = cmd("cluster");
+        get.arg("getkeysinslot").arg(slot).arg(10);
+        assert_eq!(
+            RoutingInfo::for_routable(&get),
+            Some(RoutingInfo::MasterSlot(slot))
+        );
+
+        let mut myid = cmd("CLUSTER");
+        myid.arg("MYID");
+        assert_eq!(RoutingInfo::for_routable(&myid), Some(RoutingInfo::Random));
+    }
+
     #[test]
     fn test_routing_info() {
         let mut test_cmds = vec![];
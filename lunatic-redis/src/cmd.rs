@@ -2,9 +2,12 @@ use std::{fmt, io};
 
 use serde::{Deserialize, Serialize};
 
+use crate::commands::command_arity;
 use crate::connection::ConnectionLike;
 use crate::pipeline::Pipeline;
-use crate::types::{from_redis_value, FromRedisValue, RedisResult, RedisWrite, ToRedisArgs};
+use crate::types::{
+    from_redis_value, ErrorKind, FromRedisValue, RedisResult, RedisWrite, ToRedisArgs,
+};
 
 /// An argument to a redis command
 #[derive(Clone, Deserialize, Serialize)]
@@ -292,7 +295,12 @@ impl Cmd {
     /// you can retrieve data.
     #[inline]
     pub fn query<T: FromRedisValue>(&self, con: &mut dyn ConnectionLike) -> RedisResult<T> {
-        match con.req_command(self) {
+        let reply = if T::discards_value() {
+            con.req_command_discarding_status(self)
+        } else {
+            con.req_command(self)
+        };
+        match reply {
             Ok(val) => from_redis_value(&val),
             Err(e) => Err(e),
         }
@@ -347,6 +355,51 @@ impl Cmd {
         self.query::<()>(con).unwrap();
     }
 
+    /// Checks the number of arguments of well-known commands, such as `GET`
+    /// or `SET`, against their arity and fails with
+    /// `ErrorKind::ClientError` on a mismatch.
+    ///
+    /// Commands that are not known pass the check.  Connections do this
+    /// before sending a command once `Connection::set_check_arity` is on.
+    ///
+    /// ```rust
+    /// let mut set = redis::cmd("SET");
+    /// set.arg("my_key");
+    /// assert!(set.check_arity().is_err());
+    /// set.arg(42);
+    /// assert!(set.check_arity().is_ok());
+    /// ```
+    pub fn check_arity(&self) -> RedisResult<()> {
+        let name = match self.args_iter().next() {
+            Some(Arg::Simple(name)) => name.to_ascii_uppercase(),
+            _ => return Ok(()),
+        };
+        let arity = match command_arity(&name) {
+            Some(arity) => arity,
+            None => return Ok(()),
+        };
+        let count = self.args.len() as i64;
+        let valid = if arity < 0 {
+            count >= -arity
+        } else {
+            count == arity
+        };
+        if !valid {
+            fail!((
+                ErrorKind::ClientError,
+                "Wrong number of arguments",
+                format!(
+                    "{} takes {}{} arguments including its name, got {}",
+                    String::from_utf8_lossy(&name),
+                    if arity < 0 { "at least " } else { "" },
+                    arity.abs(),
+                    count
+                )
+            ));
+        }
+        Ok(())
+    }
+
     /// Returns an iterator over the arguments in this command (including the command name itself)
     pub fn args_iter(&self) -> impl Iterator<Item = Arg<&[u8]>> + Clone + ExactSizeIterator {
         let mut prev = 0;
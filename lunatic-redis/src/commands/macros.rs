@@ -46,6 +46,124 @@ macro_rules! implement_commands {
                     { Cmd::$name($($argname),*).query(self) }
             )*
 
+            /// Set the time to live in seconds of many keys at once.
+            ///
+            /// All `EXPIRE` commands are sent in a single pipeline.  The
+            /// result holds one entry per pair, in the same order, that is
+            /// `true` if the timeout was set and `false` if the key does not
+            /// exist.
+            #[inline]
+            fn expire_many<K: ToRedisArgs>(&mut self, pairs: &[(K, u64)]) -> RedisResult<Vec<bool>> {
+                let mut p = Pipeline::with_capacity(pairs.len());
+                for (key, seconds) in pairs {
+                    p.cmd("EXPIRE").arg(key).arg(*seconds);
+                }
+                p.query(self)
+            }
+
+            /// Draws `n` members of the sorted set at `key` at random, with
+            /// replacement, each with a probability proportional to its score.
+            ///
+            /// All members with a positive score are fetched with
+            /// `ZRANGEBYSCORE` and the sampling happens on the client, so the
+            /// cost is `O(N)` in the size of the set for every call.  Members
+            /// with a score of zero or less are never drawn.  The draw is
+            /// exact for the snapshot that was fetched; concurrent updates to
+            /// the set are only picked up by the next call.
+            ///
+            /// `ZRANDMEMBER` draws members uniformly and cannot weigh them
+            /// by score, which is why this helper is not a single command
+            /// and sits behind the `weighted-sample` feature.
+            #[cfg(feature = "weighted-sample")]
+            #[cfg_attr(docsrs, doc(cfg(feature = "weighted-sample")))]
+            #[inline]
+            fn weighted_sample<K: ToRedisArgs, RV: FromRedisValue>(&mut self, key: K, n: usize) -> RedisResult<Vec<RV>> {
+                let members: Vec<(Value, f64)> = cmd("ZRANGEBYSCORE")
+                    .arg(key)
+                    .arg("(0")
+                    .arg("+inf")
+                    .arg("WITHSCORES")
+                    .query(self)?;
+                if members.is_empty() {
+                    return Ok(vec![]);
+                }
+                let weights = members.iter().map(|(_, score)| *score);
+                // A score of `+inf`, or scores adding up to more than an
+                // `f64` holds, would make `WeightedIndex::new` panic.
+                if !weights.clone().sum::<f64>().is_finite() {
+                    fail!((
+                        crate::types::ErrorKind::TypeError,
+                        "Scores cannot be used as weights",
+                        "the scores add up to infinity".to_string(),
+                    ));
+                }
+                let dist = match WeightedIndex::new(weights) {
+                    Ok(dist) => dist,
+                    Err(err) => fail!((
+                        crate::types::ErrorKind::TypeError,
+                        "Scores cannot be used as weights",
+                        err.to_string(),
+                    )),
+                };
+                let mut rng = rand::thread_rng();
+                (0..n)
+                    .map(|_| from_redis_value(&members[dist.sample(&mut rng)].0))
+                    .collect()
+            }
+
+            /// Stops the server, optionally forcing (`Save`) or skipping
+            /// (`NoSave`) a final save of the dataset.
+            ///
+            /// The server closes the connection instead of replying when it
+            /// shuts down, so reaching the end of the stream while waiting for
+            /// the reply is reported as success.  An error is returned if the
+            /// command could not be sent, or if the server refused to shut
+            /// down, e.g. because the final save failed.  The connection
+            /// cannot be used afterwards.
+            #[inline]
+            fn shutdown(&mut self, mode: Option<ShutdownMode>) -> RedisResult<()> {
+                match cmd("SHUTDOWN").arg(mode).query::<Value>(self) {
+                    Ok(_) => Ok(()),
+                    // A failed write is reported as a broken pipe or reset,
+                    // only the reader sees the end of the stream.
+                    Err(err) if err.is_end_of_stream() => Ok(()),
+                    Err(err) => Err(err),
+                }
+            }
+
+            /// Returns the current server time as seconds and microseconds
+            /// since the unix epoch, as reported by `TIME`.
+            ///
+            /// This is useful for timestamps that have to be consistent across
+            /// processes that should not trust their local clocks.
+            #[inline]
+            fn server_time(&mut self) -> RedisResult<(u64, u32)> {
+                cmd("TIME").query(self)
+            }
+
+            /// Returns the current server time as a `SystemTime`.
+            ///
+            /// See `server_time` for the raw `TIME` reply.
+            #[inline]
+            fn server_system_time(&mut self) -> RedisResult<SystemTime> {
+                let (secs, micros) = self.server_time()?;
+                Ok(UNIX_EPOCH + Duration::new(secs, micros * 1_000))
+            }
+
+            /// Read a string value in chunks of at most `chunk_size` bytes
+            /// using `GETRANGE`, so a large value never has to be held in
+            /// memory at once.
+            ///
+            /// The length of the value is fetched with `STRLEN` when the
+            /// first chunk is requested.  If the value is modified while it
+            /// is being read the chunks may not add up to a consistent value.
+            ///
+            /// Panics if `chunk_size` is `0`.
+            #[inline]
+            fn read_value_chunked<K: ToRedisArgs>(&mut self, key: K, chunk_size: usize) -> ValueChunks<'_, Self> {
+                ValueChunks::new(self, key, chunk_size)
+            }
+
             /// Incrementally iterate the keys space.
             #[inline]
             fn scan<RV: FromRedisValue>(&mut self) -> RedisResult<Iter<'_, RV>> {
@@ -62,6 +180,17 @@ macro_rules! implement_commands {
                 c.iter(self)
             }
 
+            /// Incrementally iterate the keys space with options.
+            ///
+            /// The options can set a `MATCH` pattern, a `COUNT` hint and a
+            /// `TYPE` filter.
+            #[inline]
+            fn scan_options<RV: FromRedisValue>(&mut self, options: ScanOptions) -> RedisResult<Iter<'_, RV>> {
+                let mut c = cmd("SCAN");
+                c.cursor_arg(0).arg(options);
+                c.iter(self)
+            }
+
             /// Incrementally iterate hash fields and associated values.
             #[inline]
             fn hscan<K: ToRedisArgs, RV: FromRedisValue>(&mut self, key: K) -> RedisResult<Iter<'_, RV>> {
@@ -80,6 +209,17 @@ macro_rules! implement_commands {
                 c.iter(self)
             }
 
+            /// Incrementally iterate hash fields and associated values with options.
+            ///
+            /// The options can set a `MATCH` pattern and a `COUNT` hint.
+            #[inline]
+            fn hscan_options<K: ToRedisArgs, RV: FromRedisValue>
+                    (&mut self, key: K, options: ScanOptions) -> RedisResult<Iter<'_, RV>> {
+                let mut c = cmd("HSCAN");
+                c.arg(key).cursor_arg(0).arg(options);
+                c.iter(self)
+            }
+
             /// Incrementally iterate set elements.
             #[inline]
             fn sscan<K: ToRedisArgs, RV: FromRedisValue>(&mut self, key: K) -> RedisResult<Iter<'_, RV>> {
@@ -97,6 +237,17 @@ macro_rules! implement_commands {
                 c.iter(self)
             }
 
+            /// Incrementally iterate set elements with options.
+            ///
+            /// The options can set a `MATCH` pattern and a `COUNT` hint.
+            #[inline]
+            fn sscan_options<K: ToRedisArgs, RV: FromRedisValue>
+                    (&mut self, key: K, options: ScanOptions) -> RedisResult<Iter<'_, RV>> {
+                let mut c = cmd("SSCAN");
+                c.arg(key).cursor_arg(0).arg(options);
+                c.iter(self)
+            }
+
             /// Incrementally iterate sorted set elements.
             #[inline]
             fn zscan<K: ToRedisArgs, RV: FromRedisValue>(&mut self, key: K) -> RedisResult<Iter<'_, RV>> {
@@ -113,6 +264,17 @@ macro_rules! implement_commands {
                 c.arg(key).cursor_arg(0).arg("MATCH").arg(pattern);
                 c.iter(self)
             }
+
+            /// Incrementally iterate sorted set elements with options.
+            ///
+            /// The options can set a `MATCH` pattern and a `COUNT` hint.
+            #[inline]
+            fn zscan_options<K: ToRedisArgs, RV: FromRedisValue>
+                    (&mut self, key: K, options: ScanOptions) -> RedisResult<Iter<'_, RV>> {
+                let mut c = cmd("ZSCAN");
+                c.arg(key).cursor_arg(0).arg(options);
+                c.iter(self)
+            }
         }
 
         impl Cmd {
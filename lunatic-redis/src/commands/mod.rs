@@ -1,11 +1,16 @@
 // can't use rustfmt here because it screws up the file.
 #![cfg_attr(rustfmt, rustfmt_skip)]
+#[cfg(feature = "weighted-sample")]
+use rand::distributions::{Distribution, WeightedIndex};
+#[cfg(feature = "weighted-sample")]
+use crate::types::from_redis_value;
 use serde::{Serialize, Deserialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crate::cmd::{cmd, Cmd, Iter};
 use crate::connection::{ConnectionLike, Msg};
 use crate::pipeline::Pipeline;
-use crate::types::{FromRedisValue, NumericBehavior, RedisResult, ToRedisArgs, RedisWrite, Expiry};
+use crate::types::{FromRedisValue, NumericBehavior, RedisResult, ToRedisArgs, RedisWrite, Expiry, ExpireOption, Value};
 
 #[macro_use]
 mod macros;
@@ -55,6 +60,62 @@ pub(crate) fn is_readonly_cmd(cmd: &[u8]) -> bool {
     )
 }
 
+/// Returns the arity of well-known commands, as reported by `COMMAND INFO`:
+/// the number of arguments including the command name, or its negation if
+/// that is only the minimum.
+pub(crate) fn command_arity(cmd: &[u8]) -> Option<i64> {
+    let arity = match cmd {
+        // @connection
+        b"ECHO" | b"SELECT" => 2,
+        b"PING" => -1,
+        // @hash
+        b"HGETALL" | b"HKEYS" | b"HLEN" | b"HVALS" => 2,
+        b"HEXISTS" | b"HGET" => 3,
+        b"HINCRBY" | b"HINCRBYFLOAT" | b"HSETNX" => 4,
+        b"HDEL" | b"HMGET" => -3,
+        b"HSET" | b"HMSET" => -4,
+        // @hyperloglog
+        b"PFADD" | b"PFCOUNT" | b"PFMERGE" => -2,
+        // @keyspace
+        b"KEYS" | b"PERSIST" | b"PTTL" | b"TTL" | b"TYPE" => 2,
+        b"RENAME" | b"RENAMENX" => 3,
+        b"DEL" | b"EXISTS" | b"UNLINK" => -2,
+        b"EXPIRE" | b"EXPIREAT" | b"PEXPIRE" | b"PEXPIREAT" => -3,
+        // @list
+        b"LLEN" => 2,
+        b"LINDEX" => 3,
+        b"LRANGE" | b"LREM" | b"LSET" | b"LTRIM" => 4,
+        b"LMOVE" => 5,
+        b"BLMOVE" => 6,
+        b"LPOP" | b"RPOP" => -2,
+        b"BLPOP" | b"BRPOP" | b"LPUSH" | b"LPUSHX" | b"RPUSH" | b"RPUSHX" => -3,
+        // @pubsub
+        b"PUBLISH" => 3,
+        b"PSUBSCRIBE" | b"SUBSCRIBE" => -2,
+        // @set
+        b"SCARD" | b"SMEMBERS" => 2,
+        b"SISMEMBER" => 3,
+        b"SADD" | b"SREM" => -3,
+        // @sortedset
+        b"ZCARD" => 2,
+        b"ZSCORE" => 3,
+        b"ZINCRBY" => 4,
+        b"ZREM" => -3,
+        b"ZADD" | b"ZRANGE" => -4,
+        // @string
+        b"DECR" | b"GET" | b"GETDEL" | b"INCR" | b"STRLEN" => 2,
+        b"APPEND" | b"DECRBY" | b"GETSET" | b"INCRBY" | b"INCRBYFLOAT" | b"SETNX" => 3,
+        b"GETRANGE" | b"PSETEX" | b"SETEX" | b"SETRANGE" => 4,
+        b"GETEX" | b"MGET" => -2,
+        b"MSET" | b"MSETNX" | b"SET" => -3,
+        // @transaction
+        b"DISCARD" | b"EXEC" | b"MULTI" | b"UNWATCH" => 1,
+        b"WATCH" => -2,
+        _ => return None,
+    };
+    Some(arity)
+}
+
 implement_commands! {
     'a
     // most common operations
@@ -90,6 +151,8 @@ implement_commands! {
     }
 
     /// Set the value of a key, only if the key does not exist
+    ///
+    /// Returns `true` if the key was set.
     fn set_nx<K: ToRedisArgs, V: ToRedisArgs>(key: K, value: V) {
         cmd("SETNX").arg(key).arg(value)
     }
@@ -125,26 +188,52 @@ implement_commands! {
     }
 
     /// Set a key's time to live in seconds.
+    ///
+    /// Returns `false` if the key does not exist.
     fn expire<K: ToRedisArgs>(key: K, seconds: usize) {
         cmd("EXPIRE").arg(key).arg(seconds)
     }
 
+    /// Set a key's time to live in seconds if the condition holds.
+    ///
+    /// Replies with `1` if the timeout was set and `0` if the key does not
+    /// exist or the condition was not met.  Requires Redis 7.
+    fn expire_options<K: ToRedisArgs>(key: K, seconds: usize, option: ExpireOption) {
+        cmd("EXPIRE").arg(key).arg(seconds).arg(option)
+    }
+
     /// Set the expiration for a key as a UNIX timestamp.
+    ///
+    /// Returns `false` if the key does not exist.
     fn expire_at<K: ToRedisArgs>(key: K, ts: usize) {
         cmd("EXPIREAT").arg(key).arg(ts)
     }
 
     /// Set a key's time to live in milliseconds.
+    ///
+    /// Returns `false` if the key does not exist.
     fn pexpire<K: ToRedisArgs>(key: K, ms: usize) {
         cmd("PEXPIRE").arg(key).arg(ms)
     }
 
+    /// Set a key's time to live in milliseconds if the condition holds.
+    ///
+    /// Replies with `1` if the timeout was set and `0` if the key does not
+    /// exist or the condition was not met.  Requires Redis 7.
+    fn pexpire_options<K: ToRedisArgs>(key: K, ms: usize, option: ExpireOption) {
+        cmd("PEXPIRE").arg(key).arg(ms).arg(option)
+    }
+
     /// Set the expiration for a key as a UNIX timestamp in milliseconds.
+    ///
+    /// Returns `false` if the key does not exist.
     fn pexpire_at<K: ToRedisArgs>(key: K, ts: usize) {
         cmd("PEXPIREAT").arg(key).arg(ts)
     }
 
     /// Remove the expiration from a key.
+    ///
+    /// Returns `false` if the key has no timeout or does not exist.
     fn persist<K: ToRedisArgs>(key: K) {
         cmd("PERSIST").arg(key)
     }
@@ -160,6 +249,13 @@ implement_commands! {
     }
 
     /// Get the value of a key and set expiration
+    ///
+    /// With `Expiry::PERSIST` the time to live of the key is removed
+    /// instead, which keeps a cached value around for good once it is read.
+    ///
+    /// ```text
+    /// GETEX <key> [EX seconds | PX ms | EXAT timestamp | PXAT timestamp | PERSIST]
+    /// ```
     fn get_ex<K: ToRedisArgs>(key: K, expire_at: Expiry) {
         let (option, time_arg) = match expire_at {
             Expiry::EX(sec) => ("EX", Some(sec)),
@@ -173,6 +269,9 @@ implement_commands! {
     }
 
     /// Get the value of a key and delete it
+    ///
+    /// Replies with nil if the key did not exist, which reads as `None`
+    /// into an `Option`.
     fn get_del<K: ToRedisArgs>(key: K) {
         cmd("GETDEL").arg(key)
     }
@@ -183,10 +282,20 @@ implement_commands! {
     }
 
     /// Rename a key, only if the new key does not exist.
+    ///
+    /// Returns `false` if the new key already exists.
     fn rename_nx<K: ToRedisArgs>(key: K, new_key: K) {
         cmd("RENAMENX").arg(key).arg(new_key)
     }
 
+    /// Move a key to another database.
+    ///
+    /// Returns `false` if the key does not exist or already exists in the
+    /// target database.
+    fn move_key<K: ToRedisArgs>(key: K, db: i64) {
+        cmd("MOVE").arg(key).arg(db)
+    }
+
     /// Unlink one or more keys.
     fn unlink<K: ToRedisArgs>(key: K) {
         cmd("UNLINK").arg(key)
@@ -215,6 +324,9 @@ implement_commands! {
     }
 
     /// Sets or clears the bit at offset in the string value stored at key.
+    ///
+    /// The server grows the string as needed, so bitmaps indexed by large
+    /// ids only cost a single round trip.  Offsets must be below 2^32.
     fn setbit<K: ToRedisArgs>(key: K, offset: usize, value: bool) {
         cmd("SETBIT").arg(key).arg(offset).arg(if value {1} else {0})
     }
@@ -234,6 +346,12 @@ implement_commands! {
         cmd("BITCOUNT").arg(key).arg(start).arg(end)
     }
 
+    /// Count set bits in a string in a range of bytes or bits.  Negative
+    /// indices count from the end of the string.  `BIT` requires Redis 7.
+    fn bitcount_range_unit<K: ToRedisArgs>(key: K, start: isize, end: isize, unit: BitUnit) {
+        cmd("BITCOUNT").arg(key).arg(start).arg(end).arg(unit)
+    }
+
     /// Perform a bitwise AND between multiple keys (containing string values)
     /// and store the result in the destination key.
     fn bit_and<K: ToRedisArgs>(dstkey: K, srckeys: K) {
@@ -266,21 +384,28 @@ implement_commands! {
     // hash operations
 
     /// Gets a single (or multiple) fields from a hash.
+    ///
+    /// Like keys, field names are binary-safe: a byte slice such as
+    /// `&b"\x00\xff"[..]` is sent as a single field.
     fn hget<K: ToRedisArgs, F: ToRedisArgs>(key: K, field: F) {
         cmd(if field.is_single_arg() { "HGET" } else { "HMGET" }).arg(key).arg(field)
     }
 
-    /// Deletes a single (or multiple) fields from a hash.
+    /// Deletes a single (or multiple) fields from a hash. Field names are
+    /// binary-safe.
     fn hdel<K: ToRedisArgs, F: ToRedisArgs>(key: K, field: F) {
         cmd("HDEL").arg(key).arg(field)
     }
 
-    /// Sets a single field in a hash.
+    /// Sets a single field in a hash. The field name is binary-safe and may
+    /// be any byte string.
     fn hset<K: ToRedisArgs, F: ToRedisArgs, V: ToRedisArgs>(key: K, field: F, value: V) {
         cmd("HSET").arg(key).arg(field).arg(value)
     }
 
     /// Sets a single field in a hash if it does not exist.
+    ///
+    /// Returns `true` if the field was set.
     fn hset_nx<K: ToRedisArgs, F: ToRedisArgs, V: ToRedisArgs>(key: K, field: F, value: V) {
         cmd("HSETNX").arg(key).arg(field).arg(value)
     }
@@ -300,6 +425,8 @@ implement_commands! {
     }
 
     /// Checks if a field in a hash exists.
+    ///
+    /// Can be read as a `bool`.
     fn hexists<K: ToRedisArgs, F: ToRedisArgs>(key: K, field: F) {
         cmd("HEXISTS").arg(key).arg(field)
     }
@@ -417,12 +544,24 @@ implement_commands! {
 
     /// Removes the first count occurrences of elements equal to value
     /// from the list stored at key.
+    ///
+    /// The sign of `count` picks where to start:
+    ///
+    /// * `count > 0`: remove up to `count` matches moving from head to tail.
+    /// * `count < 0`: remove up to `-count` matches moving from tail to head.
+    /// * `count = 0`: remove all matches.
+    ///
+    /// Replies with the number of removed elements.
     fn lrem<K: ToRedisArgs, V: ToRedisArgs>(key: K, count: isize, value: V) {
         cmd("LREM").arg(key).arg(count).arg(value)
     }
 
     /// Trim an existing list so that it will contain only the specified
     /// range of elements specified.
+    ///
+    /// Both ends are inclusive and negative indexes count from the tail, so
+    /// `ltrim(key, 0, 99)` caps a list pushed to with `lpush` at its 100
+    /// newest entries.
     fn ltrim<K: ToRedisArgs>(key: K, start: isize, stop: isize) {
         cmd("LTRIM").arg(key).arg(start).arg(stop)
     }
@@ -488,6 +627,8 @@ implement_commands! {
     }
 
     /// Determine if a given value is a member of a set.
+    ///
+    /// Can be read as a `bool`.
     fn sismember<K: ToRedisArgs, M: ToRedisArgs>(key: K, member: M) {
         cmd("SISMEMBER").arg(key).arg(member)
     }
@@ -498,6 +639,8 @@ implement_commands! {
     }
 
     /// Move a member from one set to another.
+    ///
+    /// Returns `false` if the member is not in the source set.
     fn smove<K: ToRedisArgs, M: ToRedisArgs>(srckey: K, dstkey: K, member: M) {
         cmd("SMOVE").arg(srckey).arg(dstkey).arg(member)
     }
@@ -544,6 +687,63 @@ implement_commands! {
         cmd("ZADD").arg(key).arg(items)
     }
 
+    /// Increment the score of a member in a sorted set by delta using
+    /// `ZADD key INCR delta member`, adding it with delta as its score if
+    /// it does not exist.
+    ///
+    /// Replies with the new score.  Read it as `Option<f64>` when used via
+    /// `zadd_incr_options`, where conditions can make it a no-op.
+    fn zadd_incr<K: ToRedisArgs, M: ToRedisArgs, D: ToRedisArgs>(key: K, member: M, delta: D) {
+        cmd("ZADD").arg(key).arg("INCR").arg(delta).arg(member)
+    }
+
+    /// Like `zadd_incr` but with a `ZAddCondition`.
+    ///
+    /// If the condition prevents the update (e.g. `NX` for an existing member
+    /// or `GT` with a score that would not be raised) the server replies with
+    /// nil, so read the reply as `Option<f64>` to get `None` in that case.
+    ///
+    /// ```rust,no_run
+    /// use redis::{Commands, RedisResult, ZAddCondition, ZAddOptions};
+    /// # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    /// # let mut con = client.get_connection().unwrap();
+    /// let options = ZAddOptions::default().condition(ZAddCondition::Gt);
+    /// let score: Option<f64> = con
+    ///     .zadd_incr_options("leaderboard", "player", 10, options)
+    ///     .unwrap();
+    /// ```
+    fn zadd_incr_options<K: ToRedisArgs, M: ToRedisArgs, D: ToRedisArgs>(
+        key: K,
+        member: M,
+        delta: D,
+        options: ZAddOptions
+    ) {
+        cmd("ZADD").arg(key).arg(options).arg("INCR").arg(delta).arg(member)
+    }
+
+    /// Add multiple members to a sorted set with a `ZAddCondition` or `CH`.
+    ///
+    /// The reply is the number of added members (or changed ones with
+    /// `CH`).  Use `zadd_incr_options` to increment a score instead.
+    ///
+    /// ```rust,no_run
+    /// use redis::{Commands, RedisResult, ZAddCondition, ZAddOptions};
+    /// # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    /// # let mut con = client.get_connection().unwrap();
+    /// // Only ever raise the high scores
+    /// let options = ZAddOptions::default().condition(ZAddCondition::Gt).ch();
+    /// let raised: usize = con
+    ///     .zadd_options("highscores", options, &[(10, "alice"), (7, "bob")])
+    ///     .unwrap();
+    /// ```
+    fn zadd_options<K: ToRedisArgs, S: ToRedisArgs, M: ToRedisArgs>(
+        key: K,
+        options: ZAddOptions,
+        items: &'a [(S, M)]
+    ) {
+        cmd("ZADD").arg(key).arg(options).arg(items)
+    }
+
     /// Get the number of members in a sorted set.
     fn zcard<K: ToRedisArgs>(key: K) {
         cmd("ZCARD").arg(key)
@@ -560,6 +760,41 @@ implement_commands! {
         cmd("ZINCRBY").arg(key).arg(delta).arg(member)
     }
 
+    /// Return the members of the first sorted set that are not in any of
+    /// the other ones.
+    fn zdiff<K: ToRedisArgs>(keys: &'a [K]) {
+        cmd("ZDIFF").arg(keys.len()).arg(keys)
+    }
+
+    /// Return the members of the first sorted set that are not in any of
+    /// the other ones, with their scores.
+    fn zdiff_withscores<K: ToRedisArgs>(keys: &'a [K]) {
+        cmd("ZDIFF").arg(keys.len()).arg(keys).arg("WITHSCORES")
+    }
+
+    /// Return the intersection of multiple sorted sets using SUM as
+    /// aggregation function, without storing it.
+    fn zinter<K: ToRedisArgs>(keys: &'a [K]) {
+        cmd("ZINTER").arg(keys.len()).arg(keys)
+    }
+
+    /// Return the intersection of multiple sorted sets, without storing it,
+    /// with weights, aggregation function and scores set by `options`.
+    ///
+    /// ```rust,no_run
+    /// use redis::{Commands, ZAggregate, ZCombineOptions};
+    /// # fn do_something(con: &mut redis::Connection) -> redis::RedisResult<()> {
+    /// let opts = ZCombineOptions::default()
+    ///     .weights(&[1.0, 2.0])
+    ///     .aggregate(ZAggregate::Max)
+    ///     .withscores();
+    /// let scores: Vec<(String, f64)> = con.zinter_options(&["views", "likes"], opts)?;
+    /// # Ok(()) }
+    /// ```
+    fn zinter_options<K: ToRedisArgs>(keys: &'a [K], options: ZCombineOptions) {
+        cmd("ZINTER").arg(keys.len()).arg(keys).arg(options)
+    }
+
     /// Intersect multiple sorted sets and store the resulting sorted set in
     /// a new key using SUM as aggregation function.
     fn zinterstore<K: ToRedisArgs>(dstkey: K, keys: &'a [K]) {
@@ -645,6 +880,10 @@ implement_commands! {
     }
 
     /// Return a range of members in a sorted set, by index with scores.
+    ///
+    /// Read the reply as `Vec<(M, f64)>` to get `(member, score)` pairs in
+    /// order.  This works with the flat reply of RESP2 as well as the nested
+    /// pairs of RESP3, and for all other `*_withscores` commands.
     fn zrange_withscores<K: ToRedisArgs>(key: K, start: isize, stop: isize) {
         cmd("ZRANGE").arg(key).arg(start).arg(stop).arg("WITHSCORES")
     }
@@ -771,6 +1010,18 @@ implement_commands! {
         cmd("ZMSCORE").arg(key).arg(members)
     }
 
+    /// Return the union of multiple sorted sets using SUM as aggregation
+    /// function, without storing it.
+    fn zunion<K: ToRedisArgs>(keys: &'a [K]) {
+        cmd("ZUNION").arg(keys.len()).arg(keys)
+    }
+
+    /// Return the union of multiple sorted sets, without storing it, with
+    /// weights, aggregation function and scores set by `options`.
+    fn zunion_options<K: ToRedisArgs>(keys: &'a [K], options: ZCombineOptions) {
+        cmd("ZUNION").arg(keys.len()).arg(keys).arg(options)
+    }
+
     /// Unions multiple sorted sets and store the resulting sorted set in
     /// a new key using SUM as aggregation function.
     fn zunionstore<K: ToRedisArgs>(dstkey: K, keys: &'a [K]) {
@@ -816,26 +1067,74 @@ implement_commands! {
     // hyperloglog commands
 
     /// Adds the specified elements to the specified HyperLogLog.
+    ///
+    /// `element` may be a single value or a slice of them.  Returns `true`
+    /// if the estimated cardinality changed.
     fn pfadd<K: ToRedisArgs, E: ToRedisArgs>(key: K, element: E) {
         cmd("PFADD").arg(key).arg(element)
     }
 
     /// Return the approximated cardinality of the set(s) observed by the
     /// HyperLogLog at key(s).
+    ///
+    /// Given a slice of keys this estimates the cardinality of their union.
     fn pfcount<K: ToRedisArgs>(key: K) {
         cmd("PFCOUNT").arg(key)
     }
 
     /// Merge N different HyperLogLogs into a single one.
+    ///
+    /// To merge several sources pass the destination as a slice too, e.g.
+    /// `pfmerge(&["dst"][..], &["a", "b"][..])`.
     fn pfmerge<K: ToRedisArgs>(dstkey: K, srckeys: K) {
         cmd("PFMERGE").arg(dstkey).arg(srckeys)
     }
 
     /// Posts a message to the given channel.
+    ///
+    /// The message is sent as is, so binary payloads such as `&[u8]` or
+    /// `Vec<u8>` arrive byte for byte and can be read back with
+    /// `Msg::get_payload::<Vec<u8>>()`.
     fn publish<K: ToRedisArgs, E: ToRedisArgs>(channel: K, message: E) {
         cmd("PUBLISH").arg(channel).arg(message)
     }
 
+    // transaction commands
+
+    /// Marks the start of a transaction block.
+    ///
+    /// Until `exec` or `discard` is called, every command sent on the
+    /// connection is queued instead of executed and replies with the status
+    /// `QUEUED`, so only decode those replies into `()` or `String`.  Reads
+    /// between `multi` and `exec` do not see any data.  Prefer
+    /// [`transaction`](fn.transaction.html) or an atomic pipeline unless the
+    /// commands need to be sent one by one.
+    ///
+    /// ```rust,no_run
+    /// # fn do_something(con: &mut redis::Connection) -> redis::RedisResult<()> {
+    /// use redis::Commands;
+    /// let _: () = con.multi()?;
+    /// let _: () = con.incr("counter", 1)?;
+    /// let _: () = con.set("flag", true)?;
+    /// let (counter, _): (i64, ()) = con.exec()?;
+    /// # Ok(()) }
+    /// ```
+    fn multi<>() {
+        &mut cmd("MULTI")
+    }
+
+    /// Executes all commands queued since `multi` and returns their replies
+    /// as an array, or nil if a watched key was modified.
+    fn exec<>() {
+        &mut cmd("EXEC")
+    }
+
+    /// Throws away all commands queued since `multi` and leaves the
+    /// transaction.  Watched keys are unwatched as well.
+    fn discard<>() {
+        &mut cmd("DISCARD")
+    }
+
     // Object commands
 
     /// Returns the encoding of a key.
@@ -858,6 +1157,186 @@ implement_commands! {
         cmd("OBJECT").arg("REFCOUNT").arg(key)
     }
 
+    // client commands
+
+    /// Returns the id of the current connection.
+    fn client_id<>() {
+        cmd("CLIENT").arg("ID")
+    }
+
+    /// Returns information about all client connections to the server, one
+    /// line per client.
+    fn client_list<>() {
+        cmd("CLIENT").arg("LIST")
+    }
+
+    /// Closes the client connections that match all of the given filters
+    /// and returns the number of clients killed.
+    ///
+    /// ```rust,no_run
+    /// use redis::{ClientKillFilter, Commands};
+    /// # fn do_something(con: &mut redis::Connection, id: u64) -> redis::RedisResult<()> {
+    /// let killed: usize = con.client_kill(ClientKillFilter::default().id(id))?;
+    /// # Ok(()) }
+    /// ```
+    fn client_kill<>(filter: ClientKillFilter) {
+        cmd("CLIENT").arg("KILL").arg(filter)
+    }
+
+    // config commands
+
+    /// Returns the configuration parameters matching a glob-style pattern
+    /// as pairs of names and values.
+    ///
+    /// Read the reply into a map with `ConfigValue` values to keep
+    /// compound values such as `save` intact:
+    ///
+    /// ```rust,no_run
+    /// use redis::{Commands, ConfigValue};
+    /// use std::collections::HashMap;
+    /// # fn do_something(con: &mut redis::Connection) -> redis::RedisResult<()> {
+    /// let config: HashMap<String, ConfigValue> = con.config_get("save")?;
+    /// for token in config["save"].tokens() {
+    ///     println!("{}", token);
+    /// }
+    /// # Ok(()) }
+    /// ```
+    fn config_get<K: ToRedisArgs>(pattern: K) {
+        cmd("CONFIG").arg("GET").arg(pattern)
+    }
+
+    /// Sets a configuration parameter at runtime.
+    ///
+    /// A value with several tokens, like `save`, must be passed as a
+    /// single argument, e.g. a `ConfigValue` or a `&str`.  A `Vec` of
+    /// tokens would be sent as separate arguments and misread.
+    fn config_set<K: ToRedisArgs, V: ToRedisArgs>(parameter: K, value: V) {
+        cmd("CONFIG").arg("SET").arg(parameter).arg(value)
+    }
+
+    // replication commands
+
+    /// Starts a coordinated failover from this primary to one of its
+    /// replicas.
+    ///
+    /// The command only starts the failover, use `INFO replication` to
+    /// follow its progress.
+    ///
+    /// ```rust,no_run
+    /// use redis::{Commands, FailoverOptions};
+    /// # fn do_something(con: &mut redis::Connection) -> redis::RedisResult<()> {
+    /// let opts = FailoverOptions::default().to("10.0.0.2", 6379).timeout(5000);
+    /// let _: () = con.failover(opts)?;
+    /// # Ok(()) }
+    /// ```
+    fn failover<>(options: FailoverOptions) {
+        cmd("FAILOVER").arg(options)
+    }
+
+    /// Aborts an ongoing coordinated failover.
+    fn failover_abort<>() {
+        cmd("FAILOVER").arg("ABORT")
+    }
+
+    // cluster commands
+
+    /// Returns the node id of the cluster node this connection is talking to.
+    #[cfg(feature = "cluster")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "cluster")))]
+    fn cluster_myid<>() {
+        cmd("CLUSTER").arg("MYID")
+    }
+
+    /// Returns the cluster configuration as seen by the node this connection
+    /// is talking to.
+    ///
+    /// The reply can be read into a `Vec<ClusterNode>` to get one parsed
+    /// entry per node.
+    #[cfg(feature = "cluster")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "cluster")))]
+    fn cluster_nodes<>() {
+        cmd("CLUSTER").arg("NODES")
+    }
+
+    /// Returns the number of keys in a hash slot. Only keys served by the
+    /// node are counted; a cluster connection sends this to the slot's
+    /// master.
+    #[cfg(feature = "cluster")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "cluster")))]
+    fn cluster_countkeysinslot<>(slot: u16) {
+        cmd("CLUSTER").arg("COUNTKEYSINSLOT").arg(slot)
+    }
+
+    /// Returns up to `count` key names stored in a hash slot, as used by
+    /// migration tooling to move slots between nodes.
+    #[cfg(feature = "cluster")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "cluster")))]
+    fn cluster_getkeysinslot<>(slot: u16, count: usize) {
+        cmd("CLUSTER").arg("GETKEYSINSLOT").arg(slot).arg(count)
+    }
+
+    // scripting commands
+
+    /// Kills the Lua script that is currently running, as long as it has
+    /// not performed any write yet.
+    ///
+    /// A busy script blocks the connection that runs it, so this has to be
+    /// sent from a different connection.  The connection that invoked the
+    /// script receives an error reply.
+    #[cfg(feature = "script")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "script")))]
+    fn script_kill<>() {
+        cmd("SCRIPT").arg("KILL")
+    }
+
+    /// Kills the function that is currently running, as long as it has not
+    /// performed any write yet.
+    ///
+    /// Like `script_kill` this has to be sent from a different connection
+    /// than the one running the function.
+    #[cfg(feature = "script")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "script")))]
+    fn function_kill<>() {
+        cmd("FUNCTION").arg("KILL")
+    }
+
+    // debug commands
+
+    /// Enables or disables the active expire cycle of the server.
+    ///
+    /// With active expire disabled, keys whose TTL has passed are only
+    /// removed lazily when they are accessed, which makes expiry behavior
+    /// deterministic in tests.  This is a `DEBUG` subcommand and must not be
+    /// used against production servers.
+    #[cfg(feature = "debug-commands")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "debug-commands")))]
+    fn debug_set_active_expire<>(enabled: bool) {
+        cmd("DEBUG").arg("SET-ACTIVE-EXPIRE").arg(enabled)
+    }
+
+    /// Sets the size in bytes above which list elements are stored in their
+    /// own plain quicklist node instead of being packed.
+    ///
+    /// Lowering the threshold lets tests force a list into the `quicklist`
+    /// encoding with small elements.  This is a `DEBUG` subcommand and must
+    /// not be used against production servers.
+    #[cfg(feature = "debug-commands")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "debug-commands")))]
+    fn debug_quicklist_packed_threshold<>(bytes: usize) {
+        cmd("DEBUG").arg("QUICKLIST-PACKED-THRESHOLD").arg(bytes)
+    }
+
+    /// Saves the dataset to the RDB file, empties the database and loads
+    /// it back from the file.
+    ///
+    /// This lets tests check that values survive persistence.  This is a
+    /// `DEBUG` subcommand and must not be used against production servers.
+    #[cfg(feature = "debug-commands")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "debug-commands")))]
+    fn debug_reload<>() {
+        cmd("DEBUG").arg("RELOAD")
+    }
+
     // ACL commands
 
     /// When Redis is configured to use an ACL file (with the aclfile
@@ -1367,6 +1846,66 @@ implement_commands! {
     }
 
 
+    /// Transfer ownership of pending messages that were idle for at least
+    /// `min_idle_time` milliseconds to `consumer`, scanning the pending
+    /// entries list from `start` (use `0-0` to begin).
+    ///
+    /// This is the building block for recovering messages of dead consumers.
+    /// Take note of the StreamAutoClaimReply return type; keep calling with
+    /// its `next_stream_id` until it is `0-0` to scan the whole list.
+    ///
+    /// ```text
+    /// XAUTOCLAIM <key> <group> <consumer> <min-idle-time> <start>
+    /// ```
+    #[cfg(feature = "streams")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "streams")))]
+    fn xautoclaim<K: ToRedisArgs, G: ToRedisArgs, C: ToRedisArgs, MIT: ToRedisArgs, S: ToRedisArgs>(
+        key: K,
+        group: G,
+        consumer: C,
+        min_idle_time: MIT,
+        start: S
+    ) {
+        cmd("XAUTOCLAIM")
+            .arg(key)
+            .arg(group)
+            .arg(consumer)
+            .arg(min_idle_time)
+            .arg(start)
+    }
+
+    /// This is the optional arguments version of `xautoclaim`.
+    ///
+    /// ```text
+    /// XAUTOCLAIM <key> <group> <consumer> <min-idle-time> <start>
+    ///     [COUNT <count>] [JUSTID]
+    /// ```
+    #[cfg(feature = "streams")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "streams")))]
+    fn xautoclaim_options<
+        K: ToRedisArgs,
+        G: ToRedisArgs,
+        C: ToRedisArgs,
+        MIT: ToRedisArgs,
+        S: ToRedisArgs
+    >(
+        key: K,
+        group: G,
+        consumer: C,
+        min_idle_time: MIT,
+        start: S,
+        options: streams::StreamAutoClaimOptions
+    ) {
+        cmd("XAUTOCLAIM")
+            .arg(key)
+            .arg(group)
+            .arg(consumer)
+            .arg(min_idle_time)
+            .arg(start)
+            .arg(options)
+    }
+
+
     /// Deletes a list of `id`s for a given stream `key`.
     ///
     /// ```text
@@ -2022,6 +2561,500 @@ impl ToRedisArgs for LposOptions {
     }
 }
 
+/// Options for the `SCAN` family of commands, used with [`scan_options`],
+/// [`hscan_options`], [`sscan_options`] and [`zscan_options`].
+///
+/// ```rust,no_run
+/// use redis::{Commands, RedisResult, ScanOptions};
+/// fn sessions(con: &mut redis::Connection) -> RedisResult<Vec<(String, String)>> {
+///     let opts = ScanOptions::default().pattern("session:*").count(500);
+///     Ok(con.hscan_options("users", opts)?.collect())
+/// }
+/// ```
+///
+/// [`scan_options`]: trait.Commands.html#method.scan_options
+/// [`hscan_options`]: trait.Commands.html#method.hscan_options
+/// [`sscan_options`]: trait.Commands.html#method.sscan_options
+/// [`zscan_options`]: trait.Commands.html#method.zscan_options
+#[derive(Default, Clone, Deserialize, Serialize)]
+pub struct ScanOptions {
+    pattern: Option<String>,
+    count: Option<usize>,
+    key_type: Option<String>,
+}
+
+impl ScanOptions {
+    /// Only return elements matching the glob-style pattern.
+    ///
+    /// The pattern is applied after fetching a batch, so some batches may
+    /// be empty; the iterator keeps going until the cursor is exhausted.
+    pub fn pattern<P: Into<String>>(mut self, pattern: P) -> Self {
+        self.pattern = Some(pattern.into());
+        self
+    }
+
+    /// Hint how many elements the server should look at per batch.
+    pub fn count(mut self, n: usize) -> Self {
+        self.count = Some(n);
+        self
+    }
+
+    /// Only return keys holding a value of the given type, e.g. `"string"`
+    /// or `"hash"`.
+    ///
+    /// This is only understood by `SCAN`, and requires redis 6 or newer.
+    pub fn key_type<T: Into<String>>(mut self, key_type: T) -> Self {
+        self.key_type = Some(key_type.into());
+        self
+    }
+}
+
+impl ToRedisArgs for ScanOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        if let Some(ref pattern) = self.pattern {
+            out.write_arg(b"MATCH");
+            out.write_arg(pattern.as_bytes());
+        }
+
+        if let Some(n) = self.count {
+            out.write_arg(b"COUNT");
+            out.write_arg_fmt(n);
+        }
+
+        if let Some(ref key_type) = self.key_type {
+            out.write_arg(b"TYPE");
+            out.write_arg(key_type.as_bytes());
+        }
+    }
+
+    fn is_single_arg(&self) -> bool {
+        false
+    }
+}
+
+/// Iterator over the chunks of a string value, returned by
+/// [`read_value_chunked`].
+///
+/// [`read_value_chunked`]: trait.Commands.html#method.read_value_chunked
+pub struct ValueChunks<'a, C: ConnectionLike> {
+    con: &'a mut C,
+    key: Vec<Vec<u8>>,
+    chunk_size: usize,
+    offset: usize,
+    len: Option<usize>,
+    done: bool,
+}
+
+impl<'a, C: ConnectionLike> ValueChunks<'a, C> {
+    fn new<K: ToRedisArgs>(con: &'a mut C, key: K, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "chunk_size must be greater than zero");
+        ValueChunks {
+            con,
+            key: key.to_redis_args(),
+            chunk_size,
+            offset: 0,
+            len: None,
+            done: false,
+        }
+    }
+
+    fn next_chunk(&mut self) -> RedisResult<Option<Vec<u8>>> {
+        let len = match self.len {
+            Some(len) => len,
+            None => {
+                let len: usize = cmd("STRLEN").arg(&self.key).query(&mut *self.con)?;
+                self.len = Some(len);
+                len
+            }
+        };
+        if self.offset >= len {
+            return Ok(None);
+        }
+        let end = (self.offset + self.chunk_size).min(len) - 1;
+        let chunk: Vec<u8> = cmd("GETRANGE")
+            .arg(&self.key)
+            .arg(self.offset)
+            .arg(end)
+            .query(&mut *self.con)?;
+        // The value shrank in the meantime.
+        if chunk.is_empty() {
+            return Ok(None);
+        }
+        self.offset += chunk.len();
+        Ok(Some(chunk))
+    }
+}
+
+impl<C: ConnectionLike> Iterator for ValueChunks<'_, C> {
+    type Item = RedisResult<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_chunk() {
+            Ok(Some(chunk)) => Some(Ok(chunk)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Options for the [ZADD](https://redis.io/commands/zadd) command
+#[derive(Default, Clone, Debug, Deserialize, Serialize)]
+pub struct ZAddOptions {
+    condition: Option<ZAddCondition>,
+    ch: bool,
+}
+
+/// When [ZADD](https://redis.io/commands/zadd) adds or updates a member
+///
+/// `NX` can't be combined with `GT` or `LT`, so only the combinations the
+/// server accepts are listed.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub enum ZAddCondition {
+    /// Only add new members, never update existing ones (`NX`)
+    Nx,
+    /// Only update existing members, never add new ones (`XX`)
+    Xx,
+    /// Only update existing members if the new score is greater, new
+    /// members are still added (`GT`)
+    Gt,
+    /// Only update existing members if the new score is less, new members
+    /// are still added (`LT`)
+    Lt,
+    /// Only update existing members, and only if the new score is greater
+    /// (`XX GT`)
+    XxGt,
+    /// Only update existing members, and only if the new score is less
+    /// (`XX LT`)
+    XxLt,
+}
+
+impl ZAddOptions {
+    /// Set when members are added or updated.
+    pub fn condition(mut self, condition: ZAddCondition) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+
+    /// Reply with the number of changed members instead of added ones.
+    pub fn ch(mut self) -> Self {
+        self.ch = true;
+        self
+    }
+}
+
+impl ToRedisArgs for ZAddOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        match self.condition {
+            Some(ZAddCondition::Nx) => out.write_arg(b"NX"),
+            Some(ZAddCondition::Xx) => out.write_arg(b"XX"),
+            Some(ZAddCondition::Gt) => out.write_arg(b"GT"),
+            Some(ZAddCondition::Lt) => out.write_arg(b"LT"),
+            Some(ZAddCondition::XxGt) => {
+                out.write_arg(b"XX");
+                out.write_arg(b"GT");
+            }
+            Some(ZAddCondition::XxLt) => {
+                out.write_arg(b"XX");
+                out.write_arg(b"LT");
+            }
+            None => {}
+        }
+        if self.ch {
+            out.write_arg(b"CH");
+        }
+    }
+
+    fn is_single_arg(&self) -> bool {
+        false
+    }
+}
+
+/// Filters for the [CLIENT KILL](https://redis.io/commands/client-kill)
+/// command
+///
+/// A client has to match all filters that are set to be killed.
+#[derive(Default, Clone, Debug, Deserialize, Serialize)]
+pub struct ClientKillFilter {
+    id: Option<u64>,
+    addr: Option<String>,
+    laddr: Option<String>,
+    client_type: Option<ClientType>,
+    skipme: Option<bool>,
+}
+
+/// The kinds of clients that can be matched by [`ClientKillFilter`].
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub enum ClientType {
+    /// Regular clients
+    Normal,
+    /// Clients that are the master of this replica
+    Master,
+    /// Replicas connected to this server
+    Replica,
+    /// Clients in pubsub mode
+    PubSub,
+}
+
+impl ClientKillFilter {
+    /// Kill the client with the given id, as returned by `CLIENT ID`.
+    pub fn id(mut self, id: u64) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    /// Kill clients connected from the given `ip:port` address.
+    pub fn addr<A: Into<String>>(mut self, addr: A) -> Self {
+        self.addr = Some(addr.into());
+        self
+    }
+
+    /// Kill clients connected to the given local `ip:port` address of the
+    /// server.
+    pub fn laddr<A: Into<String>>(mut self, laddr: A) -> Self {
+        self.laddr = Some(laddr.into());
+        self
+    }
+
+    /// Kill clients of the given type.
+    pub fn client_type(mut self, client_type: ClientType) -> Self {
+        self.client_type = Some(client_type);
+        self
+    }
+
+    /// Whether the client sending the command may be killed too.  The
+    /// server default is to skip it.
+    pub fn skipme(mut self, skipme: bool) -> Self {
+        self.skipme = Some(skipme);
+        self
+    }
+}
+
+impl ToRedisArgs for ClientKillFilter {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        if let Some(id) = self.id {
+            out.write_arg(b"ID");
+            out.write_arg_fmt(id);
+        }
+        if let Some(ref addr) = self.addr {
+            out.write_arg(b"ADDR");
+            out.write_arg(addr.as_bytes());
+        }
+        if let Some(ref laddr) = self.laddr {
+            out.write_arg(b"LADDR");
+            out.write_arg(laddr.as_bytes());
+        }
+        if let Some(client_type) = self.client_type {
+            let s: &[u8] = match client_type {
+                ClientType::Normal => b"normal",
+                ClientType::Master => b"master",
+                ClientType::Replica => b"replica",
+                ClientType::PubSub => b"pubsub",
+            };
+            out.write_arg(b"TYPE");
+            out.write_arg(s);
+        }
+        if let Some(skipme) = self.skipme {
+            let s: &[u8] = if skipme { b"yes" } else { b"no" };
+            out.write_arg(b"SKIPME");
+            out.write_arg(s);
+        }
+    }
+
+    fn is_single_arg(&self) -> bool {
+        false
+    }
+}
+
+/// Options for the [FAILOVER](https://redis.io/commands/failover) command
+#[derive(Default, Clone, Debug, Deserialize, Serialize)]
+pub struct FailoverOptions {
+    to: Option<(String, u16)>,
+    force: bool,
+    timeout: Option<u64>,
+}
+
+impl FailoverOptions {
+    /// Fail over to the replica at the given host and port instead of
+    /// letting the server pick one.
+    pub fn to<H: Into<String>>(mut self, host: H, port: u16) -> Self {
+        self.to = Some((host.into(), port));
+        self
+    }
+
+    /// Fail over to the target even if it did not catch up within the
+    /// timeout.  Requires both `to` and `timeout` to be set.
+    pub fn force(mut self) -> Self {
+        self.force = true;
+        self
+    }
+
+    /// Abort the failover if it does not complete within the given number
+    /// of milliseconds.
+    pub fn timeout(mut self, milliseconds: u64) -> Self {
+        self.timeout = Some(milliseconds);
+        self
+    }
+}
+
+impl ToRedisArgs for FailoverOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        if let Some((ref host, port)) = self.to {
+            out.write_arg(b"TO");
+            out.write_arg(host.as_bytes());
+            out.write_arg_fmt(port);
+            if self.force {
+                out.write_arg(b"FORCE");
+            }
+        }
+        if let Some(ms) = self.timeout {
+            out.write_arg(b"TIMEOUT");
+            out.write_arg_fmt(ms);
+        }
+    }
+
+    fn is_single_arg(&self) -> bool {
+        false
+    }
+}
+
+/// Options for the [ZUNION](https://redis.io/commands/zunion) and
+/// [ZINTER](https://redis.io/commands/zinter) commands
+#[derive(Default, Clone, Debug, Deserialize, Serialize)]
+pub struct ZCombineOptions {
+    weights: Option<Vec<f64>>,
+    aggregate: Option<ZAggregate>,
+    withscores: bool,
+}
+
+/// How the scores of a member that is in several sorted sets are combined
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub enum ZAggregate {
+    /// Add up the scores (the default)
+    Sum,
+    /// Use the lowest score
+    Min,
+    /// Use the highest score
+    Max,
+}
+
+impl ZCombineOptions {
+    /// Multiply the scores of each sorted set by a factor, one per key in
+    /// the same order as the keys.
+    pub fn weights(mut self, weights: &[f64]) -> Self {
+        self.weights = Some(weights.to_vec());
+        self
+    }
+
+    /// Set how the scores of a member are combined.
+    pub fn aggregate(mut self, aggregate: ZAggregate) -> Self {
+        self.aggregate = Some(aggregate);
+        self
+    }
+
+    /// Reply with the score of each member after it.
+    pub fn withscores(mut self) -> Self {
+        self.withscores = true;
+        self
+    }
+}
+
+impl ToRedisArgs for ZCombineOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        if let Some(ref weights) = self.weights {
+            out.write_arg(b"WEIGHTS");
+            for weight in weights {
+                weight.write_redis_args(out);
+            }
+        }
+        if let Some(aggregate) = self.aggregate {
+            let s: &[u8] = match aggregate {
+                ZAggregate::Sum => b"SUM",
+                ZAggregate::Min => b"MIN",
+                ZAggregate::Max => b"MAX",
+            };
+            out.write_arg(b"AGGREGATE");
+            out.write_arg(s);
+        }
+        if self.withscores {
+            out.write_arg(b"WITHSCORES");
+        }
+    }
+
+    fn is_single_arg(&self) -> bool {
+        false
+    }
+}
+
+/// Whether the server saves the dataset when it is stopped with
+/// [`shutdown`](trait.Commands.html#method.shutdown)
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub enum ShutdownMode {
+    /// Save the dataset even if no save points are configured
+    Save,
+    /// Do not save the dataset even if save points are configured
+    NoSave,
+}
+
+impl ToRedisArgs for ShutdownMode {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        let s: &[u8] = match self {
+            ShutdownMode::Save => b"SAVE",
+            ShutdownMode::NoSave => b"NOSAVE",
+        };
+        out.write_arg(s);
+    }
+}
+
+/// The unit of the range given to
+/// [`bitcount_range_unit`](trait.Commands.html#method.bitcount_range_unit)
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub enum BitUnit {
+    /// The range is in bytes, the default of the server
+    Byte,
+    /// The range is in bits
+    Bit,
+}
+
+impl ToRedisArgs for BitUnit {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        let s: &[u8] = match self {
+            BitUnit::Byte => b"BYTE",
+            BitUnit::Bit => b"BIT",
+        };
+        out.write_arg(s);
+    }
+}
+
 /// Enum for the LEFT | RIGHT args used by some commands
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum Direction {
@@ -2043,3 +3076,30 @@ impl ToRedisArgs for Direction {
         out.write_arg(s);
     }
 }
+
+#[cfg(all(test, feature = "weighted-sample"))]
+mod test {
+    use super::Commands;
+    use crate::fake_server::fake_server;
+    use crate::types::ErrorKind;
+
+    #[lunatic::test]
+    fn test_weighted_sample() {
+        let (mut con, _server) = fake_server(
+            b"*0\r\n\
+              *4\r\n$1\r\na\r\n$1\r\n1\r\n$1\r\nb\r\n$1\r\n3\r\n\
+              *4\r\n$1\r\na\r\n$1\r\n1\r\n$1\r\nb\r\n$3\r\ninf\r\n",
+        );
+
+        // An empty set has nothing to draw from.
+        assert_eq!(con.weighted_sample::<_, String>("z", 3), Ok(vec![]));
+
+        let drawn: Vec<String> = con.weighted_sample("z", 100).unwrap();
+        assert_eq!(drawn.len(), 100);
+        assert!(drawn.iter().all(|m| m == "a" || m == "b"));
+
+        // Sampling by an infinite score is not possible.
+        let err = con.weighted_sample::<_, String>("z", 1).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TypeError);
+    }
+}
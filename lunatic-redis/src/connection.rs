@@ -1,12 +1,13 @@
 use lunatic::net::{TcpStream, TlsStream, ToSocketAddrs};
 use serde;
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::fmt;
 use std::io::{self, Read, Write};
 use std::ops::DerefMut;
 use std::path::PathBuf;
 use std::str::{from_utf8, FromStr};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crate::cmd::{cmd, pipe, Cmd};
 use crate::parser::Parser;
@@ -34,8 +35,9 @@ pub fn parse_redis_url(input: &str) -> Option<url::Url> {
 /// Defines the connection address.
 ///
 /// Not all connection addresses are supported on all platforms.  For instance
-/// to connect to a unix socket you need to run this on an operating system
-/// that supports them.
+/// the lunatic runtime has no unix socket primitives, so `Unix` addresses can
+/// be parsed and passed around but connecting to them fails with an
+/// `InvalidClientConfig` error.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Eq)]
 pub enum ConnectionAddr {
     /// Format for this is `(host, port)`.
@@ -65,9 +67,8 @@ impl ConnectionAddr {
     ///
     /// Because not all platforms support all connection addresses this is a
     /// quick way to figure out if a connection method is supported.  Currently
-    /// this only affects unix connections which are only supported on unix
-    /// platforms and on older versions of rust also require an explicit feature
-    /// to be enabled.
+    /// this only affects unix connections, which the lunatic runtime can't
+    /// open.
     pub fn is_supported(&self) -> bool {
         match *self {
             ConnectionAddr::Tcp(_, _) => true,
@@ -106,6 +107,68 @@ pub struct RedisConnectionInfo {
     pub username: Option<String>,
     /// Optionally a password that should be used for connection.
     pub password: Option<String>,
+    /// Always send `SELECT` when connecting, even for database `0`.
+    ///
+    /// By default `SELECT` is skipped for database `0` as that is the
+    /// server default.  Turn this on if the server (or a proxy in front of
+    /// it) may default to another database.  Can be enabled in a redis URL
+    /// with `?always_select=true`.
+    pub always_select: bool,
+    /// The protocol the connection talks after the handshake.
+    ///
+    /// For `RESP3` a `HELLO 3` is sent once the connection is
+    /// authenticated.  Can be set in a redis URL with `?protocol=resp3`.
+    pub protocol: ProtocolVersion,
+    /// Optionally a name for the connection, set with `CLIENT SETNAME` once
+    /// it is authenticated.
+    ///
+    /// The name shows up in `CLIENT LIST`, which helps to tell apart the
+    /// connections of different processes.  Can be set in a redis URL with
+    /// `?client_name=worker`.
+    pub client_name: Option<String>,
+    /// Parse replies with strict RESP framing, see `Parser::new_strict`.
+    ///
+    /// Replies that break the protocol, e.g. because the address belongs
+    /// to some other service, then fail with a `ParseError` right away.
+    /// Can be enabled in a redis URL with `?strict_parsing=true`.
+    pub strict_parsing: bool,
+    /// Optionally a hook that runs on every new connection once it is set
+    /// up, including reconnects, see `Client::on_connect`.
+    ///
+    /// The hook is not serialized, so it is left behind when the info is
+    /// sent to another process.
+    #[serde(skip)]
+    pub on_connect: Option<OnConnect>,
+}
+
+/// A hook that runs on every new connection, see `Client::on_connect`.
+///
+/// A function pointer only means something inside the process that took
+/// it, so the hook never travels with a client, connection info or
+/// connection that is sent to another process.  Attach it again there, e.g.
+/// with `Client::on_connect` or `RedisPool::on_connect`.
+#[derive(Clone, Copy, Debug)]
+pub struct OnConnect(pub fn(&mut Connection) -> RedisResult<()>);
+
+/// The version of the redis serialization protocol a connection uses.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProtocolVersion {
+    /// RESP2, the protocol every server speaks without a handshake.
+    #[default]
+    RESP2,
+    /// RESP3, which adds maps, sets, doubles, booleans and push messages.
+    RESP3,
+}
+
+fn url_to_protocol(url: &url::Url) -> RedisResult<ProtocolVersion> {
+    match url.query_pairs().find(|(k, _)| k == "protocol") {
+        None => Ok(ProtocolVersion::RESP2),
+        Some((_, v)) => match &*v.to_ascii_lowercase() {
+            "resp2" | "2" => Ok(ProtocolVersion::RESP2),
+            "resp3" | "3" => Ok(ProtocolVersion::RESP3),
+            _ => fail!((ErrorKind::InvalidClientConfig, "Invalid protocol version")),
+        },
+    }
 }
 
 impl FromStr for ConnectionInfo {
@@ -217,16 +280,55 @@ fn url_to_tcp_connection_info(url: url::Url) -> RedisResult<ConnectionInfo> {
                 },
                 None => None,
             },
+            always_select: url
+                .query_pairs()
+                .any(|(k, v)| k == "always_select" && (v == "true" || v == "1")),
+            protocol: url_to_protocol(&url)?,
+            client_name: url
+                .query_pairs()
+                .find(|(k, _)| k == "client_name")
+                .map(|(_, v)| v.into_owned()),
+            strict_parsing: url
+                .query_pairs()
+                .any(|(k, v)| k == "strict_parsing" && (v == "true" || v == "1")),
+            on_connect: None,
         },
     })
 }
 
-#[cfg(not(unix))]
-fn url_to_unix_connection_info(_: url::Url) -> RedisResult<ConnectionInfo> {
-    fail!((
-        ErrorKind::InvalidClientConfig,
-        "Unix sockets are not available on this platform."
-    ));
+fn url_to_unix_connection_info(url: url::Url) -> RedisResult<ConnectionInfo> {
+    let path = match percent_encoding::percent_decode(url.path().as_bytes()).decode_utf8() {
+        Ok(path) if !path.is_empty() => PathBuf::from(path.into_owned()),
+        Ok(_) => fail!((ErrorKind::InvalidClientConfig, "Missing path")),
+        Err(_) => fail!((
+            ErrorKind::InvalidClientConfig,
+            "Path is not valid UTF-8 string"
+        )),
+    };
+    let mut redis = RedisConnectionInfo {
+        protocol: url_to_protocol(&url)?,
+        ..Default::default()
+    };
+    for (key, value) in url.query_pairs() {
+        match &*key {
+            "db" => {
+                redis.db = unwrap_or!(
+                    value.parse::<i64>().ok(),
+                    fail!((ErrorKind::InvalidClientConfig, "Invalid database number"))
+                )
+            }
+            "user" => redis.username = Some(value.into_owned()),
+            "pass" => redis.password = Some(value.into_owned()),
+            "always_select" => redis.always_select = value == "true" || value == "1",
+            "client_name" => redis.client_name = Some(value.into_owned()),
+            "strict_parsing" => redis.strict_parsing = value == "true" || value == "1",
+            _ => {}
+        }
+    }
+    Ok(ConnectionInfo {
+        addr: ConnectionAddr::Unix(path),
+        redis,
+    })
 }
 
 impl IntoConnectionInfo for url::Url {
@@ -264,7 +366,6 @@ pub(crate) enum ActualConnection {
 #[derive(Serialize, Deserialize)]
 pub struct Connection {
     pub(crate) con: ActualConnection,
-    #[serde(skip_serializing, skip_deserializing)]
     parser: Parser,
     db: i64,
 
@@ -273,6 +374,27 @@ pub struct Connection {
     /// This flag is checked when attempting to send a command, and if it's raised, we attempt to
     /// exit the pubsub state before executing the new request.
     pubsub: bool,
+
+    /// The information the connection was established with, used to reconnect.
+    connection_info: Option<ConnectionInfo>,
+
+    /// The read timeout set with `set_read_timeout`, restored after commands
+    /// that temporarily change it.
+    read_timeout: Option<Duration>,
+
+    /// The write timeout set with `set_write_timeout`, applied again after
+    /// reconnecting.
+    write_timeout: Option<Duration>,
+
+    /// The timeout the connection was opened with, used to reconnect.
+    connect_timeout: Option<Duration>,
+
+    /// Whether commands are checked with `Cmd::check_arity` before sending.
+    check_arity: bool,
+
+    /// RESP3 push frames that arrived while a reply was awaited, handed out
+    /// by `recv_response` before anything else is read.
+    pushes: VecDeque<Value>,
 }
 
 /// Represents a stateful redis TCP connection that can be moved to separate processes.
@@ -286,15 +408,45 @@ pub struct StrippedConnection {
     /// This flag is checked when attempting to send a command, and if it's raised, we attempt to
     /// exit the pubsub state before executing the new request.
     pubsub: bool,
+
+    /// The information the connection was established with, used to reconnect.
+    connection_info: Option<ConnectionInfo>,
+
+    /// The read timeout set with `set_read_timeout`.
+    read_timeout: Option<Duration>,
+
+    /// The write timeout set with `set_write_timeout`.
+    write_timeout: Option<Duration>,
+
+    /// The timeout the connection was opened with.
+    connect_timeout: Option<Duration>,
+
+    /// Whether commands are checked with `Cmd::check_arity` before sending.
+    check_arity: bool,
 }
 
 impl StrippedConnection {
+    /// Turns the stripped connection back into a usable `Connection`.
+    ///
+    /// The socket may have died while the connection was handed over, e.g.
+    /// because the process that opened it exited.  Call
+    /// `Connection::reconnect_if_dead` on the result to make sure it is
+    /// usable before sending commands.
     pub fn with_parser(&self) -> Connection {
         Connection {
             con: self.con.clone(),
-            parser: Parser::new(),
+            parser: self
+                .connection_info
+                .as_ref()
+                .map_or_else(Parser::new, |info| new_parser(&info.redis)),
             db: self.db,
             pubsub: self.pubsub,
+            connection_info: self.connection_info.clone(),
+            read_timeout: self.read_timeout,
+            write_timeout: self.write_timeout,
+            connect_timeout: self.connect_timeout,
+            check_arity: self.check_arity,
+            pushes: VecDeque::new(),
         }
     }
 }
@@ -305,6 +457,18 @@ pub struct Msg {
     payload: Value,
     channel: Value,
     pattern: Option<Value>,
+    kind: MsgKind,
+}
+
+/// The kind of subscription a pubsub message was delivered through.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum MsgKind {
+    /// A `message` for a channel subscribed with `SUBSCRIBE`.
+    Channel,
+    /// A `pmessage` for a pattern subscribed with `PSUBSCRIBE`.
+    Pattern,
+    /// An `smessage` for a shard channel subscribed with `SSUBSCRIBE`.
+    Shard,
 }
 
 impl ActualConnection {
@@ -349,14 +513,13 @@ impl ActualConnection {
             }
             ConnectionAddr::TcpTls { ref host, port, .. } => {
                 let tls = match timeout {
-                    None => match TlsStream::connect(host, port.into()) {
-                        Ok(res) => res,
-                        Err(e) => {
-                            fail!((ErrorKind::IoError, "SSL Handshake error", e.to_string()));
-                        }
-                    },
-                    Some(timeout) => {
-                        TlsStream::connect_timeout(host, timeout, port.into(), vec![]).unwrap()
+                    None => TlsStream::connect(host, port.into()),
+                    Some(timeout) => TlsStream::connect_timeout(host, timeout, port.into(), vec![]),
+                };
+                let tls = match tls {
+                    Ok(res) => res,
+                    Err(e) => {
+                        fail!((ErrorKind::IoError, "SSL Handshake error", e.to_string()));
                     }
                 };
                 ActualConnection::TcpTls(TcpTlsConnection {
@@ -364,12 +527,11 @@ impl ActualConnection {
                     open: true,
                 })
             }
-            #[cfg(not(unix))]
-            ConnectionAddr::Unix(ref _path) => {
+            ConnectionAddr::Unix(ref path) => {
                 fail!((
                     ErrorKind::InvalidClientConfig,
-                    "Cannot connect to unix sockets \
-                     on this platform"
+                    "Cannot connect to unix sockets, the lunatic runtime does not support them",
+                    path.display().to_string()
                 ));
             }
         })
@@ -428,12 +590,30 @@ impl ActualConnection {
         Ok(())
     }
 
+    /// Returns the read timeout currently set on the socket.
+    #[cfg(test)]
+    pub(crate) fn read_timeout(&self) -> Option<Duration> {
+        match self {
+            ActualConnection::Tcp(conn) => conn.reader.read_timeout(),
+            ActualConnection::TcpTls(TcpTlsConnection { ref reader, .. }) => reader.read_timeout(),
+        }
+    }
+
     pub fn is_open(&self) -> bool {
         match *self {
             ActualConnection::Tcp(TcpConnection { open, .. }) => open,
             ActualConnection::TcpTls(TcpTlsConnection { open, .. }) => open,
         }
     }
+
+    /// Marks the connection as closed, e.g. because it is out of sync with
+    /// the server.
+    pub fn set_closed(&mut self) {
+        match self {
+            ActualConnection::Tcp(conn) => conn.open = false,
+            ActualConnection::TcpTls(conn) => conn.open = false,
+        }
+    }
 }
 
 fn connect_auth(con: &mut Connection, connection_info: &RedisConnectionInfo) -> RedisResult<()> {
@@ -479,7 +659,35 @@ pub fn connect(
     timeout: Option<Duration>,
 ) -> RedisResult<Connection> {
     let con = ActualConnection::new(&connection_info.addr, timeout)?;
-    setup_connection(con, &connection_info.redis)
+    let mut rv = setup_connection(con, &connection_info.redis)?;
+    rv.connection_info = Some(connection_info.clone());
+    rv.connect_timeout = timeout;
+    if let Some(OnConnect(hook)) = connection_info.redis.on_connect {
+        hook(&mut rv)?;
+    }
+    Ok(rv)
+}
+
+/// Returns true for RESP3 push frames that are not the reply to a request.
+///
+/// The confirmations of (un)subscribe commands are push frames as well, but
+/// they answer the command that was sent.
+fn is_out_of_band(value: &Value) -> bool {
+    match value {
+        Value::Push(items) => !matches!(
+            items.first(),
+            Some(Value::Data(kind)) if kind.ends_with(b"subscribe")
+        ),
+        _ => false,
+    }
+}
+
+fn new_parser(connection_info: &RedisConnectionInfo) -> Parser {
+    if connection_info.strict_parsing {
+        Parser::new_strict()
+    } else {
+        Parser::new()
+    }
 }
 
 fn setup_connection(
@@ -488,16 +696,32 @@ fn setup_connection(
 ) -> RedisResult<Connection> {
     let mut rv = Connection {
         con,
-        parser: Parser::new(),
+        parser: new_parser(connection_info),
         db: connection_info.db,
         pubsub: false,
+        connection_info: None,
+        read_timeout: None,
+        write_timeout: None,
+        connect_timeout: None,
+        check_arity: false,
+        pushes: VecDeque::new(),
     };
 
     if connection_info.password.is_some() {
         connect_auth(&mut rv, connection_info)?;
     }
 
-    if connection_info.db != 0 {
+    if let Some(ref name) = connection_info.client_name {
+        if let Err(err) = cmd("CLIENT").arg("SETNAME").arg(name).query::<()>(&mut rv) {
+            fail!((
+                ErrorKind::ResponseError,
+                "Redis server refused to set the client name",
+                err.to_string()
+            ));
+        }
+    }
+
+    if connection_info.db != 0 || connection_info.always_select {
         match cmd("SELECT")
             .arg(connection_info.db)
             .query::<Value>(&mut rv)
@@ -510,6 +734,16 @@ fn setup_connection(
         }
     }
 
+    if connection_info.protocol == ProtocolVersion::RESP3 {
+        if let Err(err) = cmd("HELLO").arg(3).query::<Value>(&mut rv) {
+            fail!((
+                ErrorKind::ResponseError,
+                "Redis server refused to switch to RESP3",
+                err.to_string()
+            ));
+        }
+    }
+
     Ok(rv)
 }
 
@@ -544,6 +778,13 @@ pub trait ConnectionLike {
         self.req_packed_command(&pcmd)
     }
 
+    /// Like `req_command` but the caller is going to discard the reply, so
+    /// status replies do not need to be materialized.  Only errors matter.
+    #[doc(hidden)]
+    fn req_command_discarding_status(&mut self, cmd: &Cmd) -> RedisResult<Value> {
+        self.req_command(cmd)
+    }
+
     /// Returns the database this connection is bound to.  Note that this
     /// information might be unreliable because it's initially cached and
     /// also might be incorrect if the connection like object is not
@@ -559,6 +800,21 @@ pub trait ConnectionLike {
     /// Check that all connections it has are available (`PING` internally).
     fn check_connection(&mut self) -> bool;
 
+    /// Like `check_connection`, but gives up once a connection did not reply
+    /// within `dur`.
+    ///
+    /// A half-open socket never replies, so this is what a supervisor should
+    /// use to decide whether to recycle a connection.  The read timeout is
+    /// restored afterwards.  A connection that timed out is marked as closed
+    /// since the late `PONG` would otherwise be taken as the reply to the
+    /// next command.
+    ///
+    /// The default implementation ignores `dur`.
+    fn check_connection_timeout(&mut self, dur: Duration) -> bool {
+        let _ = dur;
+        self.check_connection()
+    }
+
     /// Returns the connection status.
     ///
     /// The connection is open until any `read_response` call recieved an
@@ -575,7 +831,17 @@ impl Clone for Connection {
             con: self.con.clone(),
             pubsub: self.pubsub,
             db: self.db,
-            parser: Parser::new(),
+            parser: if self.parser.is_strict() {
+                Parser::new_strict()
+            } else {
+                Parser::new()
+            },
+            connection_info: self.connection_info.clone(),
+            read_timeout: self.read_timeout,
+            write_timeout: self.write_timeout,
+            connect_timeout: self.connect_timeout,
+            check_arity: self.check_arity,
+            pushes: VecDeque::new(),
         }
     }
 }
@@ -594,9 +860,62 @@ impl Connection {
             con: self.con.clone(),
             db: self.db,
             pubsub: self.pubsub,
+            connection_info: self.connection_info.clone(),
+            read_timeout: self.read_timeout,
+            write_timeout: self.write_timeout,
+            connect_timeout: self.connect_timeout,
+            check_arity: self.check_arity,
         }
     }
 
+    /// Checks that the connection is alive with a `PING` and, if it is not,
+    /// re-establishes it from the information it was originally opened with.
+    ///
+    /// Returns `true` if a new connection had to be made.  This makes handing
+    /// connections over to other processes robust, since the socket may not
+    /// survive the transfer.  Subscriptions and other per-connection state
+    /// are not restored.
+    pub fn reconnect_if_dead(&mut self) -> RedisResult<bool> {
+        if self.check_connection() {
+            return Ok(false);
+        }
+        self.reconnect()?;
+        Ok(true)
+    }
+
+    /// Sets the hook that runs when the connection reconnects, which is
+    /// lost when the connection is sent to another process.
+    pub(crate) fn set_on_connect(&mut self, hook: Option<OnConnect>) {
+        if let Some(ref mut info) = self.connection_info {
+            info.redis.on_connect = hook;
+        }
+    }
+
+    /// Replaces the connection with a new one, made from the information and
+    /// the connect timeout it was originally opened with.  The read and write
+    /// timeouts are carried over.
+    pub(crate) fn reconnect(&mut self) -> RedisResult<()> {
+        let connection_info = match self.connection_info {
+            Some(ref info) => info.clone(),
+            None => fail!((
+                ErrorKind::InvalidClientConfig,
+                "Connection is dead and has no information to reconnect with"
+            )),
+        };
+        let read_timeout = self.read_timeout;
+        let write_timeout = self.write_timeout;
+        let check_arity = self.check_arity;
+        *self = connect(&connection_info, self.connect_timeout)?;
+        if read_timeout.is_some() {
+            self.set_read_timeout(read_timeout)?;
+        }
+        if write_timeout.is_some() {
+            self.set_write_timeout(write_timeout)?;
+        }
+        self.check_arity = check_arity;
+        Ok(())
+    }
+
     /// Sends an already encoded (packed) command into the TCP socket and
     /// does not read a response.  This is useful for commands like
     /// `MONITOR` which yield multiple items.  This needs to be used with
@@ -608,17 +927,96 @@ impl Connection {
 
     /// Fetches a single response from the connection.  This is useful
     /// if used in combination with `send_packed_command`.
+    ///
+    /// RESP3 push frames, e.g. pubsub messages or client side caching
+    /// invalidations, that arrived while an earlier command waited for its
+    /// reply are returned first.
     pub fn recv_response<T: Read>(&mut self) -> RedisResult<Value> {
+        if let Some(push) = self.pushes.pop_front() {
+            return Ok(push);
+        }
         self.read_response(None as Option<&mut T>)
     }
 
+    /// Sends multiple already encoded (packed) commands into the TCP socket
+    /// and hands every response to `f` as soon as it was read.
+    ///
+    /// Unlike `req_packed_commands` the responses are not buffered, which
+    /// bounds memory usage for very large pipelines.  The first `offset`
+    /// responses are read but not passed on.  All `offset + count` responses
+    /// are always drained so the connection stays usable, even if one of them
+    /// is an error; the first error is returned after draining.
+    pub fn req_packed_commands_each<F>(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+        mut f: F,
+    ) -> RedisResult<()>
+    where
+        F: FnMut(usize, Value) -> RedisResult<()>,
+    {
+        self.con.send_bytes(cmd)?;
+        let mut first_err = None;
+        for idx in 0..(offset + count) {
+            let response = self.read_reply(|parser, reader| parser.parse(reader, false));
+            let result = match response {
+                Ok(item) if idx >= offset && first_err.is_none() => f(idx - offset, item),
+                Ok(_) => Ok(()),
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                if first_err.is_none() {
+                    first_err = Some(err);
+                }
+            }
+        }
+
+        first_err.map_or(Ok(()), Err)
+    }
+
+    /// Fetches a single response from the connection, reading a bulk
+    /// string reply into `buf`.
+    ///
+    /// `Value::Data` owns its bytes, so the vector of `buf` is moved into
+    /// the `Value::Data` that is returned for a bulk string reply, and `buf`
+    /// is left empty.  Put the vector back into `buf` once done with it, and
+    /// a loop calling this does not allocate for replies that fit into it.
+    /// Other replies leave `buf` alone.
+    ///
+    /// ```rust,no_run
+    /// # fn do_something(con: &mut redis::Connection) -> redis::RedisResult<()> {
+    /// let mut buf = Vec::with_capacity(1024);
+    /// loop {
+    ///     con.send_packed_command(&redis::cmd("LPOP").arg("jobs").get_packed_command())?;
+    ///     match con.recv_response_into(&mut buf)? {
+    ///         redis::Value::Data(data) => {
+    ///             println!("{}", String::from_utf8_lossy(&data));
+    ///             buf = data;
+    ///         }
+    ///         _ => break,
+    ///     }
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn recv_response_into(&mut self, buf: &mut Vec<u8>) -> RedisResult<Value> {
+        if let Some(push) = self.pushes.pop_front() {
+            return Ok(push);
+        }
+        self.read_with(None as Option<&mut TcpStream>, |parser, reader| {
+            parser.parse_value_into(reader, buf)
+        })
+    }
+
     /// Sets the write timeout for the connection.
     ///
     /// If the provided value is `None`, then `send_packed_command` call will
     /// block indefinitely. It is an error to pass the zero `Duration` to this
     /// method.
     pub fn set_write_timeout(&mut self, dur: Option<Duration>) -> RedisResult<()> {
-        self.con.set_write_timeout(dur)
+        self.con.set_write_timeout(dur)?;
+        self.write_timeout = dur;
+        Ok(())
     }
 
     /// Sets the read timeout for the connection.
@@ -627,7 +1025,110 @@ impl Connection {
     /// block indefinitely. It is an error to pass the zero `Duration` to this
     /// method.
     pub fn set_read_timeout(&mut self, dur: Option<Duration>) -> RedisResult<()> {
-        self.con.set_read_timeout(dur)
+        self.con.set_read_timeout(dur)?;
+        self.read_timeout = dur;
+        Ok(())
+    }
+
+    /// Enables checking the number of arguments of well-known commands
+    /// before they are sent, see [`Cmd::check_arity`].
+    ///
+    /// This catches mistakes like a `SET` without a value on the client,
+    /// without a round trip to the server.  Pipelines are not checked.
+    pub fn set_check_arity(&mut self, enabled: bool) {
+        self.check_arity = enabled;
+    }
+
+    /// Switches the connection to the database `db` with `SELECT`.
+    ///
+    /// Afterwards `get_db()` returns `db`, and a reconnect, e.g. by
+    /// `reconnect_if_dead`, selects it again.  If the server refuses, for
+    /// instance because `db` is out of range, the error is returned and the
+    /// connection stays on its current database.
+    ///
+    /// ```rust,no_run
+    /// # use redis::Commands;
+    /// # fn do_something(con: &mut redis::Connection) -> redis::RedisResult<()> {
+    /// con.change_db(1)?;
+    /// let _: () = con.set("my_key", 42)?;
+    /// # Ok(()) }
+    /// ```
+    pub fn change_db(&mut self, db: i64) -> RedisResult<()> {
+        match cmd("SELECT").arg(db).query::<Value>(self)? {
+            Value::Okay => {}
+            _ => fail!((
+                ErrorKind::ResponseError,
+                "Redis server refused to switch database"
+            )),
+        }
+        self.db = db;
+        if let Some(connection_info) = self.connection_info.as_mut() {
+            connection_info.redis.db = db;
+        }
+        Ok(())
+    }
+
+    /// Names the connection with `CLIENT SETNAME`.
+    ///
+    /// The name is kept when the connection is re-established, e.g. by
+    /// `reconnect_if_dead`.  Names cannot contain spaces, newlines or other
+    /// special characters; the server rejects those.
+    pub fn set_client_name(&mut self, name: &str) -> RedisResult<()> {
+        cmd("CLIENT").arg("SETNAME").arg(name).query::<()>(self)?;
+        if let Some(connection_info) = self.connection_info.as_mut() {
+            connection_info.redis.client_name = Some(name.to_string());
+        }
+        Ok(())
+    }
+
+    /// Returns the name of the connection as set by `CLIENT SETNAME`, or
+    /// `None` if it has no name.
+    pub fn get_client_name(&mut self) -> RedisResult<Option<String>> {
+        cmd("CLIENT").arg("GETNAME").query(self)
+    }
+
+    /// Returns the read timeout set with `set_read_timeout`.
+    pub(crate) fn read_timeout(&self) -> Option<Duration> {
+        self.read_timeout
+    }
+
+    /// Pops the first element of the list at `key`, blocking until one is
+    /// available or `deadline` has passed.
+    ///
+    /// The remaining time is passed to `BLPOP` as its timeout, and the socket
+    /// read timeout is set slightly past the deadline so the server's reply
+    /// always arrives first.  The read timeout set with `set_read_timeout`
+    /// is restored afterwards.  Returns `Ok(None)` if nothing was popped
+    /// before the deadline, otherwise the `(key, element)` reply.
+    ///
+    /// ```rust,no_run
+    /// # use std::time::{Duration, Instant};
+    /// # fn do_something(con: &mut redis::Connection) -> redis::RedisResult<()> {
+    /// let deadline = Instant::now() + Duration::from_secs(2);
+    /// let popped: Option<(String, u32)> = con.blpop_deadline("my_queue", deadline)?;
+    /// # Ok(()) }
+    /// ```
+    pub fn blpop_deadline<K: ToRedisArgs, RV: FromRedisValue>(
+        &mut self,
+        key: K,
+        deadline: Instant,
+    ) -> RedisResult<Option<RV>> {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        // BLPOP blocks forever with a timeout of 0 and only has millisecond
+        // precision, so there is nothing left to wait for.
+        if remaining < Duration::from_millis(1) {
+            return Ok(None);
+        }
+
+        let previous = self.read_timeout;
+        self.con
+            .set_read_timeout(Some(remaining + Duration::from_secs(1)))?;
+        let result = cmd("BLPOP")
+            .arg(key)
+            .arg(remaining.as_secs_f64())
+            .query(self);
+        self.con.set_read_timeout(previous)?;
+        result
     }
 
     /// Creates a [`RedisPubSub`] instance for this connection.
@@ -641,19 +1142,43 @@ impl Connection {
     }
     /// Fetches a single response from the connection.
     fn read_response<T: Read>(&mut self, reader: Option<&mut T>) -> RedisResult<Value> {
+        self.read_with(reader, |parser, reader| parser.parse(reader, false))
+    }
+
+    /// Reads the reply to a request with `parse`.  RESP3 push frames that
+    /// arrive before it are not replies and are queued for `recv_response`.
+    fn read_reply<F>(&mut self, mut parse: F) -> RedisResult<Value>
+    where
+        F: FnMut(&mut Parser, &mut dyn Read) -> RedisResult<Value>,
+    {
+        loop {
+            let value = self.read_with(None as Option<&mut TcpStream>, &mut parse)?;
+            if !is_out_of_band(&value) {
+                return Ok(value);
+            }
+            self.pushes.push_back(value);
+        }
+    }
+
+    /// Reads a reply with `parse`, from `reader` or else the socket.
+    fn read_with<T, R, F>(&mut self, reader: Option<&mut T>, parse: F) -> RedisResult<R>
+    where
+        T: Read,
+        F: FnOnce(&mut Parser, &mut dyn Read) -> RedisResult<R>,
+    {
         let result = match (reader, &mut self.con) {
-            (Some(reader), _) => self.parser.parse_value(reader),
+            (Some(reader), _) => parse(&mut self.parser, reader),
             (None, ActualConnection::Tcp(TcpConnection { reader, .. })) => {
-                self.parser.parse_value(reader)
+                parse(&mut self.parser, reader)
             }
             (None, ActualConnection::TcpTls(TcpTlsConnection { ref mut reader, .. })) => {
-                self.parser.parse_value(reader)
+                parse(&mut self.parser, reader)
             }
         };
         // shutdown connection on protocol error
         if let Err(e) = &result {
             let shutdown = match e.as_io_error() {
-                Some(e) => e.kind() == io::ErrorKind::UnexpectedEof,
+                Some((kind, _)) => kind == io::ErrorKind::UnexpectedEof,
                 None => false,
             };
             if shutdown {
@@ -674,13 +1199,28 @@ impl Connection {
 }
 
 impl ConnectionLike for Connection {
+    fn req_command(&mut self, cmd: &Cmd) -> RedisResult<Value> {
+        if self.check_arity {
+            cmd.check_arity()?;
+        }
+        self.req_packed_command(&cmd.get_packed_command())
+    }
+
     fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
         // if self.pubsub {
         //     self.exit_pubsub()?;
         // }
 
         self.con.send_bytes(cmd)?;
-        self.read_response::<TcpStream>(None)
+        self.read_reply(|parser, reader| parser.parse(reader, false))
+    }
+
+    fn req_command_discarding_status(&mut self, cmd: &Cmd) -> RedisResult<Value> {
+        if self.check_arity {
+            cmd.check_arity()?;
+        }
+        self.con.send_bytes(&cmd.get_packed_command())?;
+        self.read_reply(|parser, reader| parser.parse_value_discarding_status(reader))
     }
 
     fn req_packed_commands(
@@ -699,7 +1239,7 @@ impl ConnectionLike for Connection {
             // When processing a transaction, some responses may be errors.
             // We need to keep processing the rest of the responses in that case,
             // so bailing early with `?` would not be correct.
-            let response = self.read_response(None as Option<&mut TcpStream>);
+            let response = self.read_reply(|parser, reader| parser.parse(reader, false));
             match response {
                 Ok(item) => {
                     if idx >= offset {
@@ -728,6 +1268,20 @@ impl ConnectionLike for Connection {
     fn check_connection(&mut self) -> bool {
         cmd("PING").query::<String>(self).is_ok()
     }
+
+    fn check_connection_timeout(&mut self, dur: Duration) -> bool {
+        // A zero read timeout is rejected by the socket.
+        if dur.is_zero() || self.con.set_read_timeout(Some(dur)).is_err() {
+            return false;
+        }
+        let result = cmd("PING").query::<String>(self);
+        if let Err(ref err) = result {
+            if err.is_timeout() {
+                self.con.set_closed();
+            }
+        }
+        self.con.set_read_timeout(self.read_timeout).is_ok() && result.is_ok()
+    }
 }
 
 impl<C, T> ConnectionLike for T
@@ -752,6 +1306,10 @@ where
         self.deref_mut().req_command(cmd)
     }
 
+    fn req_command_discarding_status(&mut self, cmd: &Cmd) -> RedisResult<Value> {
+        self.deref_mut().req_command_discarding_status(cmd)
+    }
+
     fn get_db(&self) -> i64 {
         self.deref().get_db()
     }
@@ -764,12 +1322,16 @@ where
         self.deref_mut().check_connection()
     }
 
+    fn check_connection_timeout(&mut self, dur: Duration) -> bool {
+        self.deref_mut().check_connection_timeout(dur)
+    }
+
     fn is_open(&self) -> bool {
         self.deref().is_open()
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub(crate) enum Confirmation {
     Pattern(String),
     Punsub(String),
@@ -809,14 +1371,21 @@ impl Msg {
         let mut pattern = None;
         let payload;
         let channel;
+        let kind;
 
-        if msg_type == "message" {
+        if msg_type == "message" || msg_type == "smessage" {
             channel = iter.next()?;
             payload = iter.next()?;
+            kind = if msg_type == "message" {
+                MsgKind::Channel
+            } else {
+                MsgKind::Shard
+            };
         } else if msg_type == "pmessage" {
             pattern = Some(iter.next()?);
             channel = iter.next()?;
             payload = iter.next()?;
+            kind = MsgKind::Pattern;
         } else {
             return None;
         }
@@ -825,6 +1394,7 @@ impl Msg {
             payload,
             channel,
             pattern,
+            kind,
         })
     }
 
@@ -859,6 +1429,39 @@ impl Msg {
         }
     }
 
+    /// Returns true if the message's payload is valid UTF-8 text.
+    ///
+    /// Together with `payload_looks_numeric` this can be used to decide how
+    /// to decode payloads on channels that carry different kinds of data.
+    pub fn payload_is_utf8(&self) -> bool {
+        match self.payload {
+            Value::Data(ref bytes) => from_utf8(bytes).is_ok(),
+            Value::Int(_) | Value::Status(_) | Value::Okay => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true if the message's payload is an integer or a finite
+    /// floating point number, i.e. it can be decoded as `i64` or `f64`.
+    pub fn payload_looks_numeric(&self) -> bool {
+        match self.payload {
+            Value::Int(_) => true,
+            Value::Data(ref bytes) => match from_utf8(bytes) {
+                Ok(s) => s.parse::<f64>().map(f64::is_finite).unwrap_or(false),
+                Err(_) => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Returns the kind of subscription the message was delivered through.
+    ///
+    /// This gives a fuller picture than `from_pattern` once shard channels
+    /// are involved.
+    pub fn kind(&self) -> MsgKind {
+        self.kind
+    }
+
     /// Returns true if the message was constructed from a pattern
     /// subscription.
     #[allow(clippy::wrong_self_convention)]
@@ -920,8 +1523,79 @@ pub fn transaction<
     keys: &[K],
     func: F,
 ) -> RedisResult<T> {
-    let mut func = func;
+    transaction_loop(con, keys, func, || Ok(()))
+}
+
+/// Like [`transaction`] but gives up once `deadline` has passed.
+///
+/// The WATCH/EXEC loop is retried as long as the transaction is aborted
+/// because a watched key was modified, but no new attempt is started after
+/// the deadline.  In that case the keys are unwatched and an I/O error of
+/// kind `TimedOut` is returned, so `RedisError::is_timeout` is true for it.
+/// This bounds how long a contended transaction can hold up the caller.
+///
+/// Note that an attempt which is already running is not interrupted.
+///
+/// ```rust,no_run
+/// use redis::Commands;
+/// use std::time::{Duration, Instant};
+/// # fn do_something() -> redis::RedisResult<()> {
+/// # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+/// # let mut con = client.get_connection().unwrap();
+/// let key = "the_key";
+/// let deadline = Instant::now() + Duration::from_millis(50);
+/// let (new_val,) : (isize,) = redis::transaction_deadline(&mut con, &[key], deadline, |con, pipe| {
+///     let old_val : isize = con.get(key)?;
+///     pipe
+///         .set(key, old_val + 1).ignore()
+///         .get(key).query(con)
+/// })?;
+/// # Ok(()) }
+/// ```
+pub fn transaction_deadline<
+    C: ConnectionLike,
+    K: ToRedisArgs,
+    T,
+    F: FnMut(&mut C, &mut Pipeline) -> RedisResult<Option<T>>,
+>(
+    con: &mut C,
+    keys: &[K],
+    deadline: Instant,
+    func: F,
+) -> RedisResult<T> {
+    transaction_loop(con, keys, func, || {
+        if Instant::now() >= deadline {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "transaction did not succeed before the deadline",
+            )
+            .into());
+        }
+        Ok(())
+    })
+}
+
+/// The WATCH/EXEC loop behind [`transaction`] and [`transaction_deadline`].
+///
+/// `may_start` is called before every attempt.  If it fails the keys are
+/// unwatched and its error is returned instead of starting the attempt.
+fn transaction_loop<C, K, T, F, G>(
+    con: &mut C,
+    keys: &[K],
+    mut func: F,
+    mut may_start: G,
+) -> RedisResult<T>
+where
+    C: ConnectionLike,
+    K: ToRedisArgs,
+    F: FnMut(&mut C, &mut Pipeline) -> RedisResult<Option<T>>,
+    G: FnMut() -> RedisResult<()>,
+{
     loop {
+        if let Err(err) = may_start() {
+            cmd("UNWATCH").query::<()>(con)?;
+            return Err(err);
+        }
         cmd("WATCH").arg(keys).query::<()>(con)?;
         let mut p = pipe();
         let response: Option<T> = func(con, p.atomic())?;
@@ -942,6 +1616,69 @@ pub fn transaction<
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fake_server::{expect_sent, fake_server, FakeServer};
+
+    fn message(payload: &[u8]) -> Msg {
+        Msg::from_value(&Value::Bulk(vec![
+            Value::Data(b"message".to_vec()),
+            Value::Data(b"channel".to_vec()),
+            Value::Data(payload.to_vec()),
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_msg_payload_kind() {
+        let numeric = message(b"42");
+        assert!(numeric.payload_is_utf8());
+        assert!(numeric.payload_looks_numeric());
+        assert!(message(b"-1.5e3").payload_looks_numeric());
+
+        let text = message(b"banana");
+        assert!(text.payload_is_utf8());
+        assert!(!text.payload_looks_numeric());
+        assert!(!message(b"NaN").payload_looks_numeric());
+        assert!(!message(b"").payload_looks_numeric());
+
+        let binary = message(&[0xff, 0xfe, 0x00, 0x31]);
+        assert!(!binary.payload_is_utf8());
+        assert!(!binary.payload_looks_numeric());
+    }
+
+    #[test]
+    fn test_msg_binary_payload() {
+        let payload = [0x00, 0xff, 0xfe, 0x80, b'\r', b'\n'];
+        let msg = message(&payload);
+        assert_eq!(msg.get_payload::<Vec<u8>>(), Ok(payload.to_vec()));
+        assert_eq!(msg.get_payload_bytes(), &payload[..]);
+        assert!(msg.get_payload::<String>().is_err());
+    }
+
+    #[test]
+    fn test_msg_kind() {
+        assert_eq!(message(b"x").kind(), MsgKind::Channel);
+
+        let pattern = Msg::from_value(&Value::Bulk(vec![
+            Value::Data(b"pmessage".to_vec()),
+            Value::Data(b"chan*".to_vec()),
+            Value::Data(b"channel".to_vec()),
+            Value::Data(b"x".to_vec()),
+        ]))
+        .unwrap();
+        assert_eq!(pattern.kind(), MsgKind::Pattern);
+        assert!(pattern.from_pattern());
+
+        let shard = Msg::from_value(&Value::Bulk(vec![
+            Value::Data(b"smessage".to_vec()),
+            Value::Data(b"channel".to_vec()),
+            Value::Data(b"x".to_vec()),
+        ]))
+        .unwrap();
+        assert_eq!(shard.kind(), MsgKind::Shard);
+        assert!(!shard.from_pattern());
+        assert_eq!(shard.get_channel_name(), "channel");
+        assert_eq!(shard.get_payload_bytes(), b"x");
+    }
 
     #[test]
     fn test_parse_redis_url() {
@@ -979,6 +1716,47 @@ mod tests {
                         db: 2,
                         username: Some("%johndoe%".to_string()),
                         password: Some("#@<>$".to_string()),
+                        ..Default::default()
+                    },
+                },
+            ),
+            (
+                url::Url::parse("redis://127.0.0.1/?protocol=resp3").unwrap(),
+                ConnectionInfo {
+                    addr: ConnectionAddr::Tcp("127.0.0.1".to_string(), 6379),
+                    redis: RedisConnectionInfo {
+                        protocol: ProtocolVersion::RESP3,
+                        ..Default::default()
+                    },
+                },
+            ),
+            (
+                url::Url::parse("redis://127.0.0.1/?always_select=true").unwrap(),
+                ConnectionInfo {
+                    addr: ConnectionAddr::Tcp("127.0.0.1".to_string(), 6379),
+                    redis: RedisConnectionInfo {
+                        always_select: true,
+                        ..Default::default()
+                    },
+                },
+            ),
+            (
+                url::Url::parse("redis://127.0.0.1/?client_name=worker-1").unwrap(),
+                ConnectionInfo {
+                    addr: ConnectionAddr::Tcp("127.0.0.1".to_string(), 6379),
+                    redis: RedisConnectionInfo {
+                        client_name: Some("worker-1".to_string()),
+                        ..Default::default()
+                    },
+                },
+            ),
+            (
+                url::Url::parse("redis://127.0.0.1/?strict_parsing=true").unwrap(),
+                ConnectionInfo {
+                    addr: ConnectionAddr::Tcp("127.0.0.1".to_string(), 6379),
+                    redis: RedisConnectionInfo {
+                        strict_parsing: true,
+                        ..Default::default()
                     },
                 },
             ),
@@ -1001,6 +1779,81 @@ mod tests {
                 "password of {} is not expected",
                 url
             );
+            assert_eq!(
+                res.redis.always_select, expected.redis.always_select,
+                "always_select of {} is not expected",
+                url
+            );
+            assert_eq!(
+                res.redis.protocol, expected.redis.protocol,
+                "protocol of {} is not expected",
+                url
+            );
+            assert_eq!(
+                res.redis.client_name, expected.redis.client_name,
+                "client_name of {} is not expected",
+                url
+            );
+            assert_eq!(
+                res.redis.strict_parsing, expected.redis.strict_parsing,
+                "strict_parsing of {} is not expected",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn test_url_to_unix_connection_info() {
+        for url in ["unix:///tmp/redis.sock", "redis+unix:///tmp/redis.sock"] {
+            let res = url::Url::parse(url)
+                .unwrap()
+                .into_connection_info()
+                .unwrap();
+            assert_eq!(
+                res.addr,
+                ConnectionAddr::Unix(PathBuf::from("/tmp/redis.sock"))
+            );
+            assert_eq!(res.redis.db, 0);
+            assert!(!res.addr.is_supported());
+        }
+
+        let res = url::Url::parse(
+            "redis+unix:///tmp/redis%20dir/redis.sock?db=3&user=me&pass=pw&client_name=w",
+        )
+        .unwrap()
+        .into_connection_info()
+        .unwrap();
+        assert_eq!(
+            res.addr,
+            ConnectionAddr::Unix(PathBuf::from("/tmp/redis dir/redis.sock"))
+        );
+        assert_eq!(res.redis.db, 3);
+        assert_eq!(res.redis.username.as_deref(), Some("me"));
+        assert_eq!(res.redis.password.as_deref(), Some("pw"));
+        assert_eq!(res.redis.client_name.as_deref(), Some("w"));
+
+        let err = url_to_unix_connection_info(url::Url::parse("unix:///tmp/r.sock?db=x").unwrap())
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidClientConfig);
+
+        let err = ActualConnection::new(&res.addr, None).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidClientConfig);
+    }
+
+    #[test]
+    fn test_failed_tls_connect_is_an_error() {
+        // Nothing listens on the port anymore.
+        let port = FakeServer::bind().port();
+
+        let addr = ConnectionAddr::TcpTls {
+            host: "127.0.0.1".to_string(),
+            port,
+            insecure: false,
+        };
+        // Both with and without a timeout the failure is reported, not a panic
+        for timeout in [None, Some(Duration::from_secs(1))] {
+            let err = ActualConnection::new(&addr, timeout).err().unwrap();
+            assert_eq!(err.kind(), ErrorKind::IoError);
         }
     }
 
@@ -1037,4 +1890,248 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_check_connection_timeout_on_silent_server() {
+        // The server accepts the connection but never replies.
+        let (mut con, _server) = fake_server(b"");
+
+        let read_timeout = Some(Duration::from_secs(5));
+        con.set_read_timeout(read_timeout).unwrap();
+        let started = Instant::now();
+        assert!(!con.check_connection_timeout(Duration::from_millis(100)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        // The socket is back at the timeout set before the check.
+        assert_eq!(con.con.read_timeout(), read_timeout);
+        // The PONG may still arrive, so the connection must not be reused.
+        assert!(!con.is_open());
+
+        assert!(!con.check_connection_timeout(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_reconnect_keeps_timeouts() {
+        let server = FakeServer::bind();
+        let connect_timeout = Some(Duration::from_secs(3));
+        let write_timeout = Some(Duration::from_secs(4));
+        let mut con = connect(&server.info(), connect_timeout).unwrap();
+        let _ = server.accept(b"");
+        con.set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        con.set_write_timeout(write_timeout).unwrap();
+
+        con.reconnect().unwrap();
+        // The new socket never gets a reply either.
+        let _stream = server.accept(b"");
+        assert_eq!(con.connect_timeout, connect_timeout);
+        assert_eq!(con.write_timeout, write_timeout);
+        let started = Instant::now();
+        let err = cmd("PING").query::<String>(&mut con).unwrap_err();
+        assert!(err.is_timeout());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_change_db() {
+        let (mut con, mut server) = fake_server(b"+OK\r\n-ERR DB index is out of range\r\n");
+        assert_eq!(con.get_db(), 0);
+
+        con.change_db(1).unwrap();
+        assert_eq!(con.get_db(), 1);
+        assert_eq!(con.connection_info.as_ref().unwrap().redis.db, 1);
+
+        let err = con.change_db(99).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ResponseError);
+        assert_eq!(con.get_db(), 1);
+
+        let expected = [
+            cmd("SELECT").arg(1).get_packed_command(),
+            cmd("SELECT").arg(99).get_packed_command(),
+        ]
+        .concat();
+        expect_sent(&mut server, &expected);
+    }
+
+    #[test]
+    fn test_read_value_chunked() {
+        use crate::Commands;
+
+        let (mut con, mut server) = fake_server(b":10\r\n$4\r\nabcd\r\n$4\r\nefgh\r\n$2\r\nij\r\n");
+        let chunks = con
+            .read_value_chunked("big", 4)
+            .collect::<RedisResult<Vec<Vec<u8>>>>()
+            .unwrap();
+        assert_eq!(
+            chunks,
+            vec![b"abcd".to_vec(), b"efgh".to_vec(), b"ij".to_vec()]
+        );
+
+        let getrange = |start: usize, end: usize| {
+            cmd("GETRANGE")
+                .arg("big")
+                .arg(start)
+                .arg(end)
+                .get_packed_command()
+        };
+        let expected = [
+            cmd("STRLEN").arg("big").get_packed_command(),
+            getrange(0, 3),
+            getrange(4, 7),
+            getrange(8, 9),
+        ]
+        .concat();
+        expect_sent(&mut server, &expected);
+    }
+
+    #[test]
+    fn test_always_select() {
+        let server = FakeServer::bind();
+        let port = server.port();
+        let client = lunatic::spawn_link!(@task |port| {
+            let url = format!("redis://127.0.0.1:{}/0?always_select=true", port);
+            connect(&url.into_connection_info().unwrap(), None).unwrap().get_db()
+        });
+
+        let mut stream = server.accept(b"+OK\r\n");
+        expect_sent(&mut stream, &cmd("SELECT").arg(0).get_packed_command());
+        assert_eq!(client.result(), 0);
+    }
+
+    #[test]
+    fn test_shutdown() {
+        use crate::{Commands, ShutdownMode};
+
+        // A refusal is an error.
+        let (mut con, _server) = fake_server(b"-ERR Errors trying to SHUTDOWN. Check logs.\r\n");
+        let err = con.shutdown(None).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ResponseError);
+
+        // Closing the connection instead of replying is a success.
+        let (mut con, server) = fake_server(b"");
+        let expected = cmd("SHUTDOWN")
+            .arg(ShutdownMode::NoSave)
+            .get_packed_command();
+        // The stream is closed when the task that owns it finishes.
+        let ctx = (server, expected.len());
+        let closer = lunatic::spawn_link!(@task |ctx| {
+            let (mut server, len): (TcpStream, usize) = ctx;
+            let mut sent = vec![0; len];
+            server.read_exact(&mut sent).unwrap();
+            sent
+        });
+        assert_eq!(con.shutdown(Some(ShutdownMode::NoSave)), Ok(()));
+        assert_eq!(closer.result(), expected);
+    }
+
+    #[test]
+    fn test_push_frames_are_not_replies() {
+        // An invalidation pushed ahead of the GET reply, and the
+        // confirmation of an UNSUBSCRIBE, which is the reply itself.
+        let (mut con, _server) = fake_server(
+            b">2\r\n$10\r\ninvalidate\r\n*1\r\n$3\r\nfoo\r\n$3\r\nbar\r\n\
+              >3\r\n$11\r\nunsubscribe\r\n$2\r\nch\r\n:0\r\n",
+        );
+        let value: String = cmd("GET").arg("foo").query(&mut con).unwrap();
+        assert_eq!(value, "bar");
+        let reply: Value = cmd("UNSUBSCRIBE").arg("ch").query(&mut con).unwrap();
+        assert_eq!(
+            reply,
+            Value::Push(vec![
+                Value::Data(b"unsubscribe".to_vec()),
+                Value::Data(b"ch".to_vec()),
+                Value::Int(0),
+            ])
+        );
+
+        assert_eq!(
+            con.recv_response::<TcpStream>().unwrap(),
+            Value::Push(vec![
+                Value::Data(b"invalidate".to_vec()),
+                Value::Bulk(vec![Value::Data(b"foo".to_vec())]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_on_connect_runs_on_every_connect() {
+        fn set_name(con: &mut Connection) -> RedisResult<()> {
+            cmd("CLIENT").arg("SETNAME").arg("hooked").query(con)
+        }
+
+        let server = FakeServer::bind();
+        let mut info = server.info();
+        info.redis.on_connect = Some(OnConnect(set_name));
+
+        // The hook is left behind when the info is sent to another process,
+        // attached again it also runs when the connection is replaced.
+        let client = lunatic::spawn_link!(@task |info| {
+            let mut info = info;
+            let arrived = info.redis.on_connect.is_some();
+            info.redis.on_connect = Some(OnConnect(set_name));
+            let mut con = connect(&info, None).unwrap();
+            con.reconnect().unwrap();
+            (arrived, con.is_open())
+        });
+
+        let expected = cmd("CLIENT")
+            .arg("SETNAME")
+            .arg("hooked")
+            .get_packed_command();
+        for _ in 0..2 {
+            let mut stream = server.accept(b"+OK\r\n");
+            expect_sent(&mut stream, &expected);
+        }
+        assert_eq!(client.result(), (false, true));
+    }
+
+    #[test]
+    fn test_strict_parsing() {
+        let server = FakeServer::bind();
+        let url = format!("redis://127.0.0.1:{}/?strict_parsing=true", server.port());
+        let mut con = connect(&url.into_connection_info().unwrap(), None).unwrap();
+        // A lenient parser would take the padded integer as `42`.
+        let _stream = server.accept(b": 42 \r\n");
+        assert!(con.clone().parser.is_strict());
+        assert!(con.strip().with_parser().parser.is_strict());
+
+        let err = cmd("INCR").arg("n").query::<i64>(&mut con).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ParseError);
+    }
+
+    #[test]
+    fn test_client_name() {
+        let server = FakeServer::bind();
+        let port = server.port();
+        let client = lunatic::spawn_link!(@task |port| {
+            let url = format!("redis://127.0.0.1:{}/?client_name=worker-1", port);
+            let mut con = connect(&url.into_connection_info().unwrap(), None).unwrap();
+            let first = con.get_client_name().unwrap();
+            con.set_client_name("worker-2").unwrap();
+            let second = con.get_client_name().unwrap();
+            let kept = con.connection_info.unwrap().redis.client_name;
+            (first, second, kept)
+        });
+
+        let mut stream = server.accept(b"+OK\r\n$8\r\nworker-1\r\n+OK\r\n$8\r\nworker-2\r\n");
+        let expected = [
+            cmd("CLIENT")
+                .arg("SETNAME")
+                .arg("worker-1")
+                .get_packed_command(),
+            cmd("CLIENT").arg("GETNAME").get_packed_command(),
+            cmd("CLIENT")
+                .arg("SETNAME")
+                .arg("worker-2")
+                .get_packed_command(),
+            cmd("CLIENT").arg("GETNAME").get_packed_command(),
+        ]
+        .concat();
+        expect_sent(&mut stream, &expected);
+
+        let worker = |name: &str| Some(name.to_string());
+        assert_eq!(
+            client.result(),
+            (worker("worker-1"), worker("worker-2"), worker("worker-2"))
+        );
+    }
 }
@@ -0,0 +1,64 @@
+//! A stand-in for a redis server in unit tests.
+//!
+//! The server does not parse the commands it gets.  Tests queue the replies
+//! up front, the client reads them once it asked, and check afterwards what
+//! was sent with [`expect_sent`].
+
+use std::io::{Read, Write};
+
+use lunatic::net::{TcpListener, TcpStream};
+
+use crate::connection::{connect, Connection, ConnectionInfo, IntoConnectionInfo};
+
+/// Listens on a free port of the loopback interface.
+pub(crate) struct FakeServer {
+    listener: TcpListener,
+    info: ConnectionInfo,
+}
+
+impl FakeServer {
+    /// Starts listening.  Once the server is dropped, connecting to its
+    /// address fails.
+    pub(crate) fn bind() -> FakeServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let info = ("127.0.0.1", port).into_connection_info().unwrap();
+        FakeServer { listener, info }
+    }
+
+    /// Returns the info to connect to the server.
+    pub(crate) fn info(&self) -> ConnectionInfo {
+        self.info.clone()
+    }
+
+    /// Returns the port the server listens on.
+    pub(crate) fn port(&self) -> u16 {
+        self.listener.local_addr().unwrap().port()
+    }
+
+    /// Accepts the next connection and sends `replies` on it right away.
+    pub(crate) fn accept(&self, replies: &[u8]) -> TcpStream {
+        let (mut stream, _) = self.listener.accept().unwrap();
+        stream.write_all(replies).unwrap();
+        stream
+    }
+}
+
+/// Connects to a server that has already sent `replies`, and returns the
+/// connection along with the server end of it.
+pub(crate) fn fake_server(replies: &[u8]) -> (Connection, TcpStream) {
+    let server = FakeServer::bind();
+    let con = connect(&server.info(), None).unwrap();
+    let stream = server.accept(replies);
+    (con, stream)
+}
+
+/// Asserts that the next bytes the client sent to `stream` are `expected`.
+pub(crate) fn expect_sent(stream: &mut TcpStream, expected: &[u8]) {
+    let mut sent = vec![0; expected.len()];
+    stream.read_exact(&mut sent).unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&sent),
+        String::from_utf8_lossy(expected)
+    );
+}
@@ -52,7 +52,16 @@
 //! There are a few features defined that can enable additional functionality
 //! if so desired.  Some of them are turned on by default.
 //!
+//! * `debug-commands`: enables `DEBUG` subcommands that are useful for
+//!   testing but should not be used against production servers
 //! * `geospatial`: enables geospatial support (enabled by default)
+//! * `json`: enables storing values as JSON with the `Json` wrapper and
+//!   reading replies into `serde_json::Value`
+//! * `lunatic-processes`: enables storing lunatic `Process` and `ProcessRef`
+//!   handles as redis values, e.g. for a redis backed process registry
+//! * `uuid`: enables using `uuid::Uuid` as redis keys and values
+//! * `weighted-sample`: enables `Commands::weighted_sample`, which draws
+//!   sorted set members proportional to their score on the client
 //!
 //! ## Connection Parameters
 //!
@@ -343,12 +352,17 @@ assert_eq!(result, Ok(("foo".to_string(), b"bar".to_vec())));
 // public api
 pub use crate::client::Client;
 pub use crate::cmd::{cmd, pack_command, pipe, Arg, Cmd, Iter};
-pub use crate::commands::{Commands, ControlFlow, Direction, LposOptions, PubSubCommands};
+pub use crate::commands::{
+    BitUnit, ClientKillFilter, ClientType, Commands, ControlFlow, Direction, FailoverOptions,
+    LposOptions, PubSubCommands, ScanOptions, ShutdownMode, ValueChunks, ZAddCondition,
+    ZAddOptions, ZAggregate, ZCombineOptions,
+};
 pub use crate::connection::{
-    parse_redis_url, transaction, Connection, ConnectionAddr, ConnectionInfo, ConnectionLike,
-    IntoConnectionInfo, Msg, RedisConnectionInfo,
+    parse_redis_url, transaction, transaction_deadline, Connection, ConnectionAddr, ConnectionInfo,
+    ConnectionLike, IntoConnectionInfo, Msg, MsgKind, OnConnect, ProtocolVersion,
+    RedisConnectionInfo,
 };
-pub use crate::parser::{parse_redis_value, Parser};
+pub use crate::parser::{encode_redis_value, parse_redis_value, Parser};
 pub use crate::pipeline::Pipeline;
 
 #[cfg(feature = "script")]
@@ -368,9 +382,13 @@ pub use crate::types::{
     FromRedisValue,
 
     // utility types
+    ConfigValue,
     InfoDict,
+    KeyspaceInfo,
     NumericBehavior,
     Expiry,
+    ExpireOption,
+    Strict,
 
     // error and result types
     RedisError,
@@ -382,11 +400,19 @@ pub use crate::types::{
     Value,
 };
 
+#[cfg(feature = "json")]
+#[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+pub use crate::types::Json;
+
 mod macros;
 mod pipeline;
 
+mod multiplexed;
+mod pool;
 mod pubsub;
 
+pub use multiplexed::MultiplexedConnection;
+pub use pool::RedisPool;
 pub use pubsub::RedisPubSub;
 
 #[cfg(feature = "acl")]
@@ -418,6 +444,8 @@ mod r2d2;
 #[cfg_attr(docsrs, doc(cfg(feature = "streams")))]
 pub mod streams;
 
+pub mod queue;
+
 mod client;
 mod cmd;
 mod commands;
@@ -425,3 +453,6 @@ mod connection;
 mod parser;
 mod script;
 mod types;
+
+#[cfg(test)]
+mod fake_server;
@@ -0,0 +1,283 @@
+use std::{io, time::Duration};
+
+use lunatic::{
+    abstract_process,
+    process::{ProcessRef, StartProcess},
+    MailboxResult,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::connection::{Connection, ConnectionLike};
+use crate::types::{RedisResult, Value};
+
+/// A connection shared by many lunatic processes.
+///
+/// The socket is owned by a separate process that runs one request at a
+/// time, so the commands of different processes are never interleaved on
+/// the wire and every reply goes back to the process that asked for it.
+/// Unlike a pooled connection nothing has to be checked out or returned:
+/// the handle only refers to that process, so it is cheap to clone and can
+/// be sent to other processes.
+///
+/// Commands that change the state of the connection, e.g. `SELECT`,
+/// `MULTI` or `SUBSCRIBE`, affect every process sharing it and should not
+/// be sent through a multiplexed connection.  A pipeline is sent as a
+/// single request and its replies are not mixed with those of others.
+///
+/// Blocking commands like `BLPOP`, `XREAD` with `BLOCK` or `WAIT` hold up
+/// every other process sharing the connection until the server replies, so
+/// they should get a connection of their own.
+///
+/// The process owning the socket is linked to the one that created the
+/// connection.  If the socket fails, the error is returned for the request
+/// that hit it and the process reconnects, so that the next requests are
+/// sent over a new connection.  By default a request waits for its reply
+/// as long as it takes, see `set_request_timeout` to give up earlier.
+///
+/// ```rust,no_run
+/// use redis::Commands;
+/// # fn do_something() -> redis::RedisResult<()> {
+/// let client = redis::Client::open("redis://127.0.0.1/")?;
+/// let mut con = client.get_multiplexed_connection()?;
+///
+/// let mut other = con.clone();
+/// let _: () = other.set("my_key", 42)?;
+/// let value: i32 = con.get("my_key")?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MultiplexedConnection {
+    process: ProcessRef<MultiplexedProcess>,
+    db: i64,
+    timeout: Option<Duration>,
+}
+
+impl MultiplexedConnection {
+    /// Moves `con` into a new process linked to the current one and returns
+    /// a handle to it.
+    pub fn new(con: Connection) -> MultiplexedConnection {
+        let db = con.get_db();
+        MultiplexedConnection {
+            process: MultiplexedProcess::start_link(con, None),
+            db,
+            timeout: None,
+        }
+    }
+
+    /// Sets how long a request may take, including the time it waits for
+    /// the requests of other processes to finish.
+    ///
+    /// If the value is `None`, requests wait for their reply indefinitely.
+    /// Otherwise they fail with a timeout error, e.g. when the process owning
+    /// the socket was closed.  The request may still be sent to the server
+    /// afterwards.  The timeout only applies to this handle and the clones
+    /// made from it afterwards.
+    pub fn set_request_timeout(&mut self, dur: Option<Duration>) {
+        self.timeout = dur;
+    }
+
+    /// Returns the request timeout set with `set_request_timeout`.
+    pub fn request_timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Stops the process owning the socket, which closes the connection
+    /// for every clone of this handle.
+    pub fn close(self) {
+        self.process.shutdown();
+    }
+}
+
+/// Turns the reply to a request made with a timeout into a result.
+fn reply<T>(result: MailboxResult<RedisResult<T>>) -> RedisResult<T> {
+    match result {
+        MailboxResult::Message(result) => result,
+        MailboxResult::TimedOut => Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "Timed out waiting for the multiplexed connection",
+        )
+        .into()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::BrokenPipe,
+            "The multiplexed connection did not reply",
+        )
+        .into()),
+    }
+}
+
+impl ConnectionLike for MultiplexedConnection {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        match self.timeout {
+            Some(timeout) => reply(self.process.with_timeout(timeout).command(cmd.to_vec())),
+            None => self.process.command(cmd.to_vec()),
+        }
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        let arg = (cmd.to_vec(), offset, count);
+        match self.timeout {
+            Some(timeout) => reply(self.process.with_timeout(timeout).commands(arg)),
+            None => self.process.commands(arg),
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        self.db
+    }
+
+    fn check_connection(&mut self) -> bool {
+        match self.timeout {
+            Some(timeout) => matches!(
+                self.process.with_timeout(timeout).ping(),
+                MailboxResult::Message(true)
+            ),
+            None => self.process.ping(),
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        match self.timeout {
+            Some(timeout) => matches!(
+                self.process.with_timeout(timeout).connected(),
+                MailboxResult::Message(true)
+            ),
+            None => self.process.connected(),
+        }
+    }
+}
+
+/// The state of the process owning the socket.
+#[derive(Serialize, Deserialize)]
+struct MultiplexedProcess {
+    con: Connection,
+}
+
+#[abstract_process]
+impl MultiplexedProcess {
+    #[init]
+    fn init(_this: ProcessRef<Self>, con: Connection) -> Self {
+        MultiplexedProcess { con }
+    }
+
+    #[handle_request]
+    fn command(&mut self, cmd: Vec<u8>) -> RedisResult<Value> {
+        let result = self.con.req_packed_command(&cmd);
+        self.recover(result)
+    }
+
+    #[handle_request]
+    fn commands(&mut self, arg: (Vec<u8>, usize, usize)) -> RedisResult<Vec<Value>> {
+        let (cmd, offset, count) = arg;
+        let result = self.con.req_packed_commands(&cmd, offset, count);
+        self.recover(result)
+    }
+
+    #[handle_request]
+    fn ping(&mut self) -> bool {
+        self.con.check_connection()
+    }
+
+    #[handle_request]
+    fn connected(&mut self) -> bool {
+        self.con.is_open()
+    }
+
+    /// Reconnects after a socket error, so that it doesn't fail the requests
+    /// of every other process too.  The error is still returned, as it is
+    /// unknown whether the server ran the command.
+    fn recover<T>(&mut self, result: RedisResult<T>) -> RedisResult<T> {
+        if matches!(result, Err(ref err) if err.is_io_error()) {
+            let _ = self.con.reconnect_if_dead();
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+    use std::time::Duration;
+
+    use crate::fake_server::{expect_sent, FakeServer};
+    use crate::{cmd, Client, ConnectionLike};
+
+    #[test]
+    fn test_concurrent_gets() {
+        let server = FakeServer::bind();
+        let client = Client::open(server.info()).unwrap();
+        let con = client.get_multiplexed_connection().unwrap();
+        let mut stream = server.accept(b"");
+
+        let tasks: Vec<_> = (0..5)
+            .map(|i| {
+                let ctx = (con.clone(), i);
+                lunatic::spawn_link!(@task |ctx| {
+                    let (mut con, i) = ctx;
+                    cmd("GET").arg(format!("key-{}", i)).query::<String>(&mut con).unwrap()
+                })
+            })
+            .collect();
+        // Give every task the time to send its request.
+        lunatic::sleep(Duration::from_millis(100));
+
+        // The commands arrive one by one, each is only sent once the reply
+        // to the previous one was read.
+        let len = cmd("GET").arg("key-0").get_packed_command().len();
+        for _ in 0..5 {
+            let mut sent = vec![0; len];
+            stream.set_read_timeout(None).unwrap();
+            stream.read_exact(&mut sent).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_millis(50)))
+                .unwrap();
+            assert!(stream.read(&mut [0; 1]).is_err(), "next command sent early");
+            let i = sent[len - 3] as char;
+            write!(stream, "$5\r\nval-{}\r\n", i).unwrap();
+        }
+
+        for (i, task) in tasks.into_iter().enumerate() {
+            assert_eq!(task.result(), format!("val-{}", i));
+        }
+    }
+
+    #[test]
+    fn test_request_timeout() {
+        let server = FakeServer::bind();
+        let client = Client::open(server.info()).unwrap();
+        let mut con = client.get_multiplexed_connection().unwrap();
+        // The server accepts the connection but never replies.
+        let _stream = server.accept(b"");
+
+        con.set_request_timeout(Some(Duration::from_millis(100)));
+        assert_eq!(con.request_timeout(), Some(Duration::from_millis(100)));
+        let err = cmd("GET").arg("key").query::<String>(&mut con).unwrap_err();
+        assert!(err.is_timeout(), "{}", err);
+        // The socket is still busy with the first request.
+        assert!(!con.check_connection());
+    }
+
+    #[test]
+    fn test_reconnects_after_socket_error() {
+        let server = FakeServer::bind();
+        let client = Client::open(server.info()).unwrap();
+        let mut con = client.get_multiplexed_connection().unwrap();
+        drop(server.accept(b""));
+
+        let err = cmd("GET").arg("key").query::<String>(&mut con).unwrap_err();
+        assert!(err.is_io_error(), "{}", err);
+
+        // The process connected again and uses the new socket.
+        let mut stream = server.accept(b"$5\r\nvalue\r\n");
+        let task = lunatic::spawn_link!(@task |con| {
+            let mut con = con;
+            cmd("GET").arg("key").query::<String>(&mut con).unwrap()
+        });
+        expect_sent(&mut stream, &cmd("GET").arg("key").get_packed_command());
+        assert_eq!(task.result(), "value");
+    }
+}
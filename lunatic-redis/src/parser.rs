@@ -1,8 +1,11 @@
 use std::{
-    io::{self, Read},
-    str,
+    cell::RefCell,
+    io::{self, Read, Write},
+    mem, str,
 };
 
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
 use crate::types::{make_extension_error, ErrorKind, RedisError, RedisResult, Value};
 
 use combine::{
@@ -53,13 +56,32 @@ where
     }
 }
 
-fn value<'a, I>(
-) -> impl combine::Parser<I, Output = RedisResult<Value>, PartialState = AnySendSyncPartialState>
+/// The buffer of the caller a top level bulk string reply is copied into,
+/// see `Parser::parse_value_into`.
+type DataBuffer<'b> = &'b RefCell<Vec<u8>>;
+
+fn data_value(buffer: Option<DataBuffer<'_>>, bytes: &[u8]) -> Value {
+    match buffer {
+        Some(buffer) => {
+            let mut buf = mem::take(&mut *buffer.borrow_mut());
+            buf.clear();
+            buf.extend_from_slice(bytes);
+            Value::Data(buf)
+        }
+        None => Value::Data(bytes.to_vec()),
+    }
+}
+
+fn value<'a, 'b, I>(
+    strict: bool,
+    discard_status: bool,
+    buffer: Option<DataBuffer<'b>>,
+) -> impl combine::Parser<I, Output = RedisResult<Value>, PartialState = AnySendSyncPartialState> + 'b
 where
-    I: RangeStream<Token = u8, Range = &'a [u8]>,
+    I: RangeStream<Token = u8, Range = &'a [u8]> + 'b,
     I::Error: combine::ParseError<u8, &'a [u8], I::Position>,
 {
-    opaque!(any_send_sync_partial_state(any().then_partial(
+    opaque!(any_send_sync_partial_state(any().then_partial({
         move |&mut b| {
             let line = || {
                 recognize(take_until_bytes(&b"\r\n"[..]).with(take(2).map(|_| ()))).and_then(
@@ -70,8 +92,10 @@ where
             };
 
             let status = || {
-                line().map(|line| {
-                    if line == "OK" {
+                line().map(move |line| {
+                    // When the caller throws the reply away anyway there is
+                    // no point in allocating a string for the status.
+                    if line == "OK" || discard_status {
                         Value::Okay
                     } else {
                         Value::Status(line.into())
@@ -80,21 +104,29 @@ where
             };
 
             let int = || {
-                line().and_then(|line| match line.trim().parse::<i64>() {
-                    Err(_) => Err(StreamErrorFor::<I>::message_static_message(
-                        "Expected integer, got garbage",
-                    )),
-                    Ok(value) => Ok(value),
+                line().and_then(move |line| {
+                    // Strict framing does not tolerate padding around integers.
+                    let line = if strict { line } else { line.trim() };
+                    match line.parse::<i64>() {
+                        Err(_) => Err(StreamErrorFor::<I>::message_static_message(
+                            "Expected integer, got garbage",
+                        )),
+                        Ok(value) => Ok(value),
+                    }
                 })
             };
 
-            let data = || {
+            let data = |buffer: Option<DataBuffer<'b>>| {
                 int().then_partial(move |size| {
-                    if *size < 0 {
-                        combine::value(Value::Nil).left()
+                    if strict && *size < -1 {
+                        combine::unexpected_any(combine::error::Token(b'-'))
+                            .left()
+                            .left()
+                    } else if *size < 0 {
+                        combine::value(Value::Nil).right().left()
                     } else {
                         take(*size as usize)
-                            .map(|bs: &[u8]| Value::Data(bs.to_vec()))
+                            .map(move |bs: &[u8]| data_value(buffer, bs))
                             .skip(crlf())
                             .right()
                     }
@@ -102,18 +134,87 @@ where
             };
 
             let bulk = || {
-                int().then_partial(|&mut length| {
-                    if length < 0 {
-                        combine::value(Value::Nil).map(Ok).left()
+                int().then_partial(move |&mut length| {
+                    if strict && length < -1 {
+                        combine::unexpected_any(combine::error::Token(b'-'))
+                            .left()
+                            .left()
+                    } else if length < 0 {
+                        combine::value(Value::Nil).map(Ok).right().left()
                     } else {
                         let length = length as usize;
-                        combine::count_min_max(length, length, value())
+                        combine::count_min_max(length, length, value(strict, discard_status, None))
                             .map(|result: ResultExtend<_, _>| result.0.map(Value::Bulk))
                             .right()
                     }
                 })
             };
 
+            let map = || {
+                int().then_partial(move |&mut length| {
+                    if strict && length < -1 {
+                        combine::unexpected_any(combine::error::Token(b'-'))
+                            .left()
+                            .left()
+                    } else if length < 0 {
+                        combine::value(Value::Nil).map(Ok).right().left()
+                    } else {
+                        let length = length as usize * 2;
+                        combine::count_min_max(length, length, value(strict, discard_status, None))
+                            .map(move |result: ResultExtend<Vec<Value>, _>| {
+                                result.0.map(|items| {
+                                    let mut items = items.into_iter();
+                                    let mut pairs = Vec::with_capacity(length / 2);
+                                    while let (Some(k), Some(v)) = (items.next(), items.next()) {
+                                        pairs.push((k, v));
+                                    }
+                                    Value::Map(pairs)
+                                })
+                            })
+                            .right()
+                    }
+                })
+            };
+
+            let double = || {
+                line().and_then(|line| match line.parse::<f64>() {
+                    Err(_) => Err(StreamErrorFor::<I>::message_static_message(
+                        "Expected double, got garbage",
+                    )),
+                    Ok(value) => Ok(value),
+                })
+            };
+
+            let boolean = || {
+                line().and_then(|line| match line {
+                    "t" => Ok(true),
+                    "f" => Ok(false),
+                    _ => Err(StreamErrorFor::<I>::message_static_message(
+                        "Expected boolean, got garbage",
+                    )),
+                })
+            };
+
+            let verbatim = || {
+                data(None).and_then(|value| match value {
+                    Value::Data(bytes) if bytes.len() >= 4 && bytes[3] == b':' => {
+                        match (str::from_utf8(&bytes[..3]), str::from_utf8(&bytes[4..])) {
+                            (Ok(format), Ok(text)) => Ok(Value::VerbatimString {
+                                format: format.into(),
+                                text: text.into(),
+                            }),
+                            _ => Err(StreamErrorFor::<I>::message_static_message(
+                                "Verbatim string is not valid UTF-8",
+                            )),
+                        }
+                    }
+                    Value::Nil => Ok(Value::Nil),
+                    _ => Err(StreamErrorFor::<I>::message_static_message(
+                        "Verbatim string has no format",
+                    )),
+                })
+            };
+
             let error = || {
                 line().map(|line: &str| {
                     let desc = "An error was signalled by the server";
@@ -142,18 +243,45 @@ where
             combine::dispatch!(b;
                 b'+' => status().map(Ok),
                 b':' => int().map(|i| Ok(Value::Int(i))),
-                b'$' => data().map(Ok),
+                b'$' => data(buffer).map(Ok),
                 b'*' => bulk(),
                 b'-' => error().map(Err),
+                b'_' => line().map(|_| Ok(Value::Nil)),
+                b',' => double().map(|d| Ok(Value::Double(d))),
+                b'#' => boolean().map(|b| Ok(Value::Boolean(b))),
+                b'(' => line().map(|line| Ok(Value::BigNumber(line.into()))),
+                b'=' => verbatim().map(Ok),
+                b'%' => map(),
+                b'|' => (map(), value(strict, discard_status, None)).map(
+                    |(attributes, data)| -> RedisResult<Value> {
+                        let attributes = match attributes? {
+                            Value::Map(pairs) => pairs,
+                            _ => vec![],
+                        };
+                        Ok(Value::Attribute {
+                            data: Box::new(data?),
+                            attributes,
+                        })
+                    }
+                ),
+                b'~' => bulk().map(|result| result.map(|value| match value {
+                    Value::Bulk(items) => Value::Set(items),
+                    value => value,
+                })),
+                b'>' => bulk().map(|result| result.map(|value| match value {
+                    Value::Bulk(items) => Value::Push(items),
+                    value => value,
+                })),
                 b => combine::unexpected_any(combine::error::Token(b))
             )
         }
-    )))
+    })))
 }
 
 /// The internal redis response parser.
 pub struct Parser {
     decoder: combine::stream::decoder::Decoder<AnySendSyncPartialState, PointerOffset<[u8]>>,
+    strict: bool,
 }
 
 impl Default for Parser {
@@ -162,6 +290,24 @@ impl Default for Parser {
     }
 }
 
+// Only the framing mode is kept when a connection is sent to another
+// process, a partially read reply can't be resumed there anyway.
+impl Serialize for Parser {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.strict.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Parser {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(if bool::deserialize(deserializer)? {
+            Parser::new_strict()
+        } else {
+            Parser::new()
+        })
+    }
+}
+
 /// The parser can be used to parse redis responses into values.  Generally
 /// you normally do not use this directly as it's already done for you by
 /// the client but in some more complex situations it might be useful to be
@@ -174,17 +320,84 @@ impl Parser {
     pub fn new() -> Parser {
         Parser {
             decoder: combine::stream::decoder::Decoder::new(),
+            strict: false,
         }
     }
 
+    /// Creates a new parser that enforces strict RESP framing.
+    ///
+    /// Any reply that does not conform to the protocol (unknown type prefix,
+    /// padded integers, invalid negative lengths) is rejected with an error of
+    /// kind `ErrorKind::ParseError`.  This makes connecting to something that
+    /// is not a redis server (e.g. an HTTP service on the wrong port) fail
+    /// loudly instead of surfacing as a confusing type error later on.
+    pub fn new_strict() -> Parser {
+        Parser {
+            decoder: combine::stream::decoder::Decoder::new(),
+            strict: true,
+        }
+    }
+
+    /// Returns true if this parser enforces strict RESP framing.
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
     // public api
 
     /// Parses synchronously into a single value from the reader.
-    pub fn parse_value<T: Read>(&mut self, mut reader: T) -> RedisResult<Value> {
+    pub fn parse_value<T: Read>(&mut self, reader: T) -> RedisResult<Value> {
+        self.parse(reader, false)
+    }
+
+    /// Like `parse_value` but does not allocate for status replies, which
+    /// are all returned as `Value::Okay`.  Only useful if the caller is going
+    /// to discard the reply.
+    pub(crate) fn parse_value_discarding_status<T: Read>(
+        &mut self,
+        reader: T,
+    ) -> RedisResult<Value> {
+        self.parse(reader, true)
+    }
+
+    /// Like `parse_value` but copies a bulk string reply into `buf` instead
+    /// of allocating a new vector for it.
+    ///
+    /// The vector of `buf` becomes the `Value::Data` that is returned, and
+    /// `buf` is left empty.  Hand the vector back once done with it, and a
+    /// loop that keeps passing the same buffer only allocates for replies
+    /// that do not fit into it.  For other replies `buf` is left alone.
+    pub fn parse_value_into<T: Read>(
+        &mut self,
+        reader: T,
+        buf: &mut Vec<u8>,
+    ) -> RedisResult<Value> {
+        let buffer = RefCell::new(mem::take(buf));
+        let result = self.decode(reader, false, Some(&buffer));
+        *buf = buffer.into_inner();
+        result
+    }
+
+    /// `parse_value` or `parse_value_discarding_status`, depending on
+    /// `discard_status`.
+    pub(crate) fn parse<T: Read>(&mut self, reader: T, discard_status: bool) -> RedisResult<Value> {
+        self.decode(reader, discard_status, None)
+    }
+
+    fn decode<T: Read>(
+        &mut self,
+        mut reader: T,
+        discard_status: bool,
+        buffer: Option<DataBuffer<'_>>,
+    ) -> RedisResult<Value> {
+        let strict = self.strict;
         let mut decoder = &mut self.decoder;
-        let result = combine::decode!(decoder, reader, value(), |input, _| {
-            combine::stream::easy::Stream::from(input)
-        });
+        let result = combine::decode!(
+            decoder,
+            reader,
+            value(strict, discard_status, buffer),
+            |input, _| { combine::stream::easy::Stream::from(input) }
+        );
         match result {
             Err(err) => Err(match err {
                 combine::stream::decoder::Error::Io { error, .. } => error.into(),
@@ -196,7 +409,15 @@ impl Parser {
                             .map_range(|range| format!("{:?}", range))
                             .map_position(|pos| pos.translate_position(decoder.buffer()))
                             .to_string();
-                        RedisError::from((ErrorKind::ResponseError, "parse error", err))
+                        if strict {
+                            RedisError::from((
+                                ErrorKind::ParseError,
+                                "reply violates RESP framing (is this a redis server?)",
+                                err,
+                            ))
+                        } else {
+                            RedisError::from((ErrorKind::ResponseError, "parse error", err))
+                        }
                     }
                 }
             }),
@@ -213,3 +434,274 @@ pub fn parse_redis_value<T: Read>(bytes: T) -> RedisResult<Value> {
     let mut parser = Parser::new();
     parser.parse_value(bytes)
 }
+
+/// Encodes a redis value into the wire format.
+///
+/// This is the counterpart of [`parse_redis_value`] and is useful to build
+/// proxies or test fixtures that have to produce raw replies.
+pub fn encode_redis_value<W: Write>(value: &Value, writer: &mut W) -> io::Result<()> {
+    match *value {
+        Value::Nil => writer.write_all(b"$-1\r\n"),
+        Value::Int(val) => write!(writer, ":{}\r\n", val),
+        Value::Data(ref val) => {
+            write!(writer, "${}\r\n", val.len())?;
+            writer.write_all(val)?;
+            writer.write_all(b"\r\n")
+        }
+        Value::Bulk(ref values) => {
+            write!(writer, "*{}\r\n", values.len())?;
+            for val in values.iter() {
+                encode_redis_value(val, writer)?;
+            }
+            Ok(())
+        }
+        Value::Okay => writer.write_all(b"+OK\r\n"),
+        Value::Status(ref s) => write!(writer, "+{}\r\n", s),
+        Value::Double(val) => write!(writer, ",{}\r\n", val),
+        Value::Boolean(val) => write!(writer, "#{}\r\n", if val { 't' } else { 'f' }),
+        Value::BigNumber(ref val) => write!(writer, "({}\r\n", val),
+        Value::VerbatimString {
+            ref format,
+            ref text,
+        } => write!(
+            writer,
+            "={}\r\n{}:{}\r\n",
+            format.len() + 1 + text.len(),
+            format,
+            text
+        ),
+        Value::Map(ref values) => {
+            write!(writer, "%{}\r\n", values.len())?;
+            for (k, v) in values.iter() {
+                encode_redis_value(k, writer)?;
+                encode_redis_value(v, writer)?;
+            }
+            Ok(())
+        }
+        Value::Attribute {
+            ref data,
+            ref attributes,
+        } => {
+            write!(writer, "|{}\r\n", attributes.len())?;
+            for (k, v) in attributes.iter() {
+                encode_redis_value(k, writer)?;
+                encode_redis_value(v, writer)?;
+            }
+            encode_redis_value(data, writer)
+        }
+        Value::Set(ref values) | Value::Push(ref values) => {
+            let prefix = if let Value::Set(_) = value { '~' } else { '>' };
+            write!(writer, "{}{}\r\n", prefix, values.len())?;
+            for val in values.iter() {
+                encode_redis_value(val, writer)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strict_rejects_http_response() {
+        let bytes = b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n";
+        let err = Parser::new_strict().parse_value(&bytes[..]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ParseError);
+    }
+
+    #[test]
+    fn test_strict_rejects_padded_integer() {
+        let mut parser = Parser::new_strict();
+        let err = parser.parse_value(&b": 42 \r\n"[..]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ParseError);
+
+        // The lenient parser keeps accepting it.
+        assert_eq!(
+            Parser::new().parse_value(&b": 42 \r\n"[..]).unwrap(),
+            Value::Int(42)
+        );
+    }
+
+    #[test]
+    fn test_discarding_status() {
+        let mut parser = Parser::new();
+        assert_eq!(
+            parser
+                .parse_value_discarding_status(&b"*2\r\n+QUEUED\r\n:1\r\n"[..])
+                .unwrap(),
+            Value::Bulk(vec![Value::Okay, Value::Int(1)])
+        );
+
+        // Errors are still reported as such.
+        let err = parser
+            .parse_value_discarding_status(&b"-ERR wrong\r\n"[..])
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ResponseError);
+    }
+
+    #[test]
+    fn test_parse_value_into() {
+        let mut parser = Parser::new();
+        let mut buf = Vec::with_capacity(64);
+        let ptr = buf.as_ptr();
+
+        // A bulk string takes the vector of the buffer with it
+        let reply = parser.parse_value_into(&b"$3\r\nfoo\r\n"[..], &mut buf);
+        let data = match reply.unwrap() {
+            Value::Data(data) => data,
+            reply => panic!("expected data, got {:?}", reply),
+        };
+        assert_eq!(data, b"foo");
+        assert_eq!(data.as_ptr(), ptr);
+        assert!(buf.is_empty());
+        buf = data;
+
+        // Other replies are returned and leave the buffer alone
+        let reply = parser.parse_value_into(&b":1\r\n"[..], &mut buf);
+        assert_eq!(reply.unwrap(), Value::Int(1));
+        assert_eq!(buf, b"foo");
+
+        let reply = parser.parse_value_into(&b"$3\r\nbar\r\n"[..], &mut buf);
+        match reply.unwrap() {
+            Value::Data(data) => {
+                assert_eq!(data, b"bar");
+                assert_eq!(data.as_ptr(), ptr);
+                buf = data;
+            }
+            reply => panic!("expected data, got {:?}", reply),
+        }
+
+        // Nested bulk strings are allocated as usual
+        let reply = parser.parse_value_into(&b"*1\r\n$1\r\nx\r\n"[..], &mut buf);
+        assert_eq!(
+            reply.unwrap(),
+            Value::Bulk(vec![Value::Data(b"x".to_vec())])
+        );
+        assert_eq!(buf.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let values = vec![
+            Value::Nil,
+            Value::Int(-42),
+            Value::Data(b"foo\r\nbar\xff".to_vec()),
+            Value::Data(vec![]),
+            Value::Bulk(vec![]),
+            Value::Bulk(vec![
+                Value::Int(1),
+                Value::Bulk(vec![Value::Data(b"nested".to_vec()), Value::Nil]),
+            ]),
+            Value::Status("PONG".to_string()),
+            Value::Okay,
+            Value::Double(1.5),
+            Value::Double(f64::INFINITY),
+            Value::Boolean(true),
+            Value::Boolean(false),
+            Value::BigNumber("3492890328409238509324850943850943825024385".to_string()),
+            Value::VerbatimString {
+                format: "txt".to_string(),
+                text: "Some string".to_string(),
+            },
+            Value::Map(vec![(Value::Data(b"key".to_vec()), Value::Int(1))]),
+            Value::Set(vec![Value::Data(b"a".to_vec()), Value::Data(b"b".to_vec())]),
+            Value::Push(vec![
+                Value::Data(b"message".to_vec()),
+                Value::Data(b"channel".to_vec()),
+                Value::Data(b"payload".to_vec()),
+            ]),
+            Value::Attribute {
+                data: Box::new(Value::Int(1)),
+                attributes: vec![(Value::Data(b"ttl".to_vec()), Value::Int(3600))],
+            },
+        ];
+        for value in values {
+            let mut encoded = Vec::new();
+            encode_redis_value(&value, &mut encoded).unwrap();
+            assert_eq!(parse_redis_value(&encoded[..]).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_resp3_values() {
+        let parse = |bytes: &[u8]| parse_redis_value(bytes).unwrap();
+        assert_eq!(parse(b"_\r\n"), Value::Nil);
+        assert_eq!(parse(b",3.25\r\n"), Value::Double(3.25));
+        assert_eq!(parse(b",-inf\r\n"), Value::Double(f64::NEG_INFINITY));
+        assert_eq!(parse(b"#t\r\n"), Value::Boolean(true));
+        assert_eq!(
+            parse(b"(12345678901234567890\r\n"),
+            Value::BigNumber("12345678901234567890".to_string())
+        );
+        assert_eq!(
+            parse(b"=15\r\ntxt:Some string\r\n"),
+            Value::VerbatimString {
+                format: "txt".to_string(),
+                text: "Some string".to_string()
+            }
+        );
+        assert_eq!(
+            parse(b"%2\r\n$4\r\nport\r\n$4\r\n6379\r\n$7\r\ntimeout\r\n:0\r\n"),
+            Value::Map(vec![
+                (Value::Data(b"port".to_vec()), Value::Data(b"6379".to_vec())),
+                (Value::Data(b"timeout".to_vec()), Value::Int(0)),
+            ])
+        );
+        assert_eq!(
+            parse(b"~1\r\n#f\r\n"),
+            Value::Set(vec![Value::Boolean(false)])
+        );
+        assert!(parse_redis_value(&b"#x\r\n"[..]).is_err());
+        assert!(parse_redis_value(&b",abc\r\n"[..]).is_err());
+    }
+
+    #[test]
+    fn test_attributes() {
+        let reply = parse_redis_value(
+            &b"|1\r\n+key-popularity\r\n%1\r\n$1\r\na\r\n,0.1923\r\n*1\r\n:2039123\r\n"[..],
+        )
+        .unwrap();
+        let attributes = reply.attributes().unwrap();
+        assert_eq!(attributes.len(), 1);
+        assert_eq!(attributes[0].0, Value::Status("key-popularity".to_string()));
+        assert_eq!(
+            attributes[0].1,
+            Value::Map(vec![(Value::Data(b"a".to_vec()), Value::Double(0.1923))])
+        );
+        assert_eq!(
+            reply.without_attributes(),
+            &Value::Bulk(vec![Value::Int(2039123)])
+        );
+
+        // Conversions see the reply without attributes, at every level.
+        let typed: Vec<i64> = crate::from_redis_value(&reply).unwrap();
+        assert_eq!(typed, vec![2039123]);
+        let raw: Value = crate::from_redis_value(&reply).unwrap();
+        assert_eq!(raw, Value::Bulk(vec![Value::Int(2039123)]));
+
+        // An attributed integer and a map with an attributed value.
+        let nested = parse_redis_value(
+            &b"*2\r\n|1\r\n+ttl\r\n:10\r\n:1\r\n\
+               %1\r\n$1\r\nk\r\n|1\r\n+ttl\r\n:10\r\n:2\r\n"[..],
+        )
+        .unwrap();
+        let typed: (i64, std::collections::HashMap<String, i64>) =
+            crate::from_redis_value(&nested).unwrap();
+        assert_eq!(typed.0, 1);
+        assert_eq!(typed.1.get("k"), Some(&2));
+    }
+
+    #[test]
+    fn test_strict_accepts_valid_replies() {
+        let mut parser = Parser::new_strict();
+        assert!(parser.is_strict());
+        assert_eq!(
+            parser
+                .parse_value(&b"*2\r\n$3\r\nfoo\r\n$-1\r\n"[..])
+                .unwrap(),
+            Value::Bulk(vec![Value::Data(b"foo".to_vec()), Value::Nil])
+        );
+    }
+}
@@ -3,9 +3,10 @@
 use serde::{Deserialize, Serialize};
 
 use crate::cmd::{cmd, cmd_len, Cmd};
-use crate::connection::ConnectionLike;
+use crate::connection::{Connection, ConnectionLike};
 use crate::types::{
-    from_redis_value, ErrorKind, FromRedisValue, HashSet, RedisResult, ToRedisArgs, Value,
+    from_redis_value, ErrorKind, FromRedisValue, HashSet, RedisError, RedisResult, ToRedisArgs,
+    Value,
 };
 
 /// Represents a redis command pipeline.
@@ -137,6 +138,180 @@ impl Pipeline {
         )
     }
 
+    /// Executes the pipeline as a `MULTI`/`EXEC` transaction and returns the
+    /// reply of `EXEC`.
+    ///
+    /// The commands are always wrapped in `MULTI`/`EXEC`, whether the
+    /// pipeline is in atomic mode or not.  Unlike `query()` this tells the
+    /// two ways a transaction can end without running apart:
+    ///
+    /// * If a `WATCH`ed key was changed in the meantime, `EXEC` replies with
+    ///   nil and `Ok(None)` is returned.
+    /// * If the server rejected one of the commands while queueing them, e.g.
+    ///   because of a wrong number of arguments, the transaction is discarded
+    ///   and an error of kind `ExecAbortError` is returned.  Its detail holds
+    ///   the error of the rejected command.
+    ///
+    /// Errors of commands that failed while `EXEC` ran them are returned as
+    /// usual.  Note that the other commands of the transaction were still
+    /// executed in that case.
+    ///
+    /// ```rust,no_run
+    /// # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    /// # let mut con = client.get_connection().unwrap();
+    /// let _ : () = redis::cmd("WATCH").arg("key_1").query(&mut con).unwrap();
+    /// let old_val : i32 = redis::cmd("GET").arg("key_1").query(&mut con).unwrap();
+    /// let reply : Option<()> = redis::pipe()
+    ///     .cmd("SET").arg("key_1").arg(old_val + 1).ignore()
+    ///     .cmd("SET").arg("key_2").arg(old_val).ignore()
+    ///     .query_transaction(&mut con).unwrap();
+    /// if reply.is_none() {
+    ///     // key_1 was changed by someone else, try again.
+    /// }
+    /// ```
+    ///
+    /// If the server refuses to start the transaction, e.g. because `MULTI`
+    /// was already sent on this connection, its error is returned and none
+    /// of the commands are sent.
+    ///
+    /// NOTE: `MULTI`, the commands and `EXEC` are each sent once the replies
+    ///       before them are read, so this takes three round trips.
+    pub fn query_transaction<T: FromRedisValue>(
+        &self,
+        con: &mut dyn ConnectionLike,
+    ) -> RedisResult<Option<T>> {
+        if !con.supports_pipelining() {
+            fail!((
+                ErrorKind::ResponseError,
+                "This connection does not support pipelining."
+            ));
+        }
+        // Without a transaction the commands would run right away.
+        con.req_command(&cmd("MULTI"))?;
+        let mut packed = Vec::new();
+        write_pipeline(&mut packed, &self.commands, false);
+        let queued = con.req_packed_commands(&packed, 0, self.commands.len());
+        if let Err(err) = queued {
+            if err.is_io_error() {
+                return Err(err);
+            }
+            // The server flagged the transaction, so EXEC only discards it.
+            return match con.req_command(&cmd("EXEC")) {
+                Err(abort) if abort.kind() == ErrorKind::ExecAbortError => Err(RedisError::from((
+                    ErrorKind::ExecAbortError,
+                    "Transaction discarded because of previous errors",
+                    err.to_string(),
+                ))),
+                Err(abort) => Err(abort),
+                Ok(_) => Err(err),
+            };
+        }
+        match con.req_command(&cmd("EXEC"))? {
+            Value::Nil => Ok(None),
+            Value::Bulk(items) => from_redis_value(&self.make_pipeline_results(items)).map(Some),
+            _ => fail!((
+                ErrorKind::ResponseError,
+                "Invalid response when parsing exec response"
+            )),
+        }
+    }
+
+    /// Executes the pipeline and passes every return value to `f` in order
+    /// as soon as it is read from the connection.
+    ///
+    /// This is meant for very large pipelines where collecting all replies
+    /// into a single value (as `query()` does) would hold everything in memory
+    /// at once.  Ignored commands are checked for errors but not passed on.
+    /// Once a reply fails to convert or `f` returns an error, `f` is no longer
+    /// called, the remaining replies are drained and the first error is
+    /// returned.
+    ///
+    /// ```rust,no_run
+    /// # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    /// # let mut con = client.get_connection().unwrap();
+    /// let mut pipe = redis::pipe();
+    /// for i in 0..1000 {
+    ///     pipe.cmd("GET").arg(format!("key_{}", i));
+    /// }
+    /// let mut found = 0;
+    /// pipe.query_each(&mut con, |value: Option<String>| {
+    ///     found += value.is_some() as usize;
+    ///     Ok(())
+    /// }).unwrap();
+    /// ```
+    ///
+    /// NOTE: In atomic mode all replies arrive in a single `EXEC` reply, so
+    ///       they cannot be streamed and are only split up after reading it.
+    ///       If a watched key was modified the transaction is not executed
+    ///       and an `ExecAbortError` is returned.
+    pub fn query_each<T, F>(&self, con: &mut Connection, mut f: F) -> RedisResult<()>
+    where
+        T: FromRedisValue,
+        F: FnMut(T) -> RedisResult<()>,
+    {
+        if self.commands.is_empty() {
+            return Ok(());
+        }
+        if self.transaction_mode {
+            return match self.execute_transaction(con)? {
+                Value::Bulk(items) => items.iter().try_for_each(|item| f(from_redis_value(item)?)),
+                _ => fail!((
+                    ErrorKind::ExecAbortError,
+                    "Transaction aborted",
+                    "EXEC returned nil because a watched key was modified".to_string()
+                )),
+            };
+        }
+        con.req_packed_commands_each(
+            &encode_pipeline(&self.commands, false),
+            0,
+            self.commands.len(),
+            |idx, value| {
+                if self.ignored_commands.contains(&idx) {
+                    Ok(())
+                } else {
+                    f(from_redis_value(&value)?)
+                }
+            },
+        )
+    }
+
+    /// Executes the pipeline and returns the replies that were read before
+    /// the first error together with that error.
+    ///
+    /// Unlike `query()`, which discards everything once a reply fails, this
+    /// lets callers salvage the work of idempotent bulk reads when, for
+    /// instance, the connection drops partway through.  If every reply is
+    /// read and converted successfully the error is `None`.  Replies of
+    /// ignored commands are not returned.
+    ///
+    /// ```rust,no_run
+    /// # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    /// # let mut con = client.get_connection().unwrap();
+    /// let mut pipe = redis::pipe();
+    /// for i in 0..10 {
+    ///     pipe.cmd("GET").arg(format!("key_{}", i));
+    /// }
+    /// let (values, err) = pipe.query_partial::<Option<String>>(&mut con);
+    /// if let Some(err) = err {
+    ///     println!("only read {} replies: {}", values.len(), err);
+    /// }
+    /// ```
+    ///
+    /// NOTE: In atomic mode the replies arrive in a single `EXEC` reply, so
+    ///       either all of them or none are returned.
+    pub fn query_partial<T: FromRedisValue>(
+        &self,
+        con: &mut Connection,
+    ) -> (Vec<T>, Option<RedisError>) {
+        let mut rv = Vec::with_capacity(self.commands.len());
+        let result = self.query_each(con, |value| {
+            rv.push(value);
+            Ok(())
+        });
+        (rv, result.err())
+    }
+
     /// This is a shortcut to `query()` that does not return a value and
     /// will fail the task if the query of the pipeline fails.
     ///
@@ -274,3 +449,42 @@ macro_rules! implement_pipeline_commands {
 }
 
 implement_pipeline_commands!(Pipeline);
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::fake_server::fake_server;
+    use crate::{pipe, ErrorKind};
+
+    #[test]
+    fn test_query_each_streams_replies() {
+        // Only two of the three replies are ever sent.  Had the replies been
+        // collected before handing them out `f` would not be called at all.
+        let (mut con, _server) = fake_server(b":1\r\n:2\r\n");
+        con.set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        let mut pipe = pipe();
+        pipe.incr("a", 1).incr("a", 1).incr("a", 1);
+        let mut seen = Vec::new();
+        let err = pipe
+            .query_each(&mut con, |value: i64| {
+                seen.push(value);
+                Ok(())
+            })
+            .unwrap_err();
+        assert!(err.is_timeout());
+        assert_eq!(seen, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_query_each_aborted_transaction() {
+        let (mut con, _server) = fake_server(b"+OK\r\n+QUEUED\r\n*-1\r\n");
+        let mut pipe = pipe();
+        pipe.atomic().get("a");
+        let err = pipe
+            .query_each(&mut con, |_: Option<i64>| panic!("no reply expected"))
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ExecAbortError);
+    }
+}
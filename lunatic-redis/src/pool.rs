@@ -0,0 +1,562 @@
+use std::collections::{hash_map::Entry, HashMap, VecDeque};
+use std::io;
+use std::time::{Duration, Instant};
+
+use lunatic::{
+    abstract_process, host,
+    process::{ProcessRef, StartProcess},
+    Mailbox, MailboxResult, Process, Tag,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::cmd::cmd;
+use crate::connection::{
+    connect, Connection, ConnectionInfo, ConnectionLike, OnConnect, StrippedConnection,
+};
+use crate::types::{ErrorKind, RedisResult, Value};
+
+/// A pool of connections shared by many lunatic processes.
+///
+/// The connections are owned by a separate pool process.  `get_conn` takes
+/// an idle connection out of it, or opens a new one while fewer than
+/// `max_size` connections exist, and `return_conn` hands it back.  The pool
+/// itself only holds a reference to that process, so it is cheap to clone
+/// and can be sent to other processes.
+///
+/// A process that takes a connection is linked to the pool process until it
+/// gives all of its connections back.  If it dies in the meantime its
+/// connections are counted as closed, and if the pool process dies every
+/// process holding one of its connections dies with it.  A connection has
+/// to be returned by the process that took it out.
+///
+/// ```rust,no_run
+/// use redis::{Commands, IntoConnectionInfo, RedisPool};
+/// # fn do_something() -> redis::RedisResult<()> {
+/// let info = "redis://127.0.0.1/".into_connection_info()?;
+/// let pool = RedisPool::new(info, 4)?;
+///
+/// let mut con = pool.get_conn()?;
+/// let _: () = con.set("my_key", 42)?;
+/// pool.return_conn(con);
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RedisPool {
+    process: ProcessRef<PoolProcess>,
+    max_size: usize,
+    db: i64,
+    acquire_timeout: Option<Duration>,
+    /// The hook of the connection info, it stays in the process that has
+    /// this handle.
+    #[serde(skip)]
+    on_connect: Option<OnConnect>,
+}
+
+impl RedisPool {
+    /// Starts a pool process that opens at most `max_size` connections to
+    /// the server described by `connection_info`.
+    ///
+    /// No connection is opened until one is requested.  The `on_connect`
+    /// hook of `connection_info` runs for the connections taken by this
+    /// process, see `RedisPool::on_connect` for other processes.
+    pub fn new(connection_info: ConnectionInfo, max_size: usize) -> RedisResult<RedisPool> {
+        if max_size == 0 {
+            fail!((
+                ErrorKind::InvalidClientConfig,
+                "The pool size must be greater than zero"
+            ));
+        }
+        let db = connection_info.redis.db;
+        let on_connect = connection_info.redis.on_connect;
+        Ok(RedisPool {
+            process: PoolProcess::start((connection_info, max_size), None),
+            max_size,
+            db,
+            acquire_timeout: None,
+            on_connect,
+        })
+    }
+
+    /// Sets a hook that runs on every connection this handle opens or
+    /// reconnects, see `Client::on_connect`.
+    ///
+    /// The hook is not sent along with the pool, so a process that got the
+    /// pool from another one has to set it again.
+    pub fn on_connect(mut self, hook: fn(&mut Connection) -> RedisResult<()>) -> RedisPool {
+        self.on_connect = Some(OnConnect(hook));
+        self
+    }
+
+    /// Sets how long `get_conn` waits for a connection when all of them are
+    /// in use.  With `None`, the default, it waits until one is returned.
+    pub fn acquire_timeout(mut self, timeout: Option<Duration>) -> RedisPool {
+        self.acquire_timeout = timeout;
+        self
+    }
+
+    /// Closes connections that stayed idle in the pool for longer than
+    /// `max_idle`.  With `None`, the default, idle connections are kept.
+    ///
+    /// Set it below the `timeout` of the server, so that the pool does not
+    /// hand out connections the server already closed.  The pool process
+    /// checks its idle connections every half of `max_idle`, also while no
+    /// connection is requested.  Unlike the other settings this applies to
+    /// every handle of the pool.
+    pub fn max_idle(self, max_idle: Option<Duration>) -> RedisPool {
+        self.process.set_max_idle(max_idle);
+        self
+    }
+
+    /// Returns the maximum number of connections the pool opens.
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Takes a connection out of the pool, opening a new one if none is
+    /// idle and the pool is not full.
+    ///
+    /// If all connections are in use the caller is queued, and gets the
+    /// next connection that is returned once everyone queued before it got
+    /// one.  It fails with a timeout error once the acquire timeout has
+    /// passed.
+    pub fn get_conn(&self) -> RedisResult<Connection> {
+        let tag = Tag::new();
+        let checkout = match self.process.checkout((Process::this(), tag)) {
+            Checkout::Queued => self.wait(tag)?,
+            checkout => checkout,
+        };
+        match checkout {
+            Checkout::Idle(con) => {
+                let mut con = con.with_parser();
+                con.set_on_connect(self.on_connect);
+                Ok(con)
+            }
+            Checkout::Open(mut connection_info) => {
+                connection_info.redis.on_connect = self.on_connect;
+                self.open(&connection_info)
+            }
+            Checkout::Queued => unreachable!("the pool answers a waiter with a connection"),
+        }
+    }
+
+    /// Opens a new connection in the slot the pool gave us.
+    fn open(&self, connection_info: &ConnectionInfo) -> RedisResult<Connection> {
+        match connect(connection_info, None) {
+            Ok(con) => Ok(con),
+            Err(err) => {
+                // Give the slot back, the connection was never made.
+                self.process.release(host::process_id());
+                Err(err)
+            }
+        }
+    }
+
+    /// Waits for the answer of the pool process to the checkout tagged with
+    /// `tag`.
+    fn wait(&self, tag: Tag) -> RedisResult<Checkout> {
+        // The answer is sent as a tagged message, so only it is taken out of
+        // the mailbox of the calling process.
+        let mailbox: Mailbox<Checkout> = unsafe { Mailbox::new() };
+        let timeout = match self.acquire_timeout {
+            Some(timeout) => timeout,
+            None => return Ok(mailbox.tag_receive(&[tag])),
+        };
+        match mailbox.tag_receive_timeout(&[tag], timeout) {
+            MailboxResult::Message(checkout) => Ok(checkout),
+            _ if self.process.cancel((host::process_id(), tag)) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "timed out waiting for a pooled connection",
+            )
+            .into()),
+            // The pool handed us a connection just as the timeout passed.
+            _ => Ok(mailbox.tag_receive(&[tag])),
+        }
+    }
+
+    /// Gives a connection taken with `get_conn` back to the pool.
+    ///
+    /// The connection is checked with a `PING` first, and switched back to
+    /// the database of the pool if `change_db` was used on it.  A connection
+    /// that was closed, e.g. because of an I/O error, or that was left
+    /// inside `MULTI` or subscribed to channels is dropped and makes room
+    /// for a new one.
+    pub fn return_conn(&self, mut con: Connection) {
+        if self.reset(&mut con) {
+            self.process.checkin((host::process_id(), con.strip()));
+        } else {
+            self.process.release(host::process_id());
+        }
+    }
+
+    /// Puts `con` back into the state of a new connection, returns false if
+    /// that is not possible.
+    fn reset(&self, con: &mut Connection) -> bool {
+        if !con.is_open() {
+            return false;
+        }
+        // Inside MULTI the PING is queued and a subscribed connection
+        // answers with a message, only a clean connection replies PONG.
+        match cmd("PING").query::<Value>(con) {
+            Ok(Value::Status(ref status)) if status == "PONG" => {}
+            _ => return false,
+        }
+        con.get_db() == self.db || con.change_db(self.db).is_ok()
+    }
+}
+
+/// The answer of the pool process to a checkout.
+#[derive(Serialize, Deserialize)]
+enum Checkout {
+    /// An idle connection.
+    Idle(StrippedConnection),
+    /// No connection is idle, but the caller may open a new one.
+    Open(ConnectionInfo),
+    /// All connections are in use, the answer is sent once one is free.
+    Queued,
+}
+
+/// The state of the process owning the pooled connections.
+struct PoolProcess {
+    this: ProcessRef<PoolProcess>,
+    connection_info: ConnectionInfo,
+    max_size: usize,
+    /// Connections that were opened and are not returned yet, or idle.
+    open: usize,
+    /// The idle connections and when they were returned, oldest first.
+    idle: Vec<(StrippedConnection, Instant)>,
+    max_idle: Option<Duration>,
+    /// Whether a `reap` message is scheduled.
+    reaping: bool,
+    /// The processes linked to the pool, by process id, with the tag of
+    /// the link and the number of connections they hold.
+    borrowers: HashMap<u64, (Tag, usize)>,
+    /// The processes waiting for a connection and the tags their answers
+    /// are sent with, in the order they asked.
+    waiters: VecDeque<(Process<Checkout>, Tag)>,
+}
+
+#[abstract_process]
+impl PoolProcess {
+    #[init]
+    fn init(this: ProcessRef<Self>, arg: (ConnectionInfo, usize)) -> Self {
+        // A borrower that dies must give its connections back instead of
+        // taking the pool down.
+        unsafe { host::api::process::die_when_link_dies(0) };
+        let (connection_info, max_size) = arg;
+        PoolProcess {
+            this,
+            connection_info,
+            max_size,
+            open: 0,
+            idle: Vec::new(),
+            max_idle: None,
+            reaping: false,
+            borrowers: HashMap::new(),
+            waiters: VecDeque::new(),
+        }
+    }
+
+    #[handle_request]
+    fn checkout(&mut self, arg: (Process<Checkout>, Tag)) -> Checkout {
+        let (borrower, tag) = arg;
+        self.link(borrower.id());
+        if self.waiters.is_empty() {
+            if let Some(checkout) = self.take() {
+                self.lend(borrower.id());
+                return checkout;
+            }
+        }
+        self.waiters.push_back((borrower, tag));
+        Checkout::Queued
+    }
+
+    #[handle_request]
+    fn cancel(&mut self, arg: (u64, Tag)) -> bool {
+        let (borrower, tag) = arg;
+        let len = self.waiters.len();
+        self.waiters
+            .retain(|(waiter, waiter_tag)| waiter.id() != borrower || *waiter_tag != tag);
+        let queued = self.waiters.len() < len;
+        self.unlink_if_done(borrower);
+        queued
+    }
+
+    #[handle_message]
+    fn checkin(&mut self, arg: (u64, StrippedConnection)) {
+        let (borrower, con) = arg;
+        self.give_back(borrower);
+        self.idle.push((con, Instant::now()));
+        self.serve();
+    }
+
+    #[handle_message]
+    fn set_max_idle(&mut self, max_idle: Option<Duration>) {
+        self.max_idle = max_idle;
+        if max_idle.is_some() && !self.reaping {
+            self.reaping = true;
+            self.schedule_reap();
+        }
+    }
+
+    /// Closes the connections that are idle for longer than `max_idle`.
+    #[handle_message]
+    fn reap(&mut self) {
+        let max_idle = match self.max_idle {
+            Some(max_idle) => max_idle,
+            None => {
+                self.reaping = false;
+                return;
+            }
+        };
+        let before = self.idle.len();
+        self.idle.retain(|(_, since)| since.elapsed() <= max_idle);
+        self.open = self.open.saturating_sub(before - self.idle.len());
+        self.serve();
+        self.schedule_reap();
+    }
+
+    #[handle_message]
+    fn release(&mut self, borrower: u64) {
+        self.give_back(borrower);
+        self.open = self.open.saturating_sub(1);
+        self.serve();
+    }
+
+    #[handle_link_trapped]
+    fn handle_link_trapped(&mut self, tag: Tag) {
+        let borrower = self
+            .borrowers
+            .iter()
+            .find(|(_, (link, _))| *link == tag)
+            .map(|(borrower, _)| *borrower);
+        if let Some(borrower) = borrower {
+            // The connections died with the process that held them.
+            let (_, held) = self.borrowers.remove(&borrower).unwrap();
+            self.open = self.open.saturating_sub(held);
+            self.waiters.retain(|(waiter, _)| waiter.id() != borrower);
+            self.serve();
+        }
+    }
+}
+
+impl PoolProcess {
+    /// Wakes the process up to reap idle connections after half of
+    /// `max_idle`.
+    fn schedule_reap(&self) {
+        if let Some(max_idle) = self.max_idle {
+            let interval = (max_idle / 2).max(Duration::from_millis(1));
+            self.this.after(interval).reap();
+        }
+    }
+
+    /// Takes an idle connection, or a slot for a new one.
+    fn take(&mut self) -> Option<Checkout> {
+        if let Some((con, _)) = self.idle.pop() {
+            Some(Checkout::Idle(con))
+        } else if self.open < self.max_size {
+            self.open += 1;
+            Some(Checkout::Open(self.connection_info.clone()))
+        } else {
+            None
+        }
+    }
+
+    /// Answers waiters while there are connections to hand out.
+    fn serve(&mut self) {
+        while !self.waiters.is_empty() {
+            let checkout = match self.take() {
+                Some(checkout) => checkout,
+                None => break,
+            };
+            let (waiter, tag) = self.waiters.pop_front().unwrap();
+            self.lend(waiter.id());
+            waiter.tag_send(tag, checkout);
+        }
+    }
+
+    /// Links the pool process to `borrower` unless it already is.
+    fn link(&mut self, borrower: u64) {
+        if let Entry::Vacant(entry) = self.borrowers.entry(borrower) {
+            let tag = Tag::new();
+            unsafe { host::api::process::link(tag.id(), borrower) };
+            entry.insert((tag, 0));
+        }
+    }
+
+    fn lend(&mut self, borrower: u64) {
+        if let Some((_, held)) = self.borrowers.get_mut(&borrower) {
+            *held += 1;
+        }
+    }
+
+    fn give_back(&mut self, borrower: u64) {
+        if let Some((_, held)) = self.borrowers.get_mut(&borrower) {
+            *held = held.saturating_sub(1);
+        }
+        self.unlink_if_done(borrower);
+    }
+
+    /// Unlinks `borrower` once it holds no connection and does not wait for
+    /// one.
+    fn unlink_if_done(&mut self, borrower: u64) {
+        let done = matches!(self.borrowers.get(&borrower), Some((_, 0)))
+            && self
+                .waiters
+                .iter()
+                .all(|(waiter, _)| waiter.id() != borrower);
+        if done {
+            self.borrowers.remove(&borrower);
+            unsafe { host::api::process::unlink(borrower) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+    use std::time::Duration;
+
+    use super::RedisPool;
+    use crate::fake_server::{expect_sent, FakeServer};
+    use crate::{cmd, Connection, ConnectionLike, ErrorKind, IntoConnectionInfo, RedisResult};
+
+    #[test]
+    fn test_rejects_empty_pool() {
+        let info = "redis://127.0.0.1/".into_connection_info().unwrap();
+        let err = RedisPool::new(info, 0).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidClientConfig);
+    }
+
+    #[test]
+    fn test_failed_connect_frees_slot() {
+        // Nothing listens there anymore.
+        let info = FakeServer::bind().info();
+        let pool = RedisPool::new(info, 1).unwrap();
+        // Both attempts try to connect; had the first kept its slot the
+        // second would wait forever.
+        for _ in 0..2 {
+            let err = pool.get_conn().err().unwrap();
+            assert!(err.is_io_error(), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_waiter_gets_returned_connection() {
+        let server = FakeServer::bind();
+        let pool = RedisPool::new(server.info(), 1).unwrap();
+
+        let con = pool.get_conn().unwrap();
+        let stream = server.accept(b"");
+        // The waiter gets the same connection, no second one is opened.
+        let stream = lunatic::spawn_link!(@task |stream| {
+            let mut stream = stream;
+            expect_sent(&mut stream, &cmd("PING").get_packed_command());
+            stream.write_all(b"+PONG\r\n").unwrap();
+            expect_sent(&mut stream, &cmd("GET").arg("key").get_packed_command());
+            stream.write_all(b"$5\r\nvalue\r\n").unwrap();
+            stream
+        });
+        let waiter = pool.clone();
+        let waiter = lunatic::spawn_link!(@task |waiter| {
+            let mut con = waiter.get_conn().unwrap();
+            cmd("GET").arg("key").query::<String>(&mut con).unwrap()
+        });
+        // Give the waiter time to queue up behind the connection in use.
+        lunatic::sleep(Duration::from_millis(100));
+
+        pool.return_conn(con);
+        assert_eq!(waiter.result(), "value");
+        // Both commands were answered over the one connection.
+        let _stream = stream.result();
+    }
+
+    #[test]
+    fn test_on_connect_set_again_in_other_process() {
+        fn set_name(con: &mut Connection) -> RedisResult<()> {
+            cmd("CLIENT").arg("SETNAME").arg("pooled").query(con)
+        }
+
+        let server = FakeServer::bind();
+        let pool = RedisPool::new(server.info(), 1).unwrap();
+
+        // The hook is not sent along with the pool, the borrower sets it.
+        let borrower = lunatic::spawn_link!(@task |pool| {
+            let pool = pool.on_connect(set_name);
+            pool.get_conn().unwrap().is_open()
+        });
+        let mut stream = server.accept(b"+OK\r\n");
+        let expected = cmd("CLIENT")
+            .arg("SETNAME")
+            .arg("pooled")
+            .get_packed_command();
+        expect_sent(&mut stream, &expected);
+        assert!(borrower.result());
+    }
+
+    #[test]
+    fn test_crashed_borrower_frees_slot() {
+        let server = FakeServer::bind();
+        let pool = RedisPool::new(server.info(), 1)
+            .unwrap()
+            .acquire_timeout(Some(Duration::from_secs(5)));
+
+        let borrower = pool.clone();
+        lunatic::spawn!(|borrower| {
+            let _con = borrower.get_conn().unwrap();
+            panic!("the borrower crashed");
+        });
+        // Once the borrower is connected it holds the only slot.
+        let _ = server.accept(b"");
+
+        let con = pool.get_conn().unwrap();
+        let _ = server.accept(b"");
+        assert!(con.is_open());
+    }
+
+    #[test]
+    fn test_closes_idle_connections() {
+        let server = FakeServer::bind();
+        let pool = RedisPool::new(server.info(), 1)
+            .unwrap()
+            .max_idle(Some(Duration::from_millis(50)));
+
+        let con = pool.get_conn().unwrap();
+        let mut stream = server.accept(b"+PONG\r\n");
+        pool.return_conn(con);
+        expect_sent(&mut stream, &cmd("PING").get_packed_command());
+
+        // The pool closes the connection on its own, nothing asks for it.
+        lunatic::sleep(Duration::from_millis(200));
+        stream
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        assert_eq!(stream.read(&mut [0; 1]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_return_resets_connection() {
+        let server = FakeServer::bind();
+        let pool = RedisPool::new(server.info(), 1).unwrap();
+
+        // A connection switched to another database is switched back.
+        let mut con = pool.get_conn().unwrap();
+        let mut stream = server.accept(b"+OK\r\n+PONG\r\n+OK\r\n");
+        con.change_db(2).unwrap();
+        pool.return_conn(con);
+        expect_sent(&mut stream, &cmd("SELECT").arg(2).get_packed_command());
+        expect_sent(&mut stream, &cmd("PING").get_packed_command());
+        expect_sent(&mut stream, &cmd("SELECT").arg(0).get_packed_command());
+
+        // A connection left inside MULTI is dropped and a new one opened.
+        let mut con = pool.get_conn().unwrap();
+        assert_eq!(con.get_db(), 0);
+        stream.write_all(b"+OK\r\n+QUEUED\r\n").unwrap();
+        cmd("MULTI").query::<()>(&mut con).unwrap();
+        pool.return_conn(con);
+        expect_sent(&mut stream, &cmd("MULTI").get_packed_command());
+        expect_sent(&mut stream, &cmd("PING").get_packed_command());
+
+        let _con = pool.get_conn().unwrap();
+        let _ = server.accept(b"");
+    }
+}
@@ -1,9 +1,18 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
 use crate::{cmd::cmd, connection::Confirmation};
 use lunatic::{abstract_process, net::TcpStream, process::ProcessRef};
 use serde::{Deserialize, Serialize};
 
 use crate::{from_redis_value, Connection, ErrorKind, Msg, RedisError, RedisResult, ToRedisArgs};
 
+/// How often `receive()` tries to reconnect after the connection dropped.
+const RECONNECT_ATTEMPTS: u32 = 5;
+/// The pause before the second reconnect attempt, doubled for every
+/// further one.
+const RECONNECT_BACKOFF: Duration = Duration::from_millis(100);
+
 /// RedisPubSub allows one to use a connection for pub-sub to publish or subscribe to certain
 /// topics and/or patterns.
 #[derive(Clone, Deserialize, Serialize)]
@@ -12,6 +21,8 @@ pub struct RedisPubSub {
     // are used for restarting connection if redis server resets connection
     subscribed_topics: Vec<String>,
     subscribed_patterns: Vec<String>,
+    // messages that arrived while waiting for a subscribe confirmation
+    pending: VecDeque<Msg>,
 }
 
 #[abstract_process]
@@ -28,43 +39,64 @@ impl RedisPubSub {
             connection,
             subscribed_topics: vec![],
             subscribed_patterns: vec![],
+            pending: VecDeque::new(),
         }
     }
 
     /// Subscribe to a topic. Now the `receive()` function will get messages
     /// on this new topic
-    pub fn subscribe<T>(&mut self, topic: T) -> RedisResult<()>
+    ///
+    /// This waits for the server to confirm the subscription, so once it
+    /// returns any message published to the topic will be received.  The
+    /// server reported number of subscriptions of this connection (topics
+    /// and patterns) is returned.
+    pub fn subscribe<T>(&mut self, topic: T) -> RedisResult<usize>
     where
         T: ToRedisArgs + ToString,
     {
         let s = topic.to_string();
-        match cmd("SUBSCRIBE")
-            .arg(topic)
-            .query::<()>(&mut self.connection)
-        {
-            Err(e) => Err(e),
-            Ok(_) => {
-                self.subscribed_topics.push(s);
-                Ok(())
-            }
-        }
+        let packed = cmd("SUBSCRIBE").arg(topic).get_packed_command();
+        self.connection.con.send_bytes(&packed)?;
+        let count = self.wait_for_confirmation(Confirmation::Topic(s.clone()))?;
+        self.subscribed_topics.push(s);
+        Ok(count)
     }
 
     /// Subscribe to topics of a certain pattern. Now the `receive()` function
     /// will get messages on topics that match this new pattern
-    pub fn psubscribe<T>(&mut self, pattern: T) -> RedisResult<()>
+    ///
+    /// Like `subscribe()` this only returns once the server has confirmed
+    /// the subscription, with the number of subscriptions of this connection.
+    pub fn psubscribe<T>(&mut self, pattern: T) -> RedisResult<usize>
     where
         T: ToRedisArgs + ToString,
     {
         let s = pattern.to_string();
-        match cmd("PSUBSCRIBE")
-            .arg(pattern)
-            .query::<()>(&mut self.connection)
-        {
-            Err(e) => Err(e),
-            Ok(_) => {
-                self.subscribed_patterns.push(s);
-                Ok(())
+        let packed = cmd("PSUBSCRIBE").arg(pattern).get_packed_command();
+        self.connection.con.send_bytes(&packed)?;
+        let count = self.wait_for_confirmation(Confirmation::Pattern(s.clone()))?;
+        self.subscribed_patterns.push(s);
+        Ok(count)
+    }
+
+    /// Reads replies until `expected` is confirmed and returns the
+    /// subscription count it reports. Messages on existing subscriptions that
+    /// arrive in the meantime are queued for `receive()`.
+    fn wait_for_confirmation(&mut self, expected: Confirmation) -> RedisResult<usize> {
+        loop {
+            let polled = self.connection.recv_response::<TcpStream>()?;
+            match Confirmation::check_confirmation(&polled) {
+                Some(confirmation) if confirmation == expected => {
+                    // ("subscribe" or "psubscribe", name, count of subscriptions)
+                    let (_, _, count): (Vec<u8>, (), usize) = from_redis_value(&polled)?;
+                    return Ok(count);
+                }
+                Some(_) => continue,
+                None => {
+                    if let Some(msg) = Msg::from_value(&polled) {
+                        self.pending.push_back(msg);
+                    }
+                }
             }
         }
     }
@@ -145,8 +177,13 @@ impl RedisPubSub {
         let mut received_unsub = false;
         let mut received_punsub = false;
         loop {
-            let res: (Vec<u8>, (), isize) =
-                from_redis_value(&connection.recv_response::<TcpStream>()?)?;
+            let reply = connection.recv_response::<TcpStream>()?;
+            // Messages published before the server handled the unsubscribe
+            // commands are dropped, nobody is going to receive them.
+            if Msg::from_value(&reply).is_some() {
+                continue;
+            }
+            let res: (Vec<u8>, (), isize) = from_redis_value(&reply)?;
 
             match res.0.first() {
                 Some(&b'u') => received_unsub = true,
@@ -161,12 +198,114 @@ impl RedisPubSub {
 
         // Finally, the connection is back in its normal state since all subscriptions were
         // cancelled *and* all unsubscribe messages were received.
+        self.subscribed_topics.clear();
+        self.subscribed_patterns.clear();
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// receive exactly `n` messages from the subscribed topics or patterns and
+    /// then unsubscribe from everything.
+    ///
+    /// Once this returns successfully the connection no longer has any active
+    /// subscriptions and can be reused, e.g. by calling `exit_pubsub()`.
+    pub fn receive_n(&mut self, n: usize) -> RedisResult<Vec<Msg>> {
+        let mut msgs = Vec::with_capacity(n);
+        while msgs.len() < n {
+            msgs.push(self.receive()?);
+        }
+        self.clear_active_subscriptions()?;
+        Ok(msgs)
+    }
+
+    /// Opens a new connection and subscribes it to all topics and patterns
+    /// again, retrying with an exponential backoff.
+    ///
+    /// The error of the last attempt is returned if the server can't be
+    /// reached after `RECONNECT_ATTEMPTS` attempts.
+    fn reconnect(&mut self) -> RedisResult<()> {
+        let mut backoff = RECONNECT_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            match self.connection.reconnect().and_then(|_| self.resubscribe()) {
+                Ok(()) => return Ok(()),
+                Err(err) if attempt >= RECONNECT_ATTEMPTS => return Err(err),
+                Err(_) => {
+                    lunatic::sleep(backoff);
+                    backoff *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Replays the subscriptions on a fresh connection.
+    fn resubscribe(&mut self) -> RedisResult<()> {
+        for topic in self.subscribed_topics.clone() {
+            let packed = cmd("SUBSCRIBE").arg(&topic).get_packed_command();
+            self.connection.con.send_bytes(&packed)?;
+            self.wait_for_confirmation(Confirmation::Topic(topic))?;
+        }
+        for pattern in self.subscribed_patterns.clone() {
+            let packed = cmd("PSUBSCRIBE").arg(&pattern).get_packed_command();
+            self.connection.con.send_bytes(&packed)?;
+            self.wait_for_confirmation(Confirmation::Pattern(pattern))?;
+        }
         Ok(())
     }
 
     #[handle_request]
     /// receive messages from any of the subscribed topics or patterns
+    ///
+    /// If the server closes the connection, e.g. because it restarted, a new
+    /// connection is made and all subscriptions are restored before waiting
+    /// for the next message.  Messages published in the meantime are lost.
+    /// An error is only returned once reconnecting failed a few times.  A
+    /// read timeout is not treated as a dropped connection and is returned
+    /// as is.
     pub fn receive(&mut self) -> RedisResult<Msg> {
+        loop {
+            match self.receive_once() {
+                Err(err) if err.is_connection_closed() => self.reconnect()?,
+                result => return result,
+            }
+        }
+    }
+
+    #[handle_request]
+    /// Like `receive()`, but gives up after waiting `dur` for a message and
+    /// returns `Ok(None)` then.
+    ///
+    /// This lets a subscriber wake up regularly, e.g. to check its mailbox.
+    /// The read timeout of the connection is restored afterwards.  It is an
+    /// error to pass a zero `Duration`.
+    pub fn receive_timeout(&mut self, dur: Duration) -> RedisResult<Option<Msg>> {
+        let previous = self.connection.read_timeout();
+        let result = self.receive_within(dur);
+        // Restored on every path, a failed reconnect leaves the old
+        // connection in place.
+        let restored = self.connection.con.set_read_timeout(previous);
+        match result {
+            Ok(msg) => restored.map(|_| Some(msg)),
+            Err(err) if err.is_timeout() => restored.map(|_| None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn receive_within(&mut self, dur: Duration) -> RedisResult<Msg> {
+        loop {
+            self.connection.con.set_read_timeout(Some(dur))?;
+            match self.receive_once() {
+                Err(err) if err.is_connection_closed() => self.reconnect()?,
+                result => return result,
+            }
+        }
+    }
+
+    fn receive_once(&mut self) -> RedisResult<Msg> {
+        if let Some(msg) = self.pending.pop_front() {
+            return Ok(msg);
+        }
         let next = loop {
             let polled = self.connection.recv_response::<TcpStream>()?;
             match Confirmation::check_confirmation(&polled) {
@@ -188,3 +327,56 @@ impl RedisPubSub {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::RedisPubSub;
+    use crate::connection::connect;
+    use crate::fake_server::{fake_server, FakeServer};
+
+    #[test]
+    fn test_receive_n_skips_late_messages() {
+        // The second message was published before the server saw the
+        // unsubscribe commands and arrives ahead of their confirmations.
+        let (con, _server) = fake_server(
+            b"*3\r\n$7\r\nmessage\r\n$2\r\nch\r\n$1\r\na\r\n\
+              *3\r\n$7\r\nmessage\r\n$2\r\nch\r\n$1\r\nb\r\n\
+              *3\r\n$11\r\nunsubscribe\r\n$2\r\nch\r\n:0\r\n\
+              *3\r\n$12\r\npunsubscribe\r\n$-1\r\n:0\r\n",
+        );
+        let mut pubsub = RedisPubSub::new(con);
+        let msgs = pubsub.receive_n(1).unwrap();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].get_payload_bytes(), b"a");
+    }
+
+    #[test]
+    fn test_receive_timeout_restores_read_timeout() {
+        let server = FakeServer::bind();
+        let mut con = connect(&server.info(), None).unwrap();
+        con.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut pubsub = RedisPubSub::new(con);
+        let stream = server.accept(b"");
+
+        let msg = pubsub.receive_timeout(Duration::from_millis(50)).unwrap();
+        assert!(msg.is_none());
+        assert_eq!(
+            pubsub.connection.con.read_timeout(),
+            Some(Duration::from_secs(5))
+        );
+
+        // The server goes away for good, so every reconnect fails.
+        drop(stream);
+        drop(server);
+        let err = pubsub
+            .receive_timeout(Duration::from_millis(50))
+            .unwrap_err();
+        assert!(err.is_io_error(), "{}", err);
+        assert_eq!(
+            pubsub.connection.con.read_timeout(),
+            Some(Duration::from_secs(5))
+        );
+    }
+}
@@ -0,0 +1,276 @@
+//! A reliable work queue on top of redis lists.
+//!
+//! Items are pushed to a pending list.  A worker takes an item by atomically
+//! moving it to a processing list and removes it from there once the work is
+//! done.  If the worker dies before acknowledging, the item stays in the
+//! processing list and can be put back with [`ReliableQueue::recover`].
+
+use serde::{Deserialize, Serialize};
+
+use crate::cmd::cmd;
+use crate::commands::{Commands, Direction};
+use crate::connection::ConnectionLike;
+use crate::types::{FromRedisValue, RedisResult, ToRedisArgs};
+
+/// Moves `ARGV[1]` from the processing list `KEYS[2]` to the head of the
+/// pending list `KEYS[1]`, only if it was in the processing list.
+const REQUEUE_SCRIPT: &str = r"
+if redis.call('LREM', KEYS[2], 1, ARGV[1]) == 0 then
+    return 0
+end
+redis.call('RPUSH', KEYS[1], ARGV[1])
+return 1
+";
+
+/// The reliable-queue pattern: `LPUSH` to enqueue, `BLMOVE` to a processing
+/// list to dequeue and `LREM` to acknowledge.
+///
+/// The queue only holds the names of its two lists, so it is cheap to clone
+/// and can be sent to other lunatic processes.
+///
+/// ```rust,no_run
+/// use redis::queue::ReliableQueue;
+/// # fn do_something(con: &mut redis::Connection) -> redis::RedisResult<()> {
+/// let queue = ReliableQueue::new("jobs");
+/// queue.enqueue(con, "resize:42")?;
+///
+/// if let Some(job) = queue.dequeue::<String, _>(con, 5)? {
+///     // ... do the work, then remove the job from the processing list
+///     queue.ack(con, &job)?;
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReliableQueue {
+    pending: String,
+    processing: String,
+}
+
+impl ReliableQueue {
+    /// Creates a queue backed by the list `name` and the processing list
+    /// `name:processing`.
+    pub fn new(name: &str) -> ReliableQueue {
+        ReliableQueue::with_lists(name, &format!("{}:processing", name))
+    }
+
+    /// Creates a queue backed by the given pending and processing lists.
+    pub fn with_lists(pending: &str, processing: &str) -> ReliableQueue {
+        ReliableQueue {
+            pending: pending.to_string(),
+            processing: processing.to_string(),
+        }
+    }
+
+    /// Returns the name of the list holding items waiting for a worker.
+    pub fn pending_list(&self) -> &str {
+        &self.pending
+    }
+
+    /// Returns the name of the list holding items taken but not acknowledged.
+    pub fn processing_list(&self) -> &str {
+        &self.processing
+    }
+
+    /// Adds an item to the queue and returns the number of pending items.
+    pub fn enqueue<V: ToRedisArgs, C: ConnectionLike>(
+        &self,
+        con: &mut C,
+        item: V,
+    ) -> RedisResult<usize> {
+        con.lpush(&self.pending, item)
+    }
+
+    /// Takes the oldest pending item and moves it to the processing list,
+    /// blocking for up to `timeout` seconds (`0` blocks forever).
+    ///
+    /// Returns `None` if no item arrived in time.
+    pub fn dequeue<RV: FromRedisValue, C: ConnectionLike>(
+        &self,
+        con: &mut C,
+        timeout: usize,
+    ) -> RedisResult<Option<RV>> {
+        con.blmove(
+            &self.pending,
+            &self.processing,
+            Direction::Right,
+            Direction::Left,
+            timeout,
+        )
+    }
+
+    /// Marks an item as done by removing it from the processing list.
+    ///
+    /// Returns false if the item was not in the processing list, e.g.
+    /// because it was already acknowledged or recovered.
+    pub fn ack<V: ToRedisArgs, C: ConnectionLike>(
+        &self,
+        con: &mut C,
+        item: V,
+    ) -> RedisResult<bool> {
+        let removed: usize = con.lrem(&self.processing, 1, item)?;
+        Ok(removed > 0)
+    }
+
+    /// Gives an item back to the queue, e.g. after a failed attempt.
+    ///
+    /// The item is removed from the processing list and becomes the next
+    /// one to be dequeued, atomically, by a Lua script.  Returns false, and
+    /// leaves the queue untouched, if the item was not in the processing
+    /// list.
+    pub fn requeue<V: ToRedisArgs, C: ConnectionLike>(
+        &self,
+        con: &mut C,
+        item: V,
+    ) -> RedisResult<bool> {
+        cmd("EVAL")
+            .arg(REQUEUE_SCRIPT)
+            .arg(2)
+            .arg(&self.pending)
+            .arg(&self.processing)
+            .arg(item)
+            .query(con)
+    }
+
+    /// Returns the items that were taken but not acknowledged yet.
+    pub fn in_flight<RV: FromRedisValue, C: ConnectionLike>(
+        &self,
+        con: &mut C,
+    ) -> RedisResult<Vec<RV>> {
+        con.lrange(&self.processing, 0, -1)
+    }
+
+    /// Moves every unacknowledged item back to the queue, oldest first, and
+    /// returns how many were moved.
+    ///
+    /// Call this when the workers that took the items are known to be gone,
+    /// e.g. from the supervisor of a crashed lunatic process.
+    pub fn recover<C: ConnectionLike>(&self, con: &mut C) -> RedisResult<usize> {
+        let mut moved = 0;
+        loop {
+            let item: Option<Vec<u8>> = con.lmove(
+                &self.processing,
+                &self.pending,
+                Direction::Left,
+                Direction::Right,
+            )?;
+            match item {
+                Some(_) => moved += 1,
+                None => return Ok(moved),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lunatic::net::TcpStream;
+
+    use super::ReliableQueue;
+    use crate::cmd::{cmd, Cmd};
+    use crate::fake_server::{expect_sent, fake_server};
+
+    /// Asserts that the server received exactly `cmds`, in order.
+    fn assert_sent(server: &mut TcpStream, cmds: &[&Cmd]) {
+        let expected: Vec<u8> = cmds.iter().flat_map(|c| c.get_packed_command()).collect();
+        expect_sent(server, &expected);
+    }
+
+    #[lunatic::test]
+    fn test_enqueue_dequeue_ack() {
+        let (mut con, mut server) = fake_server(b":1\r\n$8\r\nresize:1\r\n:1\r\n:0\r\n");
+        let queue = ReliableQueue::new("jobs");
+
+        assert_eq!(queue.enqueue(&mut con, "resize:1"), Ok(1));
+        assert_eq!(
+            queue.dequeue::<String, _>(&mut con, 5),
+            Ok(Some("resize:1".to_string()))
+        );
+        assert_eq!(queue.ack(&mut con, "resize:1"), Ok(true));
+        assert_eq!(queue.ack(&mut con, "resize:1"), Ok(false));
+
+        assert_sent(
+            &mut server,
+            &[
+                cmd("LPUSH").arg("jobs").arg("resize:1"),
+                cmd("BLMOVE")
+                    .arg("jobs")
+                    .arg("jobs:processing")
+                    .arg("RIGHT")
+                    .arg("LEFT")
+                    .arg(5),
+                cmd("LREM").arg("jobs:processing").arg(1).arg("resize:1"),
+                cmd("LREM").arg("jobs:processing").arg(1).arg("resize:1"),
+            ],
+        );
+    }
+
+    #[lunatic::test]
+    fn test_dequeue_times_out() {
+        let (mut con, mut server) = fake_server(b"$-1\r\n");
+        let queue = ReliableQueue::with_lists("todo", "doing");
+
+        assert_eq!(queue.dequeue::<String, _>(&mut con, 1), Ok(None));
+        assert_sent(
+            &mut server,
+            &[cmd("BLMOVE")
+                .arg("todo")
+                .arg("doing")
+                .arg("RIGHT")
+                .arg("LEFT")
+                .arg(1)],
+        );
+    }
+
+    #[lunatic::test]
+    fn test_requeue_only_items_in_flight() {
+        let (mut con, mut server) = fake_server(b":1\r\n:0\r\n");
+        let queue = ReliableQueue::new("jobs");
+
+        assert_eq!(queue.requeue(&mut con, "a"), Ok(true));
+        assert_eq!(queue.requeue(&mut con, "b"), Ok(false));
+
+        let requeue = |item| {
+            let mut c = cmd("EVAL");
+            c.arg(super::REQUEUE_SCRIPT)
+                .arg(2)
+                .arg("jobs")
+                .arg("jobs:processing")
+                .arg(item);
+            c
+        };
+        assert_sent(&mut server, &[&requeue("a"), &requeue("b")]);
+    }
+
+    #[lunatic::test]
+    fn test_recover_after_crash() {
+        // A worker took two items and died: both are still in flight, and
+        // recovering moves them back until the processing list is empty.
+        let (mut con, mut server) = fake_server(
+            b"*2\r\n$1\r\na\r\n$1\r\nb\r\n\
+              $1\r\na\r\n$1\r\nb\r\n$-1\r\n",
+        );
+        let queue = ReliableQueue::new("jobs");
+
+        assert_eq!(
+            queue.in_flight::<String, _>(&mut con),
+            Ok(vec!["a".to_string(), "b".to_string()])
+        );
+        assert_eq!(queue.recover(&mut con), Ok(2));
+
+        let lmove = cmd("LMOVE")
+            .arg("jobs:processing")
+            .arg("jobs")
+            .arg("LEFT")
+            .arg("RIGHT")
+            .clone();
+        assert_sent(
+            &mut server,
+            &[
+                cmd("LRANGE").arg("jobs:processing").arg(0).arg(-1),
+                &lmove,
+                &lmove,
+                &lmove,
+            ],
+        );
+    }
+}
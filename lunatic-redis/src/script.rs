@@ -29,6 +29,25 @@ pub struct Script {
 /// let result = script.arg(1).arg(2).invoke(&mut con);
 /// assert_eq!(result, Ok(3));
 /// ```
+///
+/// # Return values
+///
+/// The value a script returns is converted by the server into a regular
+/// reply, which is then decoded into the type requested from `invoke`:
+///
+/// * a Lua number becomes `Value::Int`, fractions are truncated (return
+///   them as strings to keep them)
+/// * a Lua string becomes `Value::Data`
+/// * `true` becomes `Value::Int(1)`, `false` and `nil` become `Value::Nil`
+/// * a table becomes `Value::Bulk`, nested tables become nested arrays.  The
+///   array ends at the first `nil` in the table
+/// * `redis.status_reply("OK")` becomes `Value::Okay` and any other status
+///   becomes `Value::Status`; both decode into `String`
+/// * `redis.error_reply("CODE message")` makes `invoke` fail with the error,
+///   a custom code is available from `RedisError::code`.  An error nested
+///   inside a returned table fails the whole invocation the same way.
+///
+/// Decode into `Value` or `Vec<Value>` if the shape of the result varies.
 impl Script {
     /// Creates a new script object.
     pub fn new(code: &str) -> Script {
@@ -41,10 +60,23 @@ impl Script {
     }
 
     /// Returns the script's SHA1 hash in hexadecimal format.
+    ///
+    /// The server caches scripts by this hash, so scripts with the same
+    /// code share the cached copy, even across connections and processes.
     pub fn get_hash(&self) -> &str {
         &self.hash
     }
 
+    /// Loads the script into the server's script cache.
+    ///
+    /// Invoking a script sends only its hash and uploads the code on the
+    /// first `NOSCRIPT` error, so this is never required.  It saves that
+    /// extra round trip on the first invocation, e.g. for hot scripts
+    /// prepared when a connection is opened.
+    pub fn prepare(&self, con: &mut dyn ConnectionLike) -> RedisResult<()> {
+        self.prepare_invoke().load(con).map(|_| ())
+    }
+
     /// Creates a script invocation object with a key filled in.
     #[inline]
     pub fn key<T: ToRedisArgs>(&self, key: T) -> ScriptInvocation<'_> {
@@ -124,6 +156,9 @@ impl<'a> ScriptInvocation<'a> {
     }
 
     /// Invokes the script and returns the result.
+    ///
+    /// The script is called with `EVALSHA`.  If the server does not know it
+    /// yet, it is loaded with `SCRIPT LOAD` and called once more.
     #[inline]
     pub fn invoke<T: FromRedisValue>(&self, con: &mut dyn ConnectionLike) -> RedisResult<T> {
         let eval_cmd = self.eval_cmd();
@@ -131,7 +166,7 @@ impl<'a> ScriptInvocation<'a> {
             Ok(val) => Ok(val),
             Err(err) => {
                 if err.kind() == ErrorKind::NoScriptError {
-                    self.load_cmd().query(con)?;
+                    self.load_cmd().query::<()>(con)?;
                     eval_cmd.query(con)
                 } else {
                     Err(err)
@@ -165,3 +200,75 @@ impl<'a> ScriptInvocation<'a> {
         cmd
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Script;
+    use crate::fake_server::{expect_sent, fake_server};
+    use crate::types::{ErrorKind, Value};
+
+    #[test]
+    fn test_return_values() {
+        let scripts = [
+            Script::new("return 3.7"),
+            Script::new("return true"),
+            Script::new("return false"),
+            Script::new("return redis.status_reply('OK')"),
+            Script::new("return redis.status_reply('PONG')"),
+            Script::new("return redis.error_reply('MYERR boom')"),
+            Script::new("return {1, 'two', {3, 'four'}}"),
+            Script::new("return {1, redis.error_reply('ERR boom')}"),
+        ];
+        // What the server makes of each return value.
+        let (mut con, mut server) = fake_server(
+            b":3\r\n:1\r\n$-1\r\n+OK\r\n+PONG\r\n-MYERR boom\r\n\
+              *3\r\n:1\r\n$3\r\ntwo\r\n*2\r\n:3\r\n$4\r\nfour\r\n\
+              *2\r\n:1\r\n-ERR boom\r\n",
+        );
+
+        assert_eq!(scripts[0].invoke::<f64>(&mut con), Ok(3.0));
+        assert_eq!(scripts[1].invoke::<bool>(&mut con), Ok(true));
+        assert_eq!(scripts[2].invoke::<Option<i64>>(&mut con), Ok(None));
+        assert_eq!(scripts[3].invoke::<String>(&mut con), Ok("OK".into()));
+        assert_eq!(scripts[4].invoke::<String>(&mut con), Ok("PONG".into()));
+
+        let err = scripts[5].invoke::<Value>(&mut con).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ExtensionError);
+        assert_eq!(err.code(), Some("MYERR"));
+        assert_eq!(err.detail(), Some("boom"));
+
+        let table: (i64, String, (i64, String)) = scripts[6].invoke(&mut con).unwrap();
+        assert_eq!(table, (1, "two".into(), (3, "four".into())));
+
+        let err = scripts[7].invoke::<Vec<Value>>(&mut con).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ResponseError);
+
+        let expected: Vec<u8> = scripts
+            .iter()
+            .flat_map(|s| s.prepare_invoke().eval_cmd().get_packed_command())
+            .collect();
+        expect_sent(&mut server, &expected);
+    }
+
+    #[test]
+    fn test_load_on_noscript() {
+        let script = Script::new("return tonumber(ARGV[1]) + 1");
+        let replies = format!(
+            "-NOSCRIPT No matching script\r\n$40\r\n{}\r\n:2\r\n",
+            script.get_hash()
+        );
+        let (mut con, mut server) = fake_server(replies.as_bytes());
+        assert_eq!(script.arg(1).invoke::<i64>(&mut con), Ok(2));
+
+        let mut invocation = script.prepare_invoke();
+        invocation.arg(1);
+        let eval = invocation.eval_cmd().get_packed_command();
+        let expected = [
+            eval.clone(),
+            invocation.load_cmd().get_packed_command(),
+            eval,
+        ]
+        .concat();
+        expect_sent(&mut server, &expected);
+    }
+}
@@ -114,6 +114,49 @@ impl ToRedisArgs for StreamClaimOptions {
     }
 }
 
+/// Builder options for [`xautoclaim_options`] command.
+///
+/// [`xautoclaim_options`]: ../trait.Commands.html#method.xautoclaim_options
+///
+#[derive(Default, Debug, Deserialize, Serialize)]
+pub struct StreamAutoClaimOptions {
+    /// Set COUNT <count> cmd arg.
+    count: Option<usize>,
+    /// Set JUSTID cmd arg. Be advised: the claimed entries
+    /// are returned without their field/values with this option.
+    justid: bool,
+}
+
+impl StreamAutoClaimOptions {
+    /// Set COUNT <count> cmd arg.
+    pub fn count(mut self, n: usize) -> Self {
+        self.count = Some(n);
+        self
+    }
+
+    /// Set JUSTID cmd arg to true. Be advised: the claimed entries
+    /// are returned without their field/values with this option.
+    pub fn with_justid(mut self) -> Self {
+        self.justid = true;
+        self
+    }
+}
+
+impl ToRedisArgs for StreamAutoClaimOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        if let Some(ref n) = self.count {
+            out.write_arg(b"COUNT");
+            out.write_arg(format!("{}", n).as_bytes());
+        }
+        if self.justid {
+            out.write_arg(b"JUSTID");
+        }
+    }
+}
+
 /// Argument to `StreamReadOptions`
 /// Represents the Redis GROUP <groupname> <consumername> cmd arg.
 /// This option will toggle the cmd from XREAD to XREADGROUP
@@ -248,6 +291,26 @@ pub struct StreamClaimReply {
     pub ids: Vec<StreamId>,
 }
 
+/// Reply type used with [`xautoclaim`] and [`xautoclaim_options`] commands.
+///
+/// Represents the messages whose ownership was transferred, and the cursor
+/// to pass as `start` to continue scanning the pending entries list.
+///
+/// [`xautoclaim`]: ../trait.Commands.html#method.xautoclaim
+/// [`xautoclaim_options`]: ../trait.Commands.html#method.xautoclaim_options
+///
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct StreamAutoClaimReply {
+    /// The stream ID to use as `start` for the next call.  `0-0` once
+    /// the whole pending entries list was scanned.
+    pub next_stream_id: String,
+    /// The claimed messages.  With `JUSTID` only the `id` is filled in.
+    pub claimed: Vec<StreamId>,
+    /// IDs of pending messages that no longer exist in the stream and were
+    /// removed from the pending entries list (redis 7 and later).
+    pub deleted_ids: Vec<String>,
+}
+
 /// Reply type used with [`xpending`] command.
 ///
 /// Data returned here were fetched from the stream without
@@ -337,6 +400,12 @@ pub struct StreamInfoStreamReply {
     pub first_entry: StreamId,
     /// The very last entry in the stream.
     pub last_entry: StreamId,
+    /// The number of entries ever added to the stream, including deleted
+    /// ones. Only reported by Redis 7.0 and later.
+    pub entries_added: Option<usize>,
+    /// The largest ID that was deleted from the stream. Only reported by
+    /// Redis 7.0 and later.
+    pub max_deleted_entry_id: Option<String>,
 }
 
 /// Reply type used with [`xinfo_consumer`] command, an array of every
@@ -391,6 +460,13 @@ pub struct StreamInfoGroup {
     pub pending: usize,
     /// Last ID delivered to this group.
     pub last_delivered_id: String,
+    /// The logical read counter of the group, i.e. how many entries it
+    /// has been delivered. Only reported by Redis 7.0 and later.
+    pub entries_read: Option<usize>,
+    /// The number of entries in the stream still waiting to be delivered
+    /// to the group. `None` when Redis can't tell, e.g. after entries were
+    /// deleted, or on servers older than 7.0.
+    pub lag: Option<usize>,
 }
 
 /// Represents a pending message parsed from [`xpending`] methods.
@@ -512,6 +588,41 @@ impl FromRedisValue for StreamClaimReply {
     }
 }
 
+impl FromRedisValue for StreamAutoClaimReply {
+    fn from_redis_value(v: &Value) -> RedisResult<Self> {
+        // Redis 6.2 replies with two elements, redis 7 adds the deleted ids.
+        let items = match v {
+            Value::Bulk(items) if items.len() == 2 || items.len() == 3 => items,
+            _ => fail!((
+                crate::types::ErrorKind::TypeError,
+                "Cannot parse XAUTOCLAIM reply"
+            )),
+        };
+        let mut reply = StreamAutoClaimReply {
+            next_stream_id: from_redis_value(&items[0])?,
+            ..Default::default()
+        };
+        if let Value::Bulk(entries) = &items[1] {
+            for entry in entries {
+                match entry {
+                    // JUSTID only returns the ids
+                    Value::Data(_) | Value::Status(_) => reply.claimed.push(StreamId {
+                        id: from_redis_value(entry)?,
+                        ..Default::default()
+                    }),
+                    // entries that were deleted in the meantime are nil on redis 6.2
+                    Value::Nil => {}
+                    _ => reply.claimed.push(StreamId::from_bulk_value(entry)?),
+                }
+            }
+        }
+        if let Some(deleted) = items.get(2) {
+            reply.deleted_ids = from_redis_value(deleted)?;
+        }
+        Ok(reply)
+    }
+}
+
 type SPRInner = (
     usize,
     Option<String>,
@@ -609,7 +720,7 @@ impl FromRedisValue for StreamInfoStreamReply {
         if let Some(v) = &map.get("last-generated-id") {
             reply.last_generated_id = from_redis_value(v)?;
         }
-        if let Some(v) = &map.get("radix-tree-nodes") {
+        if let Some(v) = &map.get("radix-tree-keys") {
             reply.radix_tree_keys = from_redis_value(v)?;
         }
         if let Some(v) = &map.get("groups") {
@@ -624,6 +735,12 @@ impl FromRedisValue for StreamInfoStreamReply {
         if let Some(v) = &map.get("last-entry") {
             reply.last_entry = StreamId::from_bulk_value(v)?;
         }
+        if let Some(v) = &map.get("entries-added") {
+            reply.entries_added = from_redis_value(v)?;
+        }
+        if let Some(v) = &map.get("max-deleted-entry-id") {
+            reply.max_deleted_entry_id = from_redis_value(v)?;
+        }
         Ok(reply)
     }
 }
@@ -668,8 +785,275 @@ impl FromRedisValue for StreamInfoGroupsReply {
             if let Some(v) = &map.get("last-delivered-id") {
                 g.last_delivered_id = from_redis_value(v)?;
             }
+            if let Some(v) = &map.get("entries-read") {
+                g.entries_read = from_redis_value(v)?;
+            }
+            if let Some(v) = &map.get("lag") {
+                g.lag = from_redis_value(v)?;
+            }
             reply.groups.push(g);
         }
         Ok(reply)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        StreamAutoClaimReply, StreamInfoGroupsReply, StreamInfoStreamReply, StreamRangeReply,
+        StreamReadReply,
+    };
+    use crate::types::{from_redis_value, ErrorKind, Value};
+
+    fn data(s: &str) -> Value {
+        Value::Data(s.as_bytes().to_vec())
+    }
+
+    fn entry(id: &str, field: &str, value: &str) -> Value {
+        Value::Bulk(vec![data(id), Value::Bulk(vec![data(field), data(value)])])
+    }
+
+    #[test]
+    fn test_xinfo_stream_reply() {
+        // Captured from `XINFO STREAM` on Redis 7.0.
+        let reply = Value::Bulk(vec![
+            data("length"),
+            Value::Int(2),
+            data("radix-tree-keys"),
+            Value::Int(1),
+            data("radix-tree-nodes"),
+            Value::Int(2),
+            data("last-generated-id"),
+            data("1638125141232-0"),
+            data("max-deleted-entry-id"),
+            data("0-0"),
+            data("entries-added"),
+            Value::Int(2),
+            data("recorded-first-entry-id"),
+            data("1638125133432-0"),
+            data("groups"),
+            Value::Int(1),
+            data("first-entry"),
+            entry("1638125133432-0", "message", "apple"),
+            data("last-entry"),
+            entry("1638125141232-0", "message", "banana"),
+        ]);
+
+        let info: StreamInfoStreamReply = from_redis_value(&reply).unwrap();
+        assert_eq!(info.length, 2);
+        assert_eq!(info.radix_tree_keys, 1);
+        assert_eq!(info.last_generated_id, "1638125141232-0");
+        assert_eq!(info.groups, 1);
+        assert_eq!(info.entries_added, Some(2));
+        assert_eq!(info.max_deleted_entry_id.as_deref(), Some("0-0"));
+        assert_eq!(info.first_entry.id, "1638125133432-0");
+        assert_eq!(info.first_entry.get("message"), Some("apple".to_string()));
+        assert_eq!(info.last_entry.get("message"), Some("banana".to_string()));
+    }
+
+    #[test]
+    fn test_xinfo_stream_reply_before_redis_7() {
+        let reply = Value::Bulk(vec![
+            data("length"),
+            Value::Int(0),
+            data("last-generated-id"),
+            data("0-0"),
+            data("groups"),
+            Value::Int(0),
+            data("first-entry"),
+            Value::Nil,
+            data("last-entry"),
+            Value::Nil,
+        ]);
+
+        let info: StreamInfoStreamReply = from_redis_value(&reply).unwrap();
+        assert_eq!(info.length, 0);
+        assert_eq!(info.entries_added, None);
+        assert_eq!(info.max_deleted_entry_id, None);
+        assert!(info.first_entry.is_empty());
+    }
+
+    #[test]
+    fn test_xinfo_groups_reply() {
+        // Captured from `XINFO GROUPS` on Redis 7.0, over RESP2 and RESP3.
+        let resp2 = Value::Bulk(vec![
+            Value::Bulk(vec![
+                data("name"),
+                data("mygroup"),
+                data("consumers"),
+                Value::Int(2),
+                data("pending"),
+                Value::Int(2),
+                data("last-delivered-id"),
+                data("1638126030001-0"),
+                data("entries-read"),
+                Value::Int(2),
+                data("lag"),
+                Value::Int(0),
+            ]),
+            Value::Bulk(vec![
+                data("name"),
+                data("some-other-group"),
+                data("consumers"),
+                Value::Int(1),
+                data("pending"),
+                Value::Int(0),
+                data("last-delivered-id"),
+                data("1638126028070-0"),
+                data("entries-read"),
+                Value::Int(1),
+                data("lag"),
+                Value::Nil,
+            ]),
+        ]);
+        let resp3 = Value::Bulk(vec![
+            Value::Map(vec![
+                (data("name"), data("mygroup")),
+                (data("consumers"), Value::Int(2)),
+                (data("pending"), Value::Int(2)),
+                (data("last-delivered-id"), data("1638126030001-0")),
+                (data("entries-read"), Value::Int(2)),
+                (data("lag"), Value::Int(0)),
+            ]),
+            Value::Map(vec![
+                (data("name"), data("some-other-group")),
+                (data("consumers"), Value::Int(1)),
+                (data("pending"), Value::Int(0)),
+                (data("last-delivered-id"), data("1638126028070-0")),
+                (data("entries-read"), Value::Int(1)),
+                (data("lag"), Value::Nil),
+            ]),
+        ]);
+
+        for reply in [resp2, resp3] {
+            let info: StreamInfoGroupsReply = from_redis_value(&reply).unwrap();
+            assert_eq!(info.groups.len(), 2);
+
+            let group = &info.groups[0];
+            assert_eq!(group.name, "mygroup");
+            assert_eq!(group.consumers, 2);
+            assert_eq!(group.pending, 2);
+            assert_eq!(group.last_delivered_id, "1638126030001-0");
+            assert_eq!(group.entries_read, Some(2));
+            assert_eq!(group.lag, Some(0));
+
+            let group = &info.groups[1];
+            assert_eq!(group.name, "some-other-group");
+            assert_eq!(group.pending, 0);
+            assert_eq!(group.lag, None);
+        }
+    }
+
+    #[test]
+    fn test_xinfo_stream_reply_resp3() {
+        let reply = Value::Map(vec![
+            (data("length"), Value::Int(1)),
+            (data("last-generated-id"), data("1-0")),
+            (data("groups"), Value::Int(3)),
+            (data("entries-added"), Value::Int(5)),
+            (data("first-entry"), entry("1-0", "a", "b")),
+            (data("last-entry"), entry("1-0", "a", "b")),
+        ]);
+
+        let info: StreamInfoStreamReply = from_redis_value(&reply).unwrap();
+        assert_eq!(info.length, 1);
+        assert_eq!(info.groups, 3);
+        assert_eq!(info.entries_added, Some(5));
+        assert_eq!(info.last_entry.get("a"), Some("b".to_string()));
+    }
+
+    #[lunatic::test]
+    fn test_xautoclaim_reply_redis_6() {
+        // Redis 6.2 has no deleted ids and sends nil for deleted entries.
+        let reply = Value::Bulk(vec![
+            data("1000-2"),
+            Value::Bulk(vec![
+                entry("1000-0", "h", "w"),
+                Value::Nil,
+                entry("1000-1", "h", "x"),
+            ]),
+        ]);
+
+        let claim: StreamAutoClaimReply = from_redis_value(&reply).unwrap();
+        assert_eq!(claim.next_stream_id, "1000-2");
+        let ids: Vec<_> = claim.claimed.iter().map(|id| id.id.as_str()).collect();
+        assert_eq!(ids, vec!["1000-0", "1000-1"]);
+        assert_eq!(claim.claimed[1].get("h"), Some("x".to_string()));
+        assert!(claim.deleted_ids.is_empty());
+    }
+
+    #[lunatic::test]
+    fn test_xautoclaim_reply_redis_7() {
+        let reply = Value::Bulk(vec![
+            data("0-0"),
+            Value::Bulk(vec![entry("1000-0", "h", "w")]),
+            Value::Bulk(vec![data("999-0"), data("999-1")]),
+        ]);
+
+        let claim: StreamAutoClaimReply = from_redis_value(&reply).unwrap();
+        assert_eq!(claim.next_stream_id, "0-0");
+        assert_eq!(claim.claimed.len(), 1);
+        assert_eq!(claim.claimed[0].get("h"), Some("w".to_string()));
+        assert_eq!(claim.deleted_ids, vec!["999-0", "999-1"]);
+    }
+
+    #[lunatic::test]
+    fn test_xautoclaim_reply_justid() {
+        let reply = Value::Bulk(vec![
+            data("1000-2"),
+            Value::Bulk(vec![data("1000-0"), data("1000-1")]),
+            Value::Bulk(vec![]),
+        ]);
+
+        let claim: StreamAutoClaimReply = from_redis_value(&reply).unwrap();
+        let ids: Vec<_> = claim.claimed.iter().map(|id| id.id.as_str()).collect();
+        assert_eq!(ids, vec!["1000-0", "1000-1"]);
+        assert!(claim.claimed.iter().all(|id| id.is_empty()));
+        assert!(claim.deleted_ids.is_empty());
+
+        let err = from_redis_value::<StreamAutoClaimReply>(&Value::Bulk(vec![data("0-0")]));
+        assert_eq!(err.unwrap_err().kind(), ErrorKind::TypeError);
+    }
+
+    #[test]
+    fn test_xrange_reply() {
+        let reply = Value::Bulk(vec![
+            entry("1000-0", "hello", "world"),
+            entry("1000-1", "hello", "world2"),
+        ]);
+
+        let range: StreamRangeReply = from_redis_value(&reply).unwrap();
+        let ids: Vec<_> = range.ids.iter().map(|id| id.id.as_str()).collect();
+        assert_eq!(ids, vec!["1000-0", "1000-1"]);
+        assert_eq!(range.ids[1].get("hello"), Some("world2".to_string()));
+
+        let empty: StreamRangeReply = from_redis_value(&Value::Bulk(vec![])).unwrap();
+        assert!(empty.ids.is_empty());
+    }
+
+    #[test]
+    fn test_xread_reply() {
+        let reply = Value::Bulk(vec![
+            Value::Bulk(vec![
+                data("k1"),
+                Value::Bulk(vec![entry("1000-0", "a", "1"), entry("1000-1", "a", "2")]),
+            ]),
+            Value::Bulk(vec![
+                data("k2"),
+                Value::Bulk(vec![entry("2000-0", "b", "3")]),
+            ]),
+        ]);
+
+        let read: StreamReadReply = from_redis_value(&reply).unwrap();
+        assert_eq!(read.keys.len(), 2);
+        let k1 = read.keys.iter().find(|k| k.key == "k1").unwrap();
+        assert_eq!(k1.ids.len(), 2);
+        assert_eq!(k1.ids[1].get::<i32>("a"), Some(2));
+        let k2 = read.keys.iter().find(|k| k.key == "k2").unwrap();
+        assert_eq!(k2.ids[0].id, "2000-0");
+
+        // A blocking XREAD that timed out replies with nil.
+        let timed_out: Option<StreamReadReply> = from_redis_value(&Value::Nil).unwrap();
+        assert!(timed_out.is_none());
+    }
+}
@@ -4,8 +4,11 @@ use std::default::Default;
 use std::fmt;
 use std::hash::{BuildHasher, Hash};
 use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::str::{from_utf8, Utf8Error};
 use std::string::FromUtf8Error;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[cfg(feature = "ahash")]
 pub(crate) use ahash::{AHashMap as HashMap, AHashSet as HashSet};
@@ -44,6 +47,39 @@ pub enum Expiry {
     PERSIST,
 }
 
+/// Helper enum for the conditions of `EXPIRE` and `PEXPIRE` (Redis 7)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ExpireOption {
+    /// Set the timeout unconditionally, like a plain `EXPIRE`.
+    NONE,
+    /// NX -- Set the timeout only if the key has none.
+    NX,
+    /// XX -- Set the timeout only if the key already has one.
+    XX,
+    /// GT -- Set the timeout only if it is greater than the current one.
+    /// A key without a timeout counts as an infinite one.
+    GT,
+    /// LT -- Set the timeout only if it is less than the current one.
+    /// A key without a timeout counts as an infinite one.
+    LT,
+}
+
+impl ToRedisArgs for ExpireOption {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        let s: &[u8] = match self {
+            ExpireOption::NONE => return,
+            ExpireOption::NX => b"NX",
+            ExpireOption::XX => b"XX",
+            ExpireOption::GT => b"GT",
+            ExpireOption::LT => b"LT",
+        };
+        out.write_arg(s);
+    }
+}
+
 /// Helper enum that is used in some situations to describe
 /// the behavior of arguments in a numeric context.
 #[derive(PartialEq, Eq, Clone, Debug, Copy, Deserialize, Serialize)]
@@ -98,10 +134,15 @@ pub enum ErrorKind {
     ExtensionError,
     /// Attempt to write to a read-only server
     ReadOnly,
+    /// The reply could not be parsed because it violates the RESP framing.
+    ParseError,
 }
 
 /// Internal low-level redis value enum.
-#[derive(PartialEq, Eq, Clone, Deserialize, Serialize)]
+///
+/// The variants after `Okay` are only sent by servers that talk RESP3 (see
+/// `RedisConnectionInfo::protocol`).
+#[derive(Clone, Deserialize, Serialize)]
 pub enum Value {
     /// A nil response from the server.
     Nil,
@@ -119,20 +160,105 @@ pub enum Value {
     Status(String),
     /// A status response which represents the string "OK".
     Okay,
+    /// A floating point number.
+    ///
+    /// Doubles compare by their bits so that `Value` can be `Eq`: a `NaN`
+    /// equals itself, while `0.0` and `-0.0` are different values.
+    Double(f64),
+    /// A boolean.
+    Boolean(bool),
+    /// An integer that does not fit in 64 bits, in its decimal form.
+    BigNumber(String),
+    /// A string together with its three letter format, e.g. `txt` or `mkd`.
+    VerbatimString {
+        /// The format of the text.
+        format: String,
+        /// The text itself.
+        text: String,
+    },
+    /// An ordered list of key-value pairs.
+    Map(Vec<(Value, Value)>),
+    /// An unordered collection of distinct values.
+    Set(Vec<Value>),
+    /// Out-of-band data such as pubsub messages, which arrive without a
+    /// request.  The first item names the kind of push.
+    Push(Vec<Value>),
+    /// A reply with attribute metadata attached by the server, e.g. key
+    /// popularity for client-side caching.
+    ///
+    /// Conversions through `from_redis_value`, including the one into
+    /// `Value`, see the reply with the attributes removed at every level.
+    /// They are kept on the raw replies of `ConnectionLike::req_command` and
+    /// `Connection::recv_response`.
+    Attribute {
+        /// The reply the attributes are attached to.
+        data: Box<Value>,
+        /// The attributes as key-value pairs.
+        attributes: Vec<(Value, Value)>,
+    },
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) | (Value::Okay, Value::Okay) => true,
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::Data(a), Value::Data(b)) => a == b,
+            (Value::Status(a), Value::Status(b)) | (Value::BigNumber(a), Value::BigNumber(b)) => {
+                a == b
+            }
+            (Value::Double(a), Value::Double(b)) => a.to_bits() == b.to_bits(),
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (
+                Value::VerbatimString { format, text },
+                Value::VerbatimString {
+                    format: other_format,
+                    text: other_text,
+                },
+            ) => format == other_format && text == other_text,
+            (Value::Bulk(a), Value::Bulk(b))
+            | (Value::Set(a), Value::Set(b))
+            | (Value::Push(a), Value::Push(b)) => a == b,
+            (Value::Map(a), Value::Map(b)) => a == b,
+            (
+                Value::Attribute { data, attributes },
+                Value::Attribute {
+                    data: other_data,
+                    attributes: other_attributes,
+                },
+            ) => data == other_data && attributes == other_attributes,
+            _ => false,
+        }
+    }
 }
 
-pub struct MapIter<'a>(std::slice::Iter<'a, Value>);
+impl Eq for Value {}
+
+pub struct MapIter<'a>(MapIterInner<'a>);
+
+enum MapIterInner<'a> {
+    Flat(std::slice::Iter<'a, Value>),
+    Pairs(std::slice::Iter<'a, (Value, Value)>),
+}
 
 impl<'a> Iterator for MapIter<'a> {
     type Item = (&'a Value, &'a Value);
 
     fn next(&mut self) -> Option<Self::Item> {
-        Some((self.0.next()?, self.0.next()?))
+        match self.0 {
+            MapIterInner::Flat(ref mut iter) => Some((iter.next()?, iter.next()?)),
+            MapIterInner::Pairs(ref mut iter) => iter.next().map(|(k, v)| (k, v)),
+        }
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let (low, high) = self.0.size_hint();
-        (low / 2, high.map(|h| h / 2))
+        match self.0 {
+            MapIterInner::Flat(ref iter) => {
+                let (low, high) = iter.size_hint();
+                (low / 2, high.map(|h| h / 2))
+            }
+            MapIterInner::Pairs(ref iter) => iter.size_hint(),
+        }
     }
 }
 
@@ -175,19 +301,74 @@ impl Value {
     /// Returns an `&[Value]` if `self` is compatible with a sequence type
     pub fn as_sequence(&self) -> Option<&[Value]> {
         match self {
-            Value::Bulk(items) => Some(&items[..]),
+            Value::Bulk(items) | Value::Set(items) | Value::Push(items) => Some(&items[..]),
             Value::Nil => Some(&[]),
+            Value::Attribute { data, .. } => data.as_sequence(),
             _ => None,
         }
     }
 
     /// Returns an iterator of `(&Value, &Value)` if `self` is compatible with a map type
+    ///
+    /// Both RESP3 maps and the flat key-value arrays RESP2 uses for maps
+    /// are accepted.
     pub fn as_map_iter(&self) -> Option<MapIter<'_>> {
         match self {
-            Value::Bulk(items) => Some(MapIter(items.iter())),
+            Value::Bulk(items) => Some(MapIter(MapIterInner::Flat(items.iter()))),
+            Value::Map(items) => Some(MapIter(MapIterInner::Pairs(items.iter()))),
+            Value::Attribute { data, .. } => data.as_map_iter(),
+            _ => None,
+        }
+    }
+
+    /// Returns the attributes the server attached to this reply, if any.
+    pub fn attributes(&self) -> Option<&[(Value, Value)]> {
+        match self {
+            Value::Attribute { attributes, .. } => Some(&attributes[..]),
             _ => None,
         }
     }
+
+    /// Returns the reply with any attached attributes peeled off.
+    pub fn without_attributes(&self) -> &Value {
+        match self {
+            Value::Attribute { data, .. } => data.without_attributes(),
+            value => value,
+        }
+    }
+
+    /// Returns true if attributes are attached to this value or any value
+    /// nested in it.
+    fn has_attributes(&self) -> bool {
+        match self {
+            Value::Attribute { .. } => true,
+            Value::Bulk(items) | Value::Set(items) | Value::Push(items) => {
+                items.iter().any(Value::has_attributes)
+            }
+            Value::Map(pairs) => pairs
+                .iter()
+                .any(|(k, v)| k.has_attributes() || v.has_attributes()),
+            _ => false,
+        }
+    }
+
+    /// Returns a copy of the value with the attributes removed at every
+    /// level.
+    fn strip_attributes(&self) -> Value {
+        match self {
+            Value::Attribute { data, .. } => data.strip_attributes(),
+            Value::Bulk(items) => Value::Bulk(items.iter().map(Value::strip_attributes).collect()),
+            Value::Set(items) => Value::Set(items.iter().map(Value::strip_attributes).collect()),
+            Value::Push(items) => Value::Push(items.iter().map(Value::strip_attributes).collect()),
+            Value::Map(pairs) => Value::Map(
+                pairs
+                    .iter()
+                    .map(|(k, v)| (k.strip_attributes(), v.strip_attributes()))
+                    .collect(),
+            ),
+            value => value.clone(),
+        }
+    }
 }
 
 impl fmt::Debug for Value {
@@ -213,6 +394,29 @@ impl fmt::Debug for Value {
             }
             Value::Okay => write!(fmt, "ok"),
             Value::Status(ref s) => write!(fmt, "status({:?})", s),
+            Value::Double(val) => write!(fmt, "double({:?})", val),
+            Value::Boolean(val) => write!(fmt, "boolean({:?})", val),
+            Value::BigNumber(ref val) => write!(fmt, "big-number({})", val),
+            Value::VerbatimString {
+                ref format,
+                ref text,
+            } => write!(fmt, "verbatim-string({:?}, {:?})", format, text),
+            Value::Map(ref values) => {
+                write!(fmt, "map(")?;
+                for (i, (k, v)) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(fmt, ", ")?;
+                    }
+                    write!(fmt, "{:?}: {:?}", k, v)?;
+                }
+                write!(fmt, ")")
+            }
+            Value::Set(ref values) => write!(fmt, "set({:?})", values),
+            Value::Push(ref values) => write!(fmt, "push({:?})", values),
+            Value::Attribute {
+                ref data,
+                ref attributes,
+            } => write!(fmt, "attribute({:?}, {:?})", data, attributes),
         }
     }
 }
@@ -424,49 +628,143 @@ impl From<io::ErrorKind> for IoErrorKind {
             io::ErrorKind::PermissionDenied => IoErrorKind::PermissionDenied,
             io::ErrorKind::ConnectionRefused => IoErrorKind::ConnectionRefused,
             io::ErrorKind::ConnectionReset => IoErrorKind::ConnectionReset,
-            // io::ErrorKind::HostUnreachable => IoErrorKind::HostUnreachable,
-            // io::ErrorKind::NetworkUnreachable => IoErrorKind::NetworkUnreachable,
             io::ErrorKind::ConnectionAborted => IoErrorKind::ConnectionAborted,
             io::ErrorKind::NotConnected => IoErrorKind::NotConnected,
             io::ErrorKind::AddrInUse => IoErrorKind::AddrInUse,
             io::ErrorKind::AddrNotAvailable => IoErrorKind::AddrNotAvailable,
-            // io::ErrorKind::NetworkDown => IoErrorKind::NetworkDown,
             io::ErrorKind::BrokenPipe => IoErrorKind::BrokenPipe,
             io::ErrorKind::AlreadyExists => IoErrorKind::AlreadyExists,
             io::ErrorKind::WouldBlock => IoErrorKind::WouldBlock,
-            // io::ErrorKind::NotADirectory => IoErrorKind::NotADirectory,
-            // io::ErrorKind::IsADirectory => IoErrorKind::IsADirectory,
-            // io::ErrorKind::DirectoryNotEmpty => IoErrorKind::DirectoryNotEmpty,
-            // io::ErrorKind::ReadOnlyFilesystem => IoErrorKind::ReadOnlyFilesystem,
-            // io::ErrorKind::FilesystemLoop => IoErrorKind::FilesystemLoop,
-            // io::ErrorKind::StaleNetworkFileHandle => IoErrorKind::StaleNetworkFileHandle,
             io::ErrorKind::InvalidInput => IoErrorKind::InvalidInput,
             io::ErrorKind::InvalidData => IoErrorKind::InvalidData,
             io::ErrorKind::TimedOut => IoErrorKind::TimedOut,
             io::ErrorKind::WriteZero => IoErrorKind::WriteZero,
-            // io::ErrorKind::StorageFull => IoErrorKind::StorageFull,
-            // io::ErrorKind::NotSeekable => IoErrorKind::NotSeekable,
-            // io::ErrorKind::FilesystemQuotaExceeded => IoErrorKind::FilesystemQuotaExceeded,
-            // io::ErrorKind::FileTooLarge => IoErrorKind::FileTooLarge,
-            // io::ErrorKind::ResourceBusy => IoErrorKind::ResourceBusy,
-            // io::ErrorKind::ExecutableFileBusy => IoErrorKind::ExecutableFileBusy,
-            // io::ErrorKind::Deadlock => IoErrorKind::Deadlock,
-            // io::ErrorKind::CrossesDevices => IoErrorKind::CrossesDevices,
-            // io::ErrorKind::TooManyLinks => IoErrorKind::TooManyLinks,
-            // io::ErrorKind::InvalidFilename => IoErrorKind::InvalidFilename,
-            // io::ErrorKind::ArgumentListTooLong => IoErrorKind::ArgumentListTooLong,
             io::ErrorKind::Interrupted => IoErrorKind::Interrupted,
             io::ErrorKind::Unsupported => IoErrorKind::Unsupported,
             io::ErrorKind::UnexpectedEof => IoErrorKind::UnexpectedEof,
             io::ErrorKind::OutOfMemory => IoErrorKind::OutOfMemory,
+            io::ErrorKind::HostUnreachable => IoErrorKind::HostUnreachable,
+            io::ErrorKind::NetworkUnreachable => IoErrorKind::NetworkUnreachable,
+            io::ErrorKind::NetworkDown => IoErrorKind::NetworkDown,
+            io::ErrorKind::NotADirectory => IoErrorKind::NotADirectory,
+            io::ErrorKind::IsADirectory => IoErrorKind::IsADirectory,
+            io::ErrorKind::DirectoryNotEmpty => IoErrorKind::DirectoryNotEmpty,
+            io::ErrorKind::ReadOnlyFilesystem => IoErrorKind::ReadOnlyFilesystem,
+            io::ErrorKind::StaleNetworkFileHandle => IoErrorKind::StaleNetworkFileHandle,
+            io::ErrorKind::StorageFull => IoErrorKind::StorageFull,
+            io::ErrorKind::NotSeekable => IoErrorKind::NotSeekable,
+            io::ErrorKind::FileTooLarge => IoErrorKind::FileTooLarge,
+            io::ErrorKind::ResourceBusy => IoErrorKind::ResourceBusy,
+            io::ErrorKind::ExecutableFileBusy => IoErrorKind::ExecutableFileBusy,
+            io::ErrorKind::Deadlock => IoErrorKind::Deadlock,
+            io::ErrorKind::TooManyLinks => IoErrorKind::TooManyLinks,
+            io::ErrorKind::ArgumentListTooLong => IoErrorKind::ArgumentListTooLong,
             io::ErrorKind::Other => IoErrorKind::Other,
-            // io::ErrorKind::Uncategorized => IoErrorKind::Uncategorized,
-            _ => todo!(),
+            // `io::ErrorKind` is non-exhaustive, kinds without a stable name
+            // must not bring down the process.
+            _ => IoErrorKind::Uncategorized,
+        }
+    }
+}
+
+impl From<IoErrorKind> for io::ErrorKind {
+    /// `CrossesDevices`, `InvalidFilename`, `FilesystemQuotaExceeded`,
+    /// `FilesystemLoop` and `Uncategorized` are not stable in `io::ErrorKind`
+    /// and map to `io::ErrorKind::Other`.
+    fn from(kind: IoErrorKind) -> Self {
+        match kind {
+            IoErrorKind::NotFound => io::ErrorKind::NotFound,
+            IoErrorKind::PermissionDenied => io::ErrorKind::PermissionDenied,
+            IoErrorKind::ConnectionRefused => io::ErrorKind::ConnectionRefused,
+            IoErrorKind::ConnectionReset => io::ErrorKind::ConnectionReset,
+            IoErrorKind::ConnectionAborted => io::ErrorKind::ConnectionAborted,
+            IoErrorKind::NotConnected => io::ErrorKind::NotConnected,
+            IoErrorKind::AddrInUse => io::ErrorKind::AddrInUse,
+            IoErrorKind::AddrNotAvailable => io::ErrorKind::AddrNotAvailable,
+            IoErrorKind::BrokenPipe => io::ErrorKind::BrokenPipe,
+            IoErrorKind::AlreadyExists => io::ErrorKind::AlreadyExists,
+            IoErrorKind::WouldBlock => io::ErrorKind::WouldBlock,
+            IoErrorKind::InvalidInput => io::ErrorKind::InvalidInput,
+            IoErrorKind::InvalidData => io::ErrorKind::InvalidData,
+            IoErrorKind::TimedOut => io::ErrorKind::TimedOut,
+            IoErrorKind::WriteZero => io::ErrorKind::WriteZero,
+            IoErrorKind::Interrupted => io::ErrorKind::Interrupted,
+            IoErrorKind::Unsupported => io::ErrorKind::Unsupported,
+            IoErrorKind::UnexpectedEof => io::ErrorKind::UnexpectedEof,
+            IoErrorKind::OutOfMemory => io::ErrorKind::OutOfMemory,
+            IoErrorKind::HostUnreachable => io::ErrorKind::HostUnreachable,
+            IoErrorKind::NetworkUnreachable => io::ErrorKind::NetworkUnreachable,
+            IoErrorKind::NetworkDown => io::ErrorKind::NetworkDown,
+            IoErrorKind::NotADirectory => io::ErrorKind::NotADirectory,
+            IoErrorKind::IsADirectory => io::ErrorKind::IsADirectory,
+            IoErrorKind::DirectoryNotEmpty => io::ErrorKind::DirectoryNotEmpty,
+            IoErrorKind::ReadOnlyFilesystem => io::ErrorKind::ReadOnlyFilesystem,
+            IoErrorKind::StaleNetworkFileHandle => io::ErrorKind::StaleNetworkFileHandle,
+            IoErrorKind::StorageFull => io::ErrorKind::StorageFull,
+            IoErrorKind::NotSeekable => io::ErrorKind::NotSeekable,
+            IoErrorKind::FileTooLarge => io::ErrorKind::FileTooLarge,
+            IoErrorKind::ResourceBusy => io::ErrorKind::ResourceBusy,
+            IoErrorKind::ExecutableFileBusy => io::ErrorKind::ExecutableFileBusy,
+            IoErrorKind::Deadlock => io::ErrorKind::Deadlock,
+            IoErrorKind::TooManyLinks => io::ErrorKind::TooManyLinks,
+            IoErrorKind::ArgumentListTooLong => io::ErrorKind::ArgumentListTooLong,
+            IoErrorKind::CrossesDevices
+            | IoErrorKind::InvalidFilename
+            | IoErrorKind::FilesystemQuotaExceeded
+            | IoErrorKind::FilesystemLoop
+            | IoErrorKind::Other
+            | IoErrorKind::Uncategorized => io::ErrorKind::Other,
         }
     }
 }
 
 impl IoErrorKind {
+    /// Every kind, in declaration order.
+    #[cfg(test)]
+    pub(crate) const ALL: &'static [IoErrorKind] = &[
+        IoErrorKind::NotFound,
+        IoErrorKind::PermissionDenied,
+        IoErrorKind::ConnectionRefused,
+        IoErrorKind::ConnectionReset,
+        IoErrorKind::HostUnreachable,
+        IoErrorKind::NetworkUnreachable,
+        IoErrorKind::ConnectionAborted,
+        IoErrorKind::NotConnected,
+        IoErrorKind::AddrInUse,
+        IoErrorKind::AddrNotAvailable,
+        IoErrorKind::NetworkDown,
+        IoErrorKind::BrokenPipe,
+        IoErrorKind::AlreadyExists,
+        IoErrorKind::WouldBlock,
+        IoErrorKind::NotADirectory,
+        IoErrorKind::IsADirectory,
+        IoErrorKind::DirectoryNotEmpty,
+        IoErrorKind::ReadOnlyFilesystem,
+        IoErrorKind::FilesystemLoop,
+        IoErrorKind::StaleNetworkFileHandle,
+        IoErrorKind::InvalidInput,
+        IoErrorKind::InvalidData,
+        IoErrorKind::TimedOut,
+        IoErrorKind::WriteZero,
+        IoErrorKind::StorageFull,
+        IoErrorKind::NotSeekable,
+        IoErrorKind::FilesystemQuotaExceeded,
+        IoErrorKind::FileTooLarge,
+        IoErrorKind::ResourceBusy,
+        IoErrorKind::ExecutableFileBusy,
+        IoErrorKind::Deadlock,
+        IoErrorKind::CrossesDevices,
+        IoErrorKind::TooManyLinks,
+        IoErrorKind::InvalidFilename,
+        IoErrorKind::ArgumentListTooLong,
+        IoErrorKind::Interrupted,
+        IoErrorKind::Unsupported,
+        IoErrorKind::UnexpectedEof,
+        IoErrorKind::OutOfMemory,
+        IoErrorKind::Other,
+        IoErrorKind::Uncategorized,
+    ];
+
     pub(crate) fn as_str(&self) -> &'static str {
         use IoErrorKind::*;
         // Strictly alphabetical, please.  (Sadly rustfmt cannot do this yet.)
@@ -709,6 +1007,7 @@ impl RedisError {
             ErrorKind::ExtensionError => "extension error",
             ErrorKind::ClientError => "client error",
             ErrorKind::ReadOnly => "read-only",
+            ErrorKind::ParseError => "parse error",
         }
     }
 
@@ -717,13 +1016,24 @@ impl RedisError {
         self.as_io_error().is_some()
     }
 
-    // TODO: implement mapping of custom IoErrorKind type to io::ErrorKind
-    pub(crate) fn as_io_error(&self) -> Option<&io::Error> {
-        None
-        // match &self.repr {
-        //     ErrorRepr::IoError(kind, desc) => Some(&io::Error::new(kind.into(), desc)),
-        //     _ => None,
-        // }
+    /// Returns the kind and description of an IO failure.
+    ///
+    /// The original `io::Error` can't be kept as it is not serializable, so
+    /// this is as close to borrowing it as it gets.
+    pub(crate) fn as_io_error(&self) -> Option<(io::ErrorKind, &str)> {
+        match &self.repr {
+            ErrorRepr::IoError(kind, desc) => Some(((*kind).into(), desc.as_str())),
+            _ => None,
+        }
+    }
+
+    /// Rebuilds the `io::Error` an IO failure was created from.
+    ///
+    /// The error is reconstructed from its kind and description, so it
+    /// compares equal by `kind()` but carries no OS error code.
+    pub fn to_io_error(&self) -> Option<io::Error> {
+        self.as_io_error()
+            .map(|(kind, desc)| io::Error::new(kind, desc.to_string()))
     }
 
     /// Indicates that this is a cluster error.
@@ -773,6 +1083,30 @@ impl RedisError {
         }
     }
 
+    /// Returns true if the server closed the connection, either while a
+    /// reply was read or when writing to it.
+    pub(crate) fn is_connection_closed(&self) -> bool {
+        match &self.repr {
+            ErrorRepr::IoError(kind, _desc) => matches!(
+                kind,
+                IoErrorKind::BrokenPipe
+                    | IoErrorKind::ConnectionReset
+                    | IoErrorKind::ConnectionAborted
+                    | IoErrorKind::UnexpectedEof
+            ),
+            _ => false,
+        }
+    }
+
+    /// Returns true if the server closed the connection while a reply was
+    /// being read.
+    pub(crate) fn is_end_of_stream(&self) -> bool {
+        matches!(
+            &self.repr,
+            ErrorRepr::IoError(IoErrorKind::UnexpectedEof, _)
+        )
+    }
+
     /// Returns the node the error refers to.
     ///
     /// This returns `(addr, slot_id)`.
@@ -842,6 +1176,8 @@ pub type RedisResult<T> = Result<T, RedisError>;
 #[derive(Debug, Deserialize, Serialize)]
 pub struct InfoDict {
     map: HashMap<String, Value>,
+    /// The section of every key, by key.
+    sections: HashMap<String, String>,
 }
 
 /// This type provides convenient access to key/value data returned by
@@ -858,25 +1194,90 @@ pub struct InfoDict {
 /// # let mut con = client.get_connection().unwrap();
 /// let info : redis::InfoDict = redis::cmd("INFO").query(&mut con)?;
 /// let role : Option<String> = info.get("role");
+///
+/// let memory = info.section("memory").unwrap_or_default();
+/// let used : Option<u64> = memory.get("used_memory");
+/// for db in info.keyspace() {
+///     println!("db{}: {} keys", db.db, db.keys);
+/// }
 /// # Ok(()) }
 /// ```
 impl InfoDict {
     /// Creates a new info dictionary from a string in the response of
     /// the INFO command.  Each line is a key, value pair with the
     /// key and value separated by a colon (`:`).  Lines starting with a
-    /// hash (`#`) are ignored.
+    /// hash (`#`) are section headers like `# Server`, the keys that
+    /// follow them belong to that section.
     pub fn new(kvpairs: &str) -> InfoDict {
         let mut map = HashMap::new();
+        let mut sections = HashMap::new();
+        let mut section = "";
         for line in kvpairs.lines() {
-            if line.is_empty() || line.starts_with('#') {
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('#') {
+                section = header.trim();
                 continue;
             }
             let mut p = line.splitn(2, ':');
             let k = unwrap_or!(p.next(), continue).to_string();
             let v = unwrap_or!(p.next(), continue).to_string();
+            if !section.is_empty() {
+                sections.insert(k.clone(), section.to_string());
+            }
             map.insert(k, Value::Status(v));
         }
-        InfoDict { map }
+        InfoDict { map, sections }
+    }
+
+    /// Returns the keys of the section `name`, e.g. `Replication`, as an
+    /// info dict of its own.  Section names are case insensitive.
+    ///
+    /// Returns `None` if there is no key in that section.
+    pub fn section(&self, name: &str) -> Option<InfoDict> {
+        let mut map = HashMap::new();
+        let mut sections = HashMap::new();
+        for (key, section) in &self.sections {
+            if section.eq_ignore_ascii_case(name) {
+                map.insert(key.clone(), self.map[key].clone());
+                sections.insert(key.clone(), section.clone());
+            }
+        }
+        if map.is_empty() {
+            None
+        } else {
+            Some(InfoDict { map, sections })
+        }
+    }
+
+    /// Returns the section the key belongs to, if it came after a section
+    /// header.
+    pub fn section_of(&self, key: &str) -> Option<&str> {
+        self.sections.get(key).map(String::as_str)
+    }
+
+    /// Parses the `db<N>:keys=..,expires=..,avg_ttl=..` lines of the
+    /// `Keyspace` section, ordered by database number.
+    ///
+    /// Databases without keys are not listed by the server.  Lines that
+    /// cannot be parsed are skipped, and so are keys of other sections, even
+    /// if they look like a database.
+    pub fn keyspace(&self) -> Vec<KeyspaceInfo> {
+        let mut rv: Vec<KeyspaceInfo> = self
+            .sections
+            .iter()
+            .filter(|(_, section)| section.eq_ignore_ascii_case("keyspace"))
+            .filter_map(|(key, _)| {
+                let db = key.strip_prefix("db")?.parse().ok()?;
+                match &self.map[key] {
+                    Value::Status(stats) => KeyspaceInfo::parse(db, stats),
+                    _ => None,
+                }
+            })
+            .collect();
+        rv.sort_by_key(|info| info.db);
+        rv
     }
 
     /// Fetches a value by key and converts it into the given type.
@@ -909,6 +1310,106 @@ impl InfoDict {
     }
 }
 
+impl Default for InfoDict {
+    fn default() -> Self {
+        InfoDict::new("")
+    }
+}
+
+/// The statistics of one database, from the `Keyspace` section of `INFO`.
+///
+/// See [`InfoDict::keyspace`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct KeyspaceInfo {
+    /// The database number.
+    pub db: i64,
+    /// The number of keys.
+    pub keys: u64,
+    /// The number of keys with an expiry.
+    pub expires: u64,
+    /// The estimated average time to live of the keys with an expiry, in
+    /// milliseconds.
+    pub avg_ttl: u64,
+}
+
+impl KeyspaceInfo {
+    fn parse(db: i64, stats: &str) -> Option<KeyspaceInfo> {
+        let mut rv = KeyspaceInfo {
+            db,
+            keys: 0,
+            expires: 0,
+            avg_ttl: 0,
+        };
+        for stat in stats.split(',') {
+            let mut p = stat.splitn(2, '=');
+            let (name, value) = (p.next()?, p.next()?);
+            match name {
+                "keys" => rv.keys = value.parse().ok()?,
+                "expires" => rv.expires = value.parse().ok()?,
+                "avg_ttl" => rv.avg_ttl = value.parse().ok()?,
+                // Newer servers add more, e.g. `subexpiry`.
+                _ => {}
+            }
+        }
+        Some(rv)
+    }
+}
+
+/// The value of a configuration parameter as returned by `CONFIG GET`.
+///
+/// Some parameters, like `save` or `client-output-buffer-limit`, hold
+/// several space separated tokens.  The value keeps them together so that
+/// it is written back to `config_set` as a single argument, and `tokens`
+/// splits it for inspection.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ConfigValue(String);
+
+impl ConfigValue {
+    /// Creates a value from its textual form, e.g. `"3600 1 300 100"`.
+    pub fn new<S: Into<String>>(value: S) -> ConfigValue {
+        ConfigValue(value.into())
+    }
+
+    /// Returns the value as the server sent it.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the space separated tokens of the value.
+    pub fn tokens(&self) -> impl Iterator<Item = &str> {
+        self.0.split_whitespace()
+    }
+}
+
+impl fmt::Display for ConfigValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl ToRedisArgs for ConfigValue {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(self.0.as_bytes())
+    }
+}
+
+impl FromRedisValue for ConfigValue {
+    fn from_redis_value(v: &Value) -> RedisResult<ConfigValue> {
+        match *v {
+            // Join a value that was sent as several items instead of
+            // keeping only one of them.
+            Value::Bulk(ref items) | Value::Set(ref items) => {
+                let tokens: Vec<String> = FromRedisValue::from_redis_values(items)?;
+                Ok(ConfigValue(tokens.join(" ")))
+            }
+            _ => String::from_redis_value(v).map(ConfigValue),
+        }
+    }
+}
+
 /// Abstraction trait for redis command abstractions.
 pub trait RedisWrite {
     /// Accepts a serialized redis command.
@@ -1087,6 +1588,8 @@ itoa_based_to_redis_impl!(i32, NumericBehavior::NumberIsInteger);
 itoa_based_to_redis_impl!(u32, NumericBehavior::NumberIsInteger);
 itoa_based_to_redis_impl!(i64, NumericBehavior::NumberIsInteger);
 itoa_based_to_redis_impl!(u64, NumericBehavior::NumberIsInteger);
+itoa_based_to_redis_impl!(i128, NumericBehavior::NumberIsInteger);
+itoa_based_to_redis_impl!(u128, NumericBehavior::NumberIsInteger);
 itoa_based_to_redis_impl!(isize, NumericBehavior::NumberIsInteger);
 itoa_based_to_redis_impl!(usize, NumericBehavior::NumberIsInteger);
 
@@ -1098,6 +1601,8 @@ non_zero_itoa_based_to_redis_impl!(core::num::NonZeroU32, NumericBehavior::Numbe
 non_zero_itoa_based_to_redis_impl!(core::num::NonZeroI32, NumericBehavior::NumberIsInteger);
 non_zero_itoa_based_to_redis_impl!(core::num::NonZeroU64, NumericBehavior::NumberIsInteger);
 non_zero_itoa_based_to_redis_impl!(core::num::NonZeroI64, NumericBehavior::NumberIsInteger);
+non_zero_itoa_based_to_redis_impl!(core::num::NonZeroU128, NumericBehavior::NumberIsInteger);
+non_zero_itoa_based_to_redis_impl!(core::num::NonZeroI128, NumericBehavior::NumberIsInteger);
 non_zero_itoa_based_to_redis_impl!(core::num::NonZeroUsize, NumericBehavior::NumberIsInteger);
 non_zero_itoa_based_to_redis_impl!(core::num::NonZeroIsize, NumericBehavior::NumberIsInteger);
 
@@ -1131,6 +1636,54 @@ impl<'a> ToRedisArgs for &'a str {
     }
 }
 
+impl ToRedisArgs for char {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        let mut buf = [0; 4];
+        out.write_arg(self.encode_utf8(&mut buf).as_bytes())
+    }
+}
+
+/// Writes the duration as whole milliseconds, the unit of `PX`, `PEXPIRE`
+/// and friends.  Anything below a millisecond is truncated, so e.g. 1.9ms
+/// is sent as `1`.
+impl ToRedisArgs for Duration {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        let mut buf = ::itoa::Buffer::new();
+        let s = buf.format(self.as_millis());
+        out.write_arg(s.as_bytes())
+    }
+
+    fn describe_numeric_behavior(&self) -> NumericBehavior {
+        NumericBehavior::NumberIsInteger
+    }
+}
+
+macro_rules! display_based_to_redis_impl {
+    ($t:ty) => {
+        impl ToRedisArgs for $t {
+            fn write_redis_args<W>(&self, out: &mut W)
+            where
+                W: ?Sized + RedisWrite,
+            {
+                out.write_arg(self.to_string().as_bytes())
+            }
+        }
+    };
+}
+
+display_based_to_redis_impl!(IpAddr);
+display_based_to_redis_impl!(Ipv4Addr);
+display_based_to_redis_impl!(Ipv6Addr);
+// IPv6 socket addresses are written as `[addr]:port`, which `FromStr`
+// reads back.
+display_based_to_redis_impl!(SocketAddr);
+
 impl<T: ToRedisArgs> ToRedisArgs for Vec<T> {
     fn write_redis_args<W>(&self, out: &mut W)
     where
@@ -1355,6 +1908,14 @@ pub trait FromRedisValue: Sized {
     fn from_byte_vec(_vec: &[u8]) -> Option<Vec<Self>> {
         None
     }
+
+    /// Returns true if the type ignores the value it is converted from,
+    /// which allows skipping allocations while parsing the reply.  This only
+    /// exists internally as a workaround for the lack of specialization.
+    #[doc(hidden)]
+    fn discards_value() -> bool {
+        false
+    }
 }
 
 macro_rules! from_redis_value_for_num_internal {
@@ -1370,6 +1931,16 @@ macro_rules! from_redis_value_for_num_internal {
                 Ok(rv) => Ok(rv),
                 Err(_) => invalid_type_error!(v, "Could not convert from string."),
             },
+            // Going through the string form keeps floats exact and makes
+            // integers reject doubles with a fraction instead of truncating.
+            Value::Double(val) => match val.to_string().parse::<$t>() {
+                Ok(rv) => Ok(rv),
+                Err(_) => invalid_type_error!(v, "Could not convert from double."),
+            },
+            Value::BigNumber(ref s) => match s.parse::<$t>() {
+                Ok(rv) => Ok(rv),
+                Err(_) => invalid_type_error!(v, "Could not convert from string."),
+            },
             _ => invalid_type_error!(v, "Response type not convertible to numeric."),
         }
     }};
@@ -1433,6 +2004,7 @@ impl FromRedisValue for bool {
                 }
             }
             Value::Okay => Ok(true),
+            Value::Boolean(val) => Ok(val),
             _ => invalid_type_error!(v, "Response type not bool compatible."),
         }
     }
@@ -1444,11 +2016,75 @@ impl FromRedisValue for String {
             Value::Data(ref bytes) => Ok(from_utf8(bytes)?.to_string()),
             Value::Okay => Ok("OK".to_string()),
             Value::Status(ref val) => Ok(val.to_string()),
+            Value::VerbatimString { ref text, .. } => Ok(text.to_string()),
+            Value::BigNumber(ref val) => Ok(val.to_string()),
+            Value::Double(val) => Ok(val.to_string()),
             _ => invalid_type_error!(v, "Response type not string compatible."),
         }
     }
 }
 
+impl FromRedisValue for char {
+    fn from_redis_value(v: &Value) -> RedisResult<char> {
+        let s = match *v {
+            Value::Data(ref bytes) => from_utf8(bytes)?,
+            Value::Status(ref val) => &val[..],
+            _ => invalid_type_error!(v, "Response type not char compatible."),
+        };
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => invalid_type_error!(v, "Response is not a single character."),
+        }
+    }
+}
+
+/// Reads an integer number of milliseconds, e.g. the reply of `PTTL`.
+///
+/// Negative numbers are rejected, including the `-1` and `-2` that `PTTL`
+/// returns for keys without a timeout or missing keys.
+impl FromRedisValue for Duration {
+    fn from_redis_value(v: &Value) -> RedisResult<Duration> {
+        let millis = i64::from_redis_value(v)?;
+        if millis < 0 {
+            invalid_type_error!(v, "Negative number is not a valid duration.");
+        }
+        Ok(Duration::from_millis(millis as u64))
+    }
+}
+
+macro_rules! from_str_based_from_redis_value {
+    ($t:ty, $desc:expr) => {
+        impl FromRedisValue for $t {
+            fn from_redis_value(v: &Value) -> RedisResult<$t> {
+                match String::from_redis_value(v)?.parse::<$t>() {
+                    Ok(rv) => Ok(rv),
+                    Err(_) => invalid_type_error!(v, $desc),
+                }
+            }
+        }
+    };
+}
+
+from_str_based_from_redis_value!(IpAddr, "Could not parse an IP address.");
+from_str_based_from_redis_value!(Ipv4Addr, "Could not parse an IPv4 address.");
+from_str_based_from_redis_value!(Ipv6Addr, "Could not parse an IPv6 address.");
+from_str_based_from_redis_value!(SocketAddr, "Could not parse a socket address.");
+
+/// Shares a fetched value without copying it again on every clone, e.g.
+/// to hand a large blob to many lunatic processes.
+impl FromRedisValue for Arc<[u8]> {
+    fn from_redis_value(v: &Value) -> RedisResult<Arc<[u8]>> {
+        Vec::<u8>::from_redis_value(v).map(Arc::from)
+    }
+}
+
+impl FromRedisValue for Arc<str> {
+    fn from_redis_value(v: &Value) -> RedisResult<Arc<str>> {
+        String::from_redis_value(v).map(Arc::from)
+    }
+}
+
 impl<T: FromRedisValue> FromRedisValue for Vec<T> {
     fn from_redis_value(v: &Value) -> RedisResult<Vec<T>> {
         match *v {
@@ -1458,7 +2094,17 @@ impl<T: FromRedisValue> FromRedisValue for Vec<T> {
                 Some(x) => Ok(x),
                 None => invalid_type_error!(v, "Response type not vector compatible."),
             },
-            Value::Bulk(ref items) => FromRedisValue::from_redis_values(items),
+            Value::Bulk(ref items) | Value::Set(ref items) | Value::Push(ref items) => {
+                FromRedisValue::from_redis_values(items)
+            }
+            Value::Map(ref items) => {
+                // Flatten the pairs the way RESP2 sends maps.
+                let flat: Vec<Value> = items
+                    .iter()
+                    .flat_map(|(k, v)| [k.clone(), v.clone()])
+                    .collect();
+                FromRedisValue::from_redis_values(&flat)
+            }
             Value::Nil => Ok(vec![]),
             _ => invalid_type_error!(v, "Response type not vector compatible."),
         }
@@ -1535,16 +2181,30 @@ where
     }
 }
 
+/// Converting into a `Value` returns the reply untouched.
+///
+/// Together with `Vec<Value>`, which keeps every element of an array reply
+/// as is, this is the escape hatch for commands with irregular replies that
+/// do not map onto a single type: decode into `Vec<Value>` and inspect the
+/// elements manually.
 impl FromRedisValue for Value {
     fn from_redis_value(v: &Value) -> RedisResult<Value> {
         Ok(v.clone())
     }
+
+    fn from_redis_values(items: &[Value]) -> RedisResult<Vec<Value>> {
+        Ok(items.to_vec())
+    }
 }
 
 impl FromRedisValue for () {
     fn from_redis_value(_v: &Value) -> RedisResult<()> {
         Ok(())
     }
+
+    fn discards_value() -> bool {
+        true
+    }
 }
 
 macro_rules! from_redis_value_for_tuple {
@@ -1580,6 +2240,18 @@ macro_rules! from_redis_value_for_tuple {
                 // hacky way to count the tuple size
                 let mut n = 0;
                 $(let $name = (); n += 1;)*
+
+                // RESP3 nests the member/score pairs of `... WITHSCORES`
+                // replies in their own arrays.  Only RESP3 sends scores as
+                // doubles, so a RESP2 array of arrays stays flat.
+                let nested = n == 2 && !items.is_empty() && items.iter().all(|item| {
+                    matches!(*item, Value::Bulk(ref inner)
+                        if matches!(inner[..], [_, Value::Double(_)]))
+                });
+                if nested {
+                    return items.iter().map(from_redis_value).collect();
+                }
+
                 if items.len() % n != 0 {
                     invalid_type_error!(items, "Bulk response of wrong dimension")
                 }
@@ -1628,6 +2300,134 @@ impl<T: FromRedisValue> FromRedisValue for Option<T> {
     }
 }
 
+/// Decodes like `T` but fails on a nil reply instead of accepting it.
+///
+/// Collections such as `Vec<T>` decode a nil reply into an empty collection,
+/// which hides the difference between a missing key and an empty one.
+/// `Strict<Vec<T>>` fails with a `TypeError` on nil instead, and
+/// `Option<Strict<Vec<T>>>` returns `None`:
+///
+/// ```rust,no_run
+/// # fn do_something() -> redis::RedisResult<()> {
+/// # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+/// # let mut con = client.get_connection().unwrap();
+/// use redis::Strict;
+///
+/// let members: Option<Strict<Vec<String>>> = redis::cmd("EXEC").query(&mut con)?;
+/// match members {
+///     None => println!("the transaction was aborted"),
+///     Some(Strict(members)) => println!("got {} replies", members.len()),
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Strict<T>(pub T);
+
+impl<T> Strict<T> {
+    /// Returns the decoded value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: FromRedisValue> FromRedisValue for Strict<T> {
+    fn from_redis_value(v: &Value) -> RedisResult<Strict<T>> {
+        if *v == Value::Nil {
+            invalid_type_error!(v, "Response was nil");
+        }
+        Ok(Strict(from_redis_value(v)?))
+    }
+}
+
+/// Stores `T` as a JSON string and reads it back.
+///
+/// ```rust,no_run
+/// # fn do_something() -> redis::RedisResult<()> {
+/// # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+/// # let mut con = client.get_connection().unwrap();
+/// use redis::{Commands, Json};
+/// use std::collections::HashMap;
+///
+/// let mut user = HashMap::new();
+/// user.insert("name", "Ferris");
+/// let _: () = con.set("user:1", Json::encode(&user)?)?;
+/// let Json(user): Json<HashMap<String, String>> = con.get("user:1")?;
+/// # Ok(()) }
+/// ```
+///
+/// Any `Json<T>` with a serializable `T` can be passed as an argument.
+/// Serializing panics if the value cannot be represented as JSON, e.g. a
+/// map with non-string keys; convert such values with `Json::encode`, which
+/// returns an error instead.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Json<T>(pub T);
+
+#[cfg(feature = "json")]
+impl<T> Json<T> {
+    /// Returns the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+#[cfg(feature = "json")]
+impl Json<serde_json::Value> {
+    /// Converts `value` to JSON so it can be passed as an argument, or
+    /// fails if it cannot be represented as JSON.  Writing the result as an
+    /// argument never fails.
+    pub fn encode<T: Serialize + ?Sized>(value: &T) -> RedisResult<Json<serde_json::Value>> {
+        match serde_json::to_value(value) {
+            Ok(json) => Ok(Json(json)),
+            Err(err) => fail!((
+                ErrorKind::ClientError,
+                "Value is not representable as JSON",
+                err.to_string()
+            )),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T: Serialize> ToRedisArgs for Json<T> {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        match serde_json::to_vec(&self.0) {
+            Ok(json) => out.write_arg(&json),
+            Err(err) => panic!("Value is not representable as JSON: {}", err),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+fn read_json<T: serde::de::DeserializeOwned>(v: &Value) -> RedisResult<T> {
+    let parsed = match *v {
+        Value::Data(ref bytes) => serde_json::from_slice(bytes),
+        Value::Status(ref s) => serde_json::from_str(s),
+        _ => invalid_type_error!(v, "Response type not JSON compatible."),
+    };
+    match parsed {
+        Ok(rv) => Ok(rv),
+        Err(err) => invalid_type_error!(v, format!("Could not parse JSON: {}", err)),
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T: serde::de::DeserializeOwned> FromRedisValue for Json<T> {
+    fn from_redis_value(v: &Value) -> RedisResult<Json<T>> {
+        read_json(v).map(Json)
+    }
+}
+
+#[cfg(feature = "json")]
+impl FromRedisValue for serde_json::Value {
+    fn from_redis_value(v: &Value) -> RedisResult<serde_json::Value> {
+        read_json(v)
+    }
+}
+
 #[cfg(feature = "bytes")]
 impl FromRedisValue for bytes::Bytes {
     fn from_redis_value(v: &Value) -> RedisResult<Self> {
@@ -1638,8 +2438,181 @@ impl FromRedisValue for bytes::Bytes {
     }
 }
 
+// Process handles are stored with the same bincode encoding that lunatic
+// uses to send them around in messages, so a handle read back from redis
+// refers to the same process.
+#[cfg(feature = "lunatic-processes")]
+fn write_process_handle<T, W>(handle: &T, out: &mut W)
+where
+    T: Serialize,
+    W: ?Sized + RedisWrite,
+{
+    let encoded = bincode::serialize(handle).expect("process handles are always serializable");
+    out.write_arg(&encoded)
+}
+
+#[cfg(feature = "lunatic-processes")]
+fn read_process_handle<T: serde::de::DeserializeOwned>(v: &Value) -> RedisResult<T> {
+    match v {
+        Value::Data(bytes) => match bincode::deserialize(bytes) {
+            Ok(handle) => Ok(handle),
+            Err(_) => invalid_type_error!(v, "Data is not a serialized process handle"),
+        },
+        _ => invalid_type_error!(v, "Not binary data"),
+    }
+}
+
+#[cfg(feature = "lunatic-processes")]
+impl<M, S> ToRedisArgs for lunatic::Process<M, S>
+where
+    lunatic::Process<M, S>: Serialize,
+{
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        write_process_handle(self, out)
+    }
+}
+
+#[cfg(feature = "lunatic-processes")]
+impl<M, S> FromRedisValue for lunatic::Process<M, S>
+where
+    lunatic::Process<M, S>: serde::de::DeserializeOwned,
+{
+    fn from_redis_value(v: &Value) -> RedisResult<Self> {
+        read_process_handle(v)
+    }
+}
+
+#[cfg(feature = "lunatic-processes")]
+impl<T> ToRedisArgs for lunatic::process::ProcessRef<T>
+where
+    lunatic::process::ProcessRef<T>: Serialize,
+{
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        write_process_handle(self, out)
+    }
+}
+
+#[cfg(feature = "lunatic-processes")]
+impl<T> FromRedisValue for lunatic::process::ProcessRef<T>
+where
+    lunatic::process::ProcessRef<T>: serde::de::DeserializeOwned,
+{
+    fn from_redis_value(v: &Value) -> RedisResult<Self> {
+        read_process_handle(v)
+    }
+}
+
+/// Writes the 16 raw bytes of the UUID.
+#[cfg(feature = "uuid")]
+impl ToRedisArgs for uuid::Uuid {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(self.as_bytes())
+    }
+}
+
+/// Reads either the 16 raw bytes of a UUID or its textual form, e.g.
+/// `67e55044-10b1-426f-9247-bb680e5fe0c8`.
+#[cfg(feature = "uuid")]
+impl FromRedisValue for uuid::Uuid {
+    fn from_redis_value(v: &Value) -> RedisResult<uuid::Uuid> {
+        let parsed = match *v {
+            Value::Data(ref bytes) if bytes.len() == 16 => uuid::Uuid::from_slice(bytes),
+            Value::Data(ref bytes) => uuid::Uuid::parse_str(from_utf8(bytes)?),
+            Value::Status(ref s) => uuid::Uuid::parse_str(s),
+            _ => invalid_type_error!(v, "Response type not UUID compatible."),
+        };
+        match parsed {
+            Ok(rv) => Ok(rv),
+            Err(_) => invalid_type_error!(v, "Could not parse a UUID."),
+        }
+    }
+}
+
 /// A shortcut function to invoke `FromRedisValue::from_redis_value`
 /// to make the API slightly nicer.
+///
+/// RESP3 attributes are removed from the value, including the ones attached
+/// to nested values, before it is converted.
 pub fn from_redis_value<T: FromRedisValue>(v: &Value) -> RedisResult<T> {
-    FromRedisValue::from_redis_value(v)
+    if v.has_attributes() {
+        FromRedisValue::from_redis_value(&v.strip_attributes())
+    } else {
+        FromRedisValue::from_redis_value(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_error_kind_round_trip() {
+        for &kind in IoErrorKind::ALL {
+            let std_kind = io::ErrorKind::from(kind);
+            if std_kind == io::ErrorKind::Other {
+                // Kinds without a stable std name collapse into `Other`.
+                assert_eq!(IoErrorKind::from(std_kind), IoErrorKind::Other);
+            } else {
+                assert_eq!(IoErrorKind::from(std_kind), kind, "{:?}", kind);
+            }
+
+            // A stored error can be turned back into an `io::Error`.
+            let err = RedisError {
+                repr: ErrorRepr::IoError(kind, format!("{:?}", kind)),
+            };
+            let io_err = err.to_io_error().unwrap();
+            assert_eq!(io_err.kind(), std_kind);
+            assert_eq!(io_err.to_string(), format!("{:?}", kind));
+            assert_eq!(RedisError::from(io_err).kind(), ErrorKind::IoError);
+        }
+    }
+
+    #[test]
+    fn test_io_error_kind_from_any_std_kind() {
+        // Every errno the platform knows, which includes kinds that have no
+        // stable name in std.
+        let kinds = (0..200).map(|code| io::Error::from_raw_os_error(code).kind());
+        for std_kind in kinds.chain(vec![
+            io::ErrorKind::NotFound,
+            io::ErrorKind::TimedOut,
+            io::ErrorKind::Unsupported,
+            io::ErrorKind::OutOfMemory,
+            io::ErrorKind::Other,
+        ]) {
+            let kind = IoErrorKind::from(std_kind);
+            match io::ErrorKind::from(kind) {
+                // No stable name in std to go back to.
+                io::ErrorKind::Other if kind != IoErrorKind::Other => {}
+                back => assert_eq!(back, std_kind, "{:?}", kind),
+            }
+        }
+    }
+
+    #[test]
+    fn test_to_io_error() {
+        let err = RedisError::from(io::Error::new(io::ErrorKind::TimedOut, "too slow"));
+        assert!(err.is_io_error());
+        assert!(err.is_timeout());
+        assert!(!err.is_connection_dropped());
+        let io_err = err.to_io_error().unwrap();
+        assert_eq!(io_err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(io_err.to_string(), "too slow");
+
+        let err = RedisError::from(io::Error::from(io::ErrorKind::BrokenPipe));
+        assert!(err.is_io_error());
+        assert!(err.is_connection_dropped());
+
+        let err = RedisError::from((ErrorKind::ResponseError, "nope"));
+        assert!(!err.is_io_error());
+        assert!(err.to_io_error().is_none());
+    }
 }
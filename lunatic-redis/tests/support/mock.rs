@@ -0,0 +1,67 @@
+use std::collections::VecDeque;
+
+use lunatic_redis::{ConnectionLike, RedisResult, Value};
+
+/// A connection that answers with scripted replies instead of talking to a
+/// server.  Every reply the server would send, including the ones of
+/// pipelined commands, is one entry of `replies`.
+pub struct MockConnection {
+    pub replies: VecDeque<RedisResult<Value>>,
+    /// The packed commands received so far, one entry per request.
+    pub sent: Vec<Vec<u8>>,
+}
+
+impl MockConnection {
+    pub fn new<I: IntoIterator<Item = RedisResult<Value>>>(replies: I) -> MockConnection {
+        MockConnection {
+            replies: replies.into_iter().collect(),
+            sent: Vec::new(),
+        }
+    }
+
+    fn next_reply(&mut self) -> RedisResult<Value> {
+        self.replies.pop_front().expect("unexpected command")
+    }
+}
+
+impl ConnectionLike for MockConnection {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        self.sent.push(cmd.to_vec());
+        self.next_reply()
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        self.sent.push(cmd.to_vec());
+        // Like a real connection, read all replies before failing with the
+        // first error.
+        let mut rv = vec![];
+        let mut first_err = None;
+        for idx in 0..(offset + count) {
+            match self.next_reply() {
+                Ok(item) if idx >= offset => rv.push(item),
+                Ok(_) => {}
+                Err(err) => {
+                    first_err.get_or_insert(err);
+                }
+            }
+        }
+        first_err.map_or(Ok(rv), Err)
+    }
+
+    fn get_db(&self) -> i64 {
+        0
+    }
+
+    fn check_connection(&mut self) -> bool {
+        true
+    }
+
+    fn is_open(&self) -> bool {
+        true
+    }
+}
@@ -14,6 +14,10 @@ mod cluster;
 #[cfg(feature = "cluster")]
 pub use self::cluster::*;
 
+mod mock;
+
+pub use self::mock::*;
+
 #[derive(PartialEq)]
 enum ServerType {
     Tcp { tls: bool },
@@ -215,25 +219,7 @@ pub fn encode_value<W>(value: &Value, writer: &mut W) -> io::Result<()>
 where
     W: io::Write,
 {
-    #![allow(clippy::write_with_newline)]
-    match *value {
-        Value::Nil => write!(writer, "$-1\r\n"),
-        Value::Int(val) => write!(writer, ":{}\r\n", val),
-        Value::Data(ref val) => {
-            write!(writer, "${}\r\n", val.len())?;
-            writer.write_all(val)?;
-            writer.write_all(b"\r\n")
-        }
-        Value::Bulk(ref values) => {
-            write!(writer, "*{}\r\n", values.len())?;
-            for val in values.iter() {
-                encode_value(val, writer)?;
-            }
-            Ok(())
-        }
-        Value::Okay => write!(writer, "+OK\r\n"),
-        Value::Status(ref s) => write!(writer, "+{}\r\n", s),
-    }
+    lunatic_redis::encode_redis_value(value, writer)
 }
 
 #[derive(Clone)]
@@ -0,0 +1,105 @@
+//! Counts the allocations of hot loops against a server that only sends
+//! canned replies.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use lunatic::net::{TcpListener, TcpStream};
+use lunatic_redis::{cmd, Client, Connection, Value};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const ROUNDS: usize = 1000;
+
+/// Connects to a server that has already sent `reply` `count` times, and
+/// returns the connection with the server end of it.
+fn connect_with_replies(reply: &[u8], count: usize) -> (Connection, TcpStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let con = Client::open(("127.0.0.1", port))
+        .unwrap()
+        .get_connection()
+        .unwrap();
+    let (mut server, _) = listener.accept().unwrap();
+    server.write_all(&reply.repeat(count)).unwrap();
+    (con, server)
+}
+
+/// Returns how many allocations `ROUNDS` calls of `f` made, after a few
+/// calls to warm up the buffers of the connection.
+fn count_allocations(mut f: impl FnMut()) -> usize {
+    for _ in 0..10 {
+        f();
+    }
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..ROUNDS {
+        f();
+    }
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+#[lunatic::test]
+fn test_recv_response_into_reuses_buffer() {
+    let reply = b"$5\r\nhello\r\n";
+    let (mut con, _server) = connect_with_replies(reply, 2 * (ROUNDS + 10));
+
+    let owned = count_allocations(|| match con.recv_response::<TcpStream>().unwrap() {
+        Value::Data(data) => assert_eq!(data, b"hello"),
+        value => panic!("expected data, got {:?}", value),
+    });
+    let mut buf = Vec::with_capacity(16);
+    let reused = count_allocations(|| match con.recv_response_into(&mut buf).unwrap() {
+        Value::Data(data) => {
+            assert_eq!(data, b"hello");
+            buf = data;
+        }
+        value => panic!("expected data, got {:?}", value),
+    });
+    // Reading from the socket allocates now and then either way, but only
+    // `recv_response` allocates a vector for every reply.
+    assert!(
+        owned >= reused + ROUNDS,
+        "recv_response: {}, recv_response_into: {}",
+        owned,
+        reused
+    );
+    assert!(reused < ROUNDS / 10, "{} allocations", reused);
+}
+
+#[lunatic::test]
+fn test_discarded_status_reply_is_not_allocated() {
+    // Inside MULTI every SET is answered with a QUEUED status.
+    let (mut con, _server) = connect_with_replies(b"+QUEUED\r\n", 2 * (ROUNDS + 10));
+    let mut set = cmd("SET");
+    set.arg("key").arg(42);
+
+    let unit = count_allocations(|| set.query::<()>(&mut con).unwrap());
+    let value = count_allocations(|| {
+        set.query::<Value>(&mut con).unwrap();
+    });
+    // Sending the command allocates either way, only the `Value` keeps a
+    // string of the status.
+    assert!(value >= unit + ROUNDS, "(): {}, Value: {}", unit, value);
+}
@@ -1,7 +1,8 @@
 #![cfg(feature = "cluster")]
 mod support;
 use crate::support::*;
-use redis::cluster::cluster_pipe;
+use redis::cluster::{cluster_pipe, get_slot};
+use redis::Commands;
 
 #[test]
 fn test_cluster_basics() {
@@ -22,6 +23,41 @@ fn test_cluster_basics() {
     );
 }
 
+#[test]
+fn test_cluster_keys_in_slot() {
+    let cluster = TestClusterContext::new(3, 0);
+    let mut con = cluster.connection();
+
+    let _: () = con.set("{slot}key1", 1).unwrap();
+    let _: () = con.set("{slot}key2", 2).unwrap();
+
+    let slot = get_slot(b"slot");
+    let count: usize = con.cluster_countkeysinslot(slot).unwrap();
+    assert_eq!(count, 2);
+
+    let mut keys: Vec<String> = con.cluster_getkeysinslot(slot, 10).unwrap();
+    keys.sort();
+    assert_eq!(keys, vec!["{slot}key1", "{slot}key2"]);
+}
+
+#[test]
+fn test_cluster_watch_cross_slot() {
+    let cluster = TestClusterContext::new(3, 0);
+    let mut con = cluster.connection();
+
+    let result: redis::RedisResult<(Option<String>,)> =
+        redis::transaction(&mut con, &["foo", "bar"], |con, pipe| {
+            pipe.get("foo").query(con)
+        });
+    assert_eq!(result.unwrap_err().kind(), redis::ErrorKind::CrossSlot);
+
+    // Keys sharing a hash tag can be watched together.
+    let watched: redis::RedisResult<()> = redis::cmd("WATCH")
+        .arg(&["{user}foo", "{user}bar"])
+        .query(&mut con);
+    assert!(watched.is_ok());
+}
+
 #[test]
 fn test_cluster_with_username_and_password() {
     let cluster = TestClusterContext::new_with_cluster_client_builder(3, 0, |builder| {
@@ -599,3 +599,26 @@
 //     let reply: StreamRangeReply = con.xrevrange_count("k1", "+", "-", 1).unwrap();
 //     assert_eq!(reply.ids.len(), 1);
 // }
+
+// #[lunatic::test]
+// fn test_xadd_generated_ids_range_scan() {
+//     let ctx = TestContext::new();
+//     let mut con = ctx.connection();
+
+//     let mut added = Vec::new();
+//     for i in 0..5 {
+//         let id: String = con.xadd("events", "*", &[("n", i)]).unwrap();
+//         added.push(id);
+//     }
+//     assert_eq!(con.xlen("events"), Ok(5));
+
+//     let reply: StreamRangeReply = con.xrange("events", "-", "+").unwrap();
+//     let ids: Vec<String> = reply.ids.iter().map(|e| e.id.clone()).collect();
+//     assert_eq!(ids, added);
+//     let values: Vec<i32> = reply.ids.iter().map(|e| e.get("n").unwrap()).collect();
+//     assert_eq!(values, vec![0, 1, 2, 3, 4]);
+
+//     // non-blocking read of everything after the second entry
+//     let reply: StreamReadReply = con.xread(&["events"], &[&added[1]]).unwrap();
+//     assert_eq!(reply.keys[0].ids.len(), 3);
+// }
@@ -1,3 +1,6 @@
+mod support;
+
+use crate::support::MockConnection;
 use lunatic_redis::ToRedisArgs;
 
 #[lunatic::test]
@@ -33,6 +36,75 @@ fn test_info_dict() {
     assert_eq!(d.get::<String>("key3"), None);
 }
 
+#[lunatic::test]
+fn test_info_dict_sections() {
+    use lunatic_redis::{FromRedisValue, InfoDict, KeyspaceInfo, Value};
+
+    // Trimmed reply of `INFO` from a redis 7 server.
+    let payload = "# Server\r\n\
+                   redis_version:7.0.5\r\n\
+                   redis_mode:standalone\r\n\
+                   tcp_port:6379\r\n\
+                   \r\n\
+                   # Memory\r\n\
+                   used_memory:1104776\r\n\
+                   used_memory_human:1.05M\r\n\
+                   \r\n\
+                   # Replication\r\n\
+                   role:master\r\n\
+                   connected_slaves:0\r\n\
+                   \r\n\
+                   # Keyspace\r\n\
+                   db0:keys=12,expires=3,avg_ttl=81452\r\n\
+                   db10:keys=1,expires=0,avg_ttl=0\r\n\
+                   db2:keys=5,expires=1,avg_ttl=1200,subexpiry=0\r\n";
+    let info: InfoDict =
+        FromRedisValue::from_redis_value(&Value::Data(payload.as_bytes().to_vec())).unwrap();
+
+    // The flat view is unchanged.
+    assert_eq!(info.len(), 10);
+    assert_eq!(info.get("redis_version"), Some("7.0.5".to_string()));
+    assert_eq!(info.get("used_memory"), Some(1104776u64));
+    assert_eq!(info.section_of("role"), Some("Replication"));
+    assert_eq!(info.section_of("no_such_key"), None);
+
+    let memory = info.section("memory").unwrap();
+    assert_eq!(memory.len(), 2);
+    assert_eq!(memory.get("used_memory_human"), Some("1.05M".to_string()));
+    assert!(!memory.contains_key(&"role"));
+    assert!(info.section("Replication").unwrap().contains_key(&"role"));
+    assert!(info.section("Cluster").is_none());
+
+    assert_eq!(
+        info.keyspace(),
+        vec![
+            KeyspaceInfo {
+                db: 0,
+                keys: 12,
+                expires: 3,
+                avg_ttl: 81452
+            },
+            KeyspaceInfo {
+                db: 2,
+                keys: 5,
+                expires: 1,
+                avg_ttl: 1200
+            },
+            KeyspaceInfo {
+                db: 10,
+                keys: 1,
+                expires: 0,
+                avg_ttl: 0
+            },
+        ]
+    );
+    assert_eq!(info.section("keyspace").unwrap().keyspace().len(), 3);
+    assert!(InfoDict::new("# Keyspace\r\n").keyspace().is_empty());
+    // Only keys of the keyspace section are databases.
+    let info = InfoDict::new("db1:keys=1,expires=0,avg_ttl=0\r\n# Modules\r\ndb2:keys=1\r\n");
+    assert!(info.keyspace().is_empty());
+}
+
 #[lunatic::test]
 fn test_i32() {
     use lunatic_redis::{ErrorKind, FromRedisValue, Value};
@@ -74,6 +146,279 @@ fn test_vec() {
     assert_eq!(v, Ok(vec![1i32, 2, 3]));
 }
 
+#[lunatic::test]
+fn test_vec_of_values() {
+    use lunatic_redis::{FromRedisValue, Value};
+
+    let items = vec![Value::Int(1), Value::Data("two".into()), Value::Nil];
+    let v: Vec<Value> = FromRedisValue::from_redis_value(&Value::Bulk(items.clone())).unwrap();
+    assert_eq!(v, items);
+
+    // Nested arrays are kept as they are as well
+    let nested = vec![Value::Bulk(items.clone()), Value::Okay];
+    let v: Vec<Value> = FromRedisValue::from_redis_value(&Value::Bulk(nested.clone())).unwrap();
+    assert_eq!(v, nested);
+}
+
+#[lunatic::test]
+fn test_resp3_values() {
+    use lunatic_redis::{FromRedisValue, Value};
+    use std::collections::{HashMap, HashSet};
+
+    let map = Value::Map(vec![
+        (Value::Data(b"port".to_vec()), Value::Data(b"6379".to_vec())),
+        (Value::Data(b"timeout".to_vec()), Value::Int(0)),
+    ]);
+    let v: HashMap<String, i64> = FromRedisValue::from_redis_value(&map).unwrap();
+    assert_eq!(v.get("port"), Some(&6379));
+    assert_eq!(v.get("timeout"), Some(&0));
+    let v: Vec<(String, i64)> = FromRedisValue::from_redis_value(&map).unwrap();
+    assert_eq!(v, vec![("port".into(), 6379), ("timeout".into(), 0)]);
+
+    let set = Value::Set(vec![Value::Data(b"a".to_vec()), Value::Data(b"b".to_vec())]);
+    let v: HashSet<String> = FromRedisValue::from_redis_value(&set).unwrap();
+    assert_eq!(v.len(), 2);
+
+    assert_eq!(f64::from_redis_value(&Value::Double(1.5)), Ok(1.5));
+    assert_eq!(bool::from_redis_value(&Value::Boolean(false)), Ok(false));
+    assert_eq!(
+        String::from_redis_value(&Value::VerbatimString {
+            format: "txt".into(),
+            text: "hello".into(),
+        }),
+        Ok("hello".to_string())
+    );
+}
+
+#[lunatic::test]
+fn test_value_eq() {
+    use lunatic_redis::Value;
+
+    fn assert_eq_impl<T: Eq>() {}
+    assert_eq_impl::<Value>();
+
+    // Doubles compare by their bits.
+    assert_eq!(Value::Double(f64::NAN), Value::Double(f64::NAN));
+    assert_ne!(Value::Double(0.0), Value::Double(-0.0));
+    assert_eq!(
+        Value::Map(vec![(Value::Data(b"a".to_vec()), Value::Double(0.5))]),
+        Value::Map(vec![(Value::Data(b"a".to_vec()), Value::Double(0.5))])
+    );
+    assert_ne!(
+        Value::Push(vec![Value::Int(1)]),
+        Value::Bulk(vec![Value::Int(1)])
+    );
+}
+
+#[lunatic::test]
+fn test_resp3_double_and_boolean() {
+    use lunatic_redis::{parse_redis_value, FromRedisValue};
+
+    // ZSCORE replies with a double in RESP3 and a bulk string in RESP2
+    let resp3 = parse_redis_value(&b",2.5\r\n"[..]).unwrap();
+    let resp2 = parse_redis_value(&b"$3\r\n2.5\r\n"[..]).unwrap();
+    assert_eq!(f64::from_redis_value(&resp3), Ok(2.5));
+    assert_eq!(f64::from_redis_value(&resp2), Ok(2.5));
+    assert_eq!(f32::from_redis_value(&resp3), Ok(2.5));
+    assert!(i64::from_redis_value(&resp3).is_err());
+
+    let inf = parse_redis_value(&b",inf\r\n"[..]).unwrap();
+    assert_eq!(f64::from_redis_value(&inf), Ok(f64::INFINITY));
+    let whole = parse_redis_value(&b",3\r\n"[..]).unwrap();
+    assert_eq!(i64::from_redis_value(&whole), Ok(3));
+
+    // SISMEMBER replies with a boolean in RESP3 and an integer in RESP2
+    let resp3 = parse_redis_value(&b"#t\r\n"[..]).unwrap();
+    let resp2 = parse_redis_value(&b":1\r\n"[..]).unwrap();
+    assert_eq!(bool::from_redis_value(&resp3), Ok(true));
+    assert_eq!(bool::from_redis_value(&resp2), Ok(true));
+    assert_eq!(
+        bool::from_redis_value(&parse_redis_value(&b"#f\r\n"[..]).unwrap()),
+        Ok(false)
+    );
+}
+
+#[lunatic::test]
+fn test_128_bit_integers() {
+    use lunatic_redis::{FromRedisValue, NumericBehavior, ToRedisArgs, Value};
+
+    let args = u128::MAX.to_redis_args();
+    assert_eq!(args, vec![u128::MAX.to_string().into_bytes()]);
+    assert_eq!(
+        u128::MAX.describe_numeric_behavior(),
+        NumericBehavior::NumberIsInteger
+    );
+    let value = Value::Data(args.into_iter().next().unwrap());
+    assert_eq!(u128::from_redis_value(&value), Ok(u128::MAX));
+
+    let args = i128::MIN.to_redis_args();
+    assert_eq!(
+        args,
+        vec![b"-170141183460469231731687303715884105728".to_vec()]
+    );
+    let value = Value::Data(args.into_iter().next().unwrap());
+    assert_eq!(i128::from_redis_value(&value), Ok(i128::MIN));
+}
+
+#[lunatic::test]
+fn test_check_arity() {
+    use lunatic_redis::{cmd, Cmd, ErrorKind};
+
+    // SET without a value
+    let err = cmd("SET").arg("key").check_arity().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ClientError);
+    assert!(Cmd::set("key", 42).check_arity().is_ok());
+    assert!(cmd("set")
+        .arg("key")
+        .arg(42)
+        .arg("EX")
+        .arg(10)
+        .check_arity()
+        .is_ok());
+
+    // Exact arity
+    assert!(cmd("GET").arg("a").arg("b").check_arity().is_err());
+    assert!(cmd("MULTI").check_arity().is_ok());
+
+    // Unknown commands are not checked
+    assert!(cmd("MY.MODULE.CMD").check_arity().is_ok());
+}
+
+#[lunatic::test]
+fn test_arc() {
+    use lunatic_redis::{FromRedisValue, Value};
+    use std::sync::Arc;
+
+    let value = Value::Data(b"a large blob".to_vec());
+    let bytes: Arc<[u8]> = FromRedisValue::from_redis_value(&value).unwrap();
+    assert_eq!(&bytes[..], b"a large blob");
+
+    // Clones share the bytes
+    let shared = Arc::clone(&bytes);
+    assert!(Arc::ptr_eq(&bytes, &shared));
+    assert_eq!(shared.as_ptr(), bytes.as_ptr());
+    assert_eq!(Arc::strong_count(&bytes), 2);
+
+    let text: Arc<str> = FromRedisValue::from_redis_value(&value).unwrap();
+    assert_eq!(&*text, "a large blob");
+    let v: Result<Arc<str>, _> = FromRedisValue::from_redis_value(&Value::Data(vec![0xff]));
+    assert!(v.is_err());
+}
+
+#[lunatic::test]
+fn test_char() {
+    use lunatic_redis::{ErrorKind, FromRedisValue, ToRedisArgs, Value};
+
+    assert_eq!('ü'.to_redis_args(), vec!["ü".as_bytes().to_vec()]);
+    assert_eq!('x'.to_redis_args(), vec![b"x".to_vec()]);
+
+    let v = char::from_redis_value(&Value::Data("ü".as_bytes().to_vec()));
+    assert_eq!(v, Ok('ü'));
+    let v = char::from_redis_value(&Value::Status("x".into()));
+    assert_eq!(v, Ok('x'));
+
+    for value in &[
+        Value::Data(b"ab".to_vec()),
+        Value::Data(vec![]),
+        Value::Status("ab".into()),
+        Value::Int(1),
+    ] {
+        let err = char::from_redis_value(value).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TypeError);
+    }
+}
+
+#[lunatic::test]
+fn test_duration() {
+    use lunatic_redis::{ErrorKind, FromRedisValue, NumericBehavior, ToRedisArgs, Value};
+    use std::time::Duration;
+
+    let d = Duration::from_secs(30);
+    assert_eq!(d.to_redis_args(), vec![b"30000".to_vec()]);
+    assert_eq!(
+        d.describe_numeric_behavior(),
+        NumericBehavior::NumberIsInteger
+    );
+    // Sub-millisecond parts are truncated
+    let d = Duration::from_micros(1900);
+    assert_eq!(d.to_redis_args(), vec![b"1".to_vec()]);
+
+    let v = Duration::from_redis_value(&Value::Int(30000));
+    assert_eq!(v, Ok(Duration::from_secs(30)));
+    let v = Duration::from_redis_value(&Value::Data(b"30000".to_vec()));
+    assert_eq!(v, Ok(Duration::from_secs(30)));
+
+    let err = Duration::from_redis_value(&Value::Int(-1)).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TypeError);
+    let err = Duration::from_redis_value(&Value::Data(b"-2".to_vec())).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TypeError);
+}
+
+#[lunatic::test]
+fn test_addresses() {
+    use lunatic_redis::{ErrorKind, FromRedisValue, ToRedisArgs, Value};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+    fn round_trip<T: ToRedisArgs + FromRedisValue>(val: &T, text: &str) -> T {
+        assert_eq!(val.to_redis_args(), vec![text.as_bytes().to_vec()]);
+        let value = Value::Data(val.to_redis_args().into_iter().next().unwrap());
+        T::from_redis_value(&value).unwrap()
+    }
+
+    let v4 = Ipv4Addr::new(10, 0, 0, 1);
+    let v6 = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+    assert_eq!(round_trip(&v4, "10.0.0.1"), v4);
+    assert_eq!(round_trip(&v6, "fe80::1"), v6);
+    assert_eq!(round_trip(&IpAddr::V4(v4), "10.0.0.1"), IpAddr::V4(v4));
+    assert_eq!(round_trip(&IpAddr::V6(v6), "fe80::1"), IpAddr::V6(v6));
+
+    let addr = SocketAddr::new(IpAddr::V4(v4), 6379);
+    assert_eq!(round_trip(&addr, "10.0.0.1:6379"), addr);
+    let addr = SocketAddr::new(IpAddr::V6(v6), 6379);
+    assert_eq!(round_trip(&addr, "[fe80::1]:6379"), addr);
+
+    let v = Ipv4Addr::from_redis_value(&Value::Status("10.0.0.1".into()));
+    assert_eq!(v, Ok(v4));
+
+    let err = IpAddr::from_redis_value(&Value::Data(b"not an ip".to_vec())).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TypeError);
+    let err = Ipv4Addr::from_redis_value(&Value::Data(b"fe80::1".to_vec())).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TypeError);
+    // The port is required, and a bare IPv6 address with one is ambiguous
+    let err = SocketAddr::from_redis_value(&Value::Data(b"10.0.0.1".to_vec())).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TypeError);
+    let err = SocketAddr::from_redis_value(&Value::Data(b"fe80::1:6379".to_vec())).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TypeError);
+}
+
+#[lunatic::test]
+fn test_strict_vec() {
+    use lunatic_redis::{ErrorKind, FromRedisValue, Strict, Value};
+
+    let empty = Value::Bulk(vec![]);
+
+    // The lenient decoder cannot tell nil and an empty array apart
+    let v: Vec<i32> = FromRedisValue::from_redis_value(&Value::Nil).unwrap();
+    assert!(v.is_empty());
+    let v: Vec<i32> = FromRedisValue::from_redis_value(&empty).unwrap();
+    assert!(v.is_empty());
+
+    // The strict one can
+    let v: Result<Strict<Vec<i32>>, _> = FromRedisValue::from_redis_value(&Value::Nil);
+    assert_eq!(v.unwrap_err().kind(), ErrorKind::TypeError);
+    let v: Strict<Vec<i32>> = FromRedisValue::from_redis_value(&empty).unwrap();
+    assert_eq!(v, Strict(vec![]));
+
+    let v: Option<Strict<Vec<i32>>> = FromRedisValue::from_redis_value(&Value::Nil).unwrap();
+    assert_eq!(v, None);
+    let v: Option<Strict<Vec<i32>>> = FromRedisValue::from_redis_value(&empty).unwrap();
+    assert_eq!(v, Some(Strict(vec![])));
+
+    let v: Strict<Vec<i32>> =
+        FromRedisValue::from_redis_value(&Value::Bulk(vec![Value::Int(1), Value::Int(2)])).unwrap();
+    assert_eq!(v.into_inner(), vec![1, 2]);
+}
+
 #[lunatic::test]
 fn test_tuple() {
     use lunatic_redis::{FromRedisValue, Value};
@@ -87,6 +432,84 @@ fn test_tuple() {
     assert_eq!(v, Ok(((1i32, 2, 3,),)));
 }
 
+#[lunatic::test]
+fn test_withscores_pairs() {
+    use lunatic_redis::{Cmd, FromRedisValue, Value};
+
+    fn data(s: &str) -> Value {
+        Value::Data(s.as_bytes().to_vec())
+    }
+
+    // ties keep the (lexicographic) order the server sent
+    let expected = vec![
+        ("a".to_string(), 1.0),
+        ("b".to_string(), 2.5),
+        ("c".to_string(), 2.5),
+    ];
+
+    // RESP2 sends a flat array with the scores as strings
+    let flat = Value::Bulk(vec![
+        data("a"),
+        data("1"),
+        data("b"),
+        data("2.5"),
+        data("c"),
+        data("2.5"),
+    ]);
+    let v: Vec<(String, f64)> = FromRedisValue::from_redis_value(&flat).unwrap();
+    assert_eq!(v, expected);
+
+    // RESP3 sends one array per pair with the scores as doubles
+    let nested = Value::Bulk(vec![
+        Value::Bulk(vec![data("a"), Value::Double(1.0)]),
+        Value::Bulk(vec![data("b"), Value::Double(2.5)]),
+        Value::Bulk(vec![data("c"), Value::Double(2.5)]),
+    ]);
+    let v: Vec<(String, f64)> = FromRedisValue::from_redis_value(&nested).unwrap();
+    assert_eq!(v, expected);
+
+    let empty: Vec<(String, f64)> = FromRedisValue::from_redis_value(&Value::Bulk(vec![])).unwrap();
+    assert!(empty.is_empty());
+
+    // A RESP2 array of arrays is read flat, whatever the arrays hold.
+    let arrays = Value::Bulk(vec![
+        Value::Bulk(vec![data("a"), data("1")]),
+        Value::Bulk(vec![data("b"), data("2")]),
+    ]);
+    let v: Vec<(Vec<String>, Vec<String>)> = FromRedisValue::from_redis_value(&arrays).unwrap();
+    assert_eq!(
+        v,
+        vec![(
+            vec!["a".to_string(), "1".to_string()],
+            vec!["b".to_string(), "2".to_string()]
+        )]
+    );
+
+    assert_eq!(
+        Cmd::zrevrange_withscores("z", 0, -1).get_packed_command(),
+        Cmd::new()
+            .arg("ZREVRANGE")
+            .arg("z")
+            .arg(0)
+            .arg(-1)
+            .arg("WITHSCORES")
+            .get_packed_command()
+    );
+    assert_eq!(
+        Cmd::zrangebyscore_limit_withscores("z", "-inf", "+inf", 10, 5).get_packed_command(),
+        Cmd::new()
+            .arg("ZRANGEBYSCORE")
+            .arg("z")
+            .arg("-inf")
+            .arg("+inf")
+            .arg("WITHSCORES")
+            .arg("LIMIT")
+            .arg(10)
+            .arg(5)
+            .get_packed_command()
+    );
+}
+
 #[lunatic::test]
 fn test_hashmap() {
     use fnv::FnvHasher;
@@ -227,3 +650,687 @@ fn test_types_to_redis_args() {
         .to_redis_args()
         .is_empty());
 }
+
+#[cfg(feature = "streams")]
+#[lunatic::test]
+fn test_stream_basic_args() {
+    use lunatic_redis::Cmd;
+
+    assert_eq!(
+        Cmd::xadd("s", "*", &[("a", "1"), ("b", "2")]).get_packed_command(),
+        b"*7\r\n$4\r\nXADD\r\n$1\r\ns\r\n$1\r\n*\r\n$1\r\na\r\n$1\r\n1\r\n$1\r\nb\r\n$1\r\n2\r\n"
+    );
+    assert_eq!(
+        Cmd::xadd("s", "1000-0", &[("a", "1")]).get_packed_command(),
+        b"*5\r\n$4\r\nXADD\r\n$1\r\ns\r\n$6\r\n1000-0\r\n$1\r\na\r\n$1\r\n1\r\n"
+    );
+    assert_eq!(
+        Cmd::xlen("s").get_packed_command(),
+        b"*2\r\n$4\r\nXLEN\r\n$1\r\ns\r\n"
+    );
+    assert_eq!(
+        Cmd::xrange("s", "-", "+").get_packed_command(),
+        b"*4\r\n$6\r\nXRANGE\r\n$1\r\ns\r\n$1\r\n-\r\n$1\r\n+\r\n"
+    );
+    assert_eq!(
+        Cmd::xread(&["s"], &["0"]).get_packed_command(),
+        b"*4\r\n$5\r\nXREAD\r\n$7\r\nSTREAMS\r\n$1\r\ns\r\n$1\r\n0\r\n"
+    );
+}
+
+#[cfg(feature = "streams")]
+#[lunatic::test]
+fn test_stream_autoclaim_reply() {
+    use lunatic_redis::streams::StreamAutoClaimReply;
+    use lunatic_redis::{FromRedisValue, Value};
+
+    let entry = Value::Bulk(vec![
+        Value::Data(b"1-0".to_vec()),
+        Value::Bulk(vec![Value::Data(b"h".to_vec()), Value::Data(b"w".to_vec())]),
+    ]);
+
+    // redis 6.2 shape
+    let reply = StreamAutoClaimReply::from_redis_value(&Value::Bulk(vec![
+        Value::Data(b"0-0".to_vec()),
+        Value::Bulk(vec![entry.clone()]),
+    ]))
+    .unwrap();
+    assert_eq!(reply.next_stream_id, "0-0");
+    assert_eq!(reply.claimed.len(), 1);
+    assert_eq!(reply.claimed[0].id, "1-0");
+    assert_eq!(reply.claimed[0].get("h"), Some("w".to_string()));
+    assert!(reply.deleted_ids.is_empty());
+
+    // redis 7 shape with deleted ids
+    let reply = StreamAutoClaimReply::from_redis_value(&Value::Bulk(vec![
+        Value::Data(b"2-0".to_vec()),
+        Value::Bulk(vec![entry]),
+        Value::Bulk(vec![Value::Data(b"1-1".to_vec())]),
+    ]))
+    .unwrap();
+    assert_eq!(reply.next_stream_id, "2-0");
+    assert_eq!(reply.deleted_ids, vec!["1-1".to_string()]);
+
+    // JUSTID shape
+    let reply = StreamAutoClaimReply::from_redis_value(&Value::Bulk(vec![
+        Value::Data(b"0-0".to_vec()),
+        Value::Bulk(vec![Value::Data(b"1-0".to_vec())]),
+        Value::Bulk(vec![]),
+    ]))
+    .unwrap();
+    assert_eq!(reply.claimed[0].id, "1-0");
+    assert!(reply.claimed[0].is_empty());
+}
+
+#[lunatic::test]
+fn test_zadd_options_args() {
+    use lunatic_redis::{ToRedisArgs, ZAddCondition, ZAddOptions};
+
+    assert!(ZAddOptions::default().to_redis_args().is_empty());
+    assert_eq!(
+        ZAddOptions::default()
+            .condition(ZAddCondition::Nx)
+            .ch()
+            .to_redis_args(),
+        vec![b"NX".to_vec(), b"CH".to_vec()]
+    );
+    assert_eq!(
+        ZAddOptions::default()
+            .condition(ZAddCondition::XxLt)
+            .to_redis_args(),
+        vec![b"XX".to_vec(), b"LT".to_vec()]
+    );
+    // the last condition wins
+    assert_eq!(
+        ZAddOptions::default()
+            .condition(ZAddCondition::Nx)
+            .condition(ZAddCondition::Gt)
+            .to_redis_args(),
+        vec![b"GT".to_vec()]
+    );
+}
+
+#[lunatic::test]
+fn test_zadd_options_cmd() {
+    use lunatic_redis::{Cmd, ZAddCondition, ZAddOptions};
+
+    // options go before the score/member pairs
+    let options = ZAddOptions::default().condition(ZAddCondition::Gt).ch();
+    assert_eq!(
+        Cmd::zadd_options("z", options, &[(1, "a"), (2, "b")]).get_packed_command(),
+        Cmd::new()
+            .arg("ZADD")
+            .arg("z")
+            .arg("GT")
+            .arg("CH")
+            .arg(1)
+            .arg("a")
+            .arg(2)
+            .arg("b")
+            .get_packed_command()
+    );
+
+    // INCR is only ever added by zadd_incr_options, after the options
+    let options = ZAddOptions::default().condition(ZAddCondition::XxGt);
+    assert_eq!(
+        Cmd::zadd_incr_options("z", "a", 2, options).get_packed_command(),
+        Cmd::new()
+            .arg("ZADD")
+            .arg("z")
+            .arg("XX")
+            .arg("GT")
+            .arg("INCR")
+            .arg(2)
+            .arg("a")
+            .get_packed_command()
+    );
+}
+
+#[lunatic::test]
+fn test_zcombine_options_args() {
+    use lunatic_redis::{ToRedisArgs, ZAggregate, ZCombineOptions};
+
+    assert!(ZCombineOptions::default().to_redis_args().is_empty());
+    assert_eq!(
+        ZCombineOptions::default()
+            .weights(&[1.0, 2.5])
+            .aggregate(ZAggregate::Max)
+            .withscores()
+            .to_redis_args(),
+        vec![
+            b"WEIGHTS".to_vec(),
+            b"1.0".to_vec(),
+            b"2.5".to_vec(),
+            b"AGGREGATE".to_vec(),
+            b"MAX".to_vec(),
+            b"WITHSCORES".to_vec(),
+        ]
+    );
+}
+
+#[lunatic::test]
+fn test_lrem_ltrim_args() {
+    use lunatic_redis::Cmd;
+
+    assert_eq!(
+        Cmd::lrem("l", -2, "a").get_packed_command(),
+        b"*4\r\n$4\r\nLREM\r\n$1\r\nl\r\n$2\r\n-2\r\n$1\r\na\r\n".to_vec()
+    );
+    assert_eq!(
+        Cmd::ltrim("l", 0, -1).get_packed_command(),
+        b"*4\r\n$5\r\nLTRIM\r\n$1\r\nl\r\n$1\r\n0\r\n$2\r\n-1\r\n".to_vec()
+    );
+}
+
+#[lunatic::test]
+fn test_get_ex_args() {
+    use lunatic_redis::{Cmd, Expiry};
+
+    assert_eq!(
+        Cmd::get_ex("k", Expiry::EX(10)).get_packed_command(),
+        b"*4\r\n$5\r\nGETEX\r\n$1\r\nk\r\n$2\r\nEX\r\n$2\r\n10\r\n".to_vec()
+    );
+    assert_eq!(
+        Cmd::get_ex("k", Expiry::PX(10)).get_packed_command(),
+        b"*4\r\n$5\r\nGETEX\r\n$1\r\nk\r\n$2\r\nPX\r\n$2\r\n10\r\n".to_vec()
+    );
+    assert_eq!(
+        Cmd::get_ex("k", Expiry::EXAT(1700000000)).get_packed_command(),
+        b"*4\r\n$5\r\nGETEX\r\n$1\r\nk\r\n$4\r\nEXAT\r\n$10\r\n1700000000\r\n".to_vec()
+    );
+    assert_eq!(
+        Cmd::get_ex("k", Expiry::PXAT(1700000000)).get_packed_command(),
+        b"*4\r\n$5\r\nGETEX\r\n$1\r\nk\r\n$4\r\nPXAT\r\n$10\r\n1700000000\r\n".to_vec()
+    );
+    // PERSIST takes no value
+    assert_eq!(
+        Cmd::get_ex("k", Expiry::PERSIST).get_packed_command(),
+        b"*3\r\n$5\r\nGETEX\r\n$1\r\nk\r\n$7\r\nPERSIST\r\n".to_vec()
+    );
+}
+
+#[lunatic::test]
+fn test_bitcount_range_unit_args() {
+    use lunatic_redis::{BitUnit, Cmd};
+
+    assert_eq!(
+        Cmd::bitcount_range_unit("k", 0, -1, BitUnit::Bit).get_packed_command(),
+        b"*5\r\n$8\r\nBITCOUNT\r\n$1\r\nk\r\n$1\r\n0\r\n$2\r\n-1\r\n$3\r\nBIT\r\n".to_vec()
+    );
+    assert_eq!(
+        Cmd::setbit("k", 4_000_000_000, true).get_packed_command(),
+        b"*4\r\n$6\r\nSETBIT\r\n$1\r\nk\r\n$10\r\n4000000000\r\n$1\r\n1\r\n".to_vec()
+    );
+}
+
+#[lunatic::test]
+fn test_publish_binary_args() {
+    use lunatic_redis::Cmd;
+
+    let payload = &b"\x00\xff\r\n"[..];
+    let expected = b"*3\r\n$7\r\nPUBLISH\r\n$2\r\nch\r\n$4\r\n\x00\xff\r\n\r\n".to_vec();
+    assert_eq!(Cmd::publish("ch", payload).get_packed_command(), expected);
+    assert_eq!(
+        Cmd::publish("ch", payload.to_vec()).get_packed_command(),
+        expected
+    );
+}
+
+#[lunatic::test]
+fn test_expire_options_args() {
+    use lunatic_redis::{Cmd, ExpireOption};
+
+    assert_eq!(
+        Cmd::expire_options("k", 10, ExpireOption::GT).get_packed_command(),
+        b"*4\r\n$6\r\nEXPIRE\r\n$1\r\nk\r\n$2\r\n10\r\n$2\r\nGT\r\n".to_vec()
+    );
+    // Without a condition this is a plain EXPIRE
+    assert_eq!(
+        Cmd::expire_options("k", 10, ExpireOption::NONE).get_packed_command(),
+        Cmd::expire("k", 10).get_packed_command()
+    );
+    assert_eq!(
+        Cmd::pexpire_options("k", 10, ExpireOption::NX).get_packed_command(),
+        b"*4\r\n$7\r\nPEXPIRE\r\n$1\r\nk\r\n$2\r\n10\r\n$2\r\nNX\r\n".to_vec()
+    );
+}
+
+#[lunatic::test]
+fn test_config_value() {
+    use lunatic_redis::{Cmd, ConfigValue, FromRedisValue, Value};
+    use std::collections::HashMap;
+
+    let save = "3600 1 300 100";
+    // RESP2 sends a flat array, RESP3 a map
+    for reply in &[
+        Value::Bulk(vec![
+            Value::Data(b"save".to_vec()),
+            Value::Data(save.as_bytes().to_vec()),
+        ]),
+        Value::Map(vec![(
+            Value::Data(b"save".to_vec()),
+            Value::Data(save.as_bytes().to_vec()),
+        )]),
+    ] {
+        let config: HashMap<String, ConfigValue> = FromRedisValue::from_redis_value(reply).unwrap();
+        assert_eq!(config["save"].as_str(), save);
+        assert_eq!(
+            config["save"].tokens().collect::<Vec<_>>(),
+            vec!["3600", "1", "300", "100"]
+        );
+    }
+
+    let joined = ConfigValue::from_redis_value(&Value::Bulk(vec![
+        Value::Data(b"3600".to_vec()),
+        Value::Data(b"1".to_vec()),
+    ]));
+    assert_eq!(joined, Ok(ConfigValue::new("3600 1")));
+
+    // Written back as one argument
+    assert_eq!(
+        Cmd::config_set("save", ConfigValue::new(save)).get_packed_command(),
+        b"*4\r\n$6\r\nCONFIG\r\n$3\r\nSET\r\n$4\r\nsave\r\n$14\r\n3600 1 300 100\r\n".to_vec()
+    );
+}
+
+#[lunatic::test]
+fn test_get_del_args() {
+    use lunatic_redis::{Cmd, FromRedisValue, Value};
+
+    assert_eq!(
+        Cmd::get_del("k").get_packed_command(),
+        b"*2\r\n$6\r\nGETDEL\r\n$1\r\nk\r\n".to_vec()
+    );
+    // The reply for a missing key
+    let v: Option<String> = FromRedisValue::from_redis_value(&Value::Nil).unwrap();
+    assert_eq!(v, None);
+}
+
+#[lunatic::test]
+fn test_hyperloglog_args() {
+    use lunatic_redis::Cmd;
+
+    assert_eq!(
+        Cmd::pfadd("hll", &["a", "b"][..]).get_packed_command(),
+        b"*4\r\n$5\r\nPFADD\r\n$3\r\nhll\r\n$1\r\na\r\n$1\r\nb\r\n".to_vec()
+    );
+    assert_eq!(
+        Cmd::pfcount(&["h1", "h2"][..]).get_packed_command(),
+        b"*3\r\n$7\r\nPFCOUNT\r\n$2\r\nh1\r\n$2\r\nh2\r\n".to_vec()
+    );
+    assert_eq!(
+        Cmd::pfmerge(&["dst"][..], &["h1", "h2"][..]).get_packed_command(),
+        b"*4\r\n$7\r\nPFMERGE\r\n$3\r\ndst\r\n$2\r\nh1\r\n$2\r\nh2\r\n".to_vec()
+    );
+}
+
+#[lunatic::test]
+fn test_binary_hash_fields() {
+    use lunatic_redis::Cmd;
+
+    let field = &b"\x00\xff\xfe"[..];
+    assert_eq!(
+        Cmd::hset("h", field, 1).get_packed_command(),
+        b"*4\r\n$4\r\nHSET\r\n$1\r\nh\r\n$3\r\n\x00\xff\xfe\r\n$1\r\n1\r\n".to_vec()
+    );
+    assert_eq!(
+        Cmd::hget("h", field).get_packed_command(),
+        b"*3\r\n$4\r\nHGET\r\n$1\r\nh\r\n$3\r\n\x00\xff\xfe\r\n".to_vec()
+    );
+    assert_eq!(
+        Cmd::hdel("h", field).get_packed_command(),
+        b"*3\r\n$4\r\nHDEL\r\n$1\r\nh\r\n$3\r\n\x00\xff\xfe\r\n".to_vec()
+    );
+}
+
+#[lunatic::test]
+fn test_client_kill_filter_args() {
+    use lunatic_redis::{ClientKillFilter, ClientType, ToRedisArgs};
+
+    assert!(ClientKillFilter::default().to_redis_args().is_empty());
+    assert_eq!(
+        ClientKillFilter::default()
+            .id(7)
+            .addr("127.0.0.1:5000")
+            .client_type(ClientType::PubSub)
+            .skipme(false)
+            .to_redis_args(),
+        vec![
+            b"ID".to_vec(),
+            b"7".to_vec(),
+            b"ADDR".to_vec(),
+            b"127.0.0.1:5000".to_vec(),
+            b"TYPE".to_vec(),
+            b"pubsub".to_vec(),
+            b"SKIPME".to_vec(),
+            b"no".to_vec(),
+        ]
+    );
+}
+
+#[lunatic::test]
+fn test_failover_options_args() {
+    use lunatic_redis::{FailoverOptions, ToRedisArgs};
+
+    assert!(FailoverOptions::default().to_redis_args().is_empty());
+    assert_eq!(
+        FailoverOptions::default()
+            .to("10.0.0.2", 6380)
+            .force()
+            .timeout(500)
+            .to_redis_args(),
+        vec![
+            b"TO".to_vec(),
+            b"10.0.0.2".to_vec(),
+            b"6380".to_vec(),
+            b"FORCE".to_vec(),
+            b"TIMEOUT".to_vec(),
+            b"500".to_vec(),
+        ]
+    );
+    // FORCE is only valid together with a target
+    assert_eq!(
+        FailoverOptions::default().force().to_redis_args(),
+        Vec::<Vec<u8>>::new()
+    );
+}
+
+#[cfg(feature = "lunatic-processes")]
+#[lunatic::test]
+fn test_process_handle_round_trip() {
+    use lunatic::Process;
+    use lunatic_redis::{FromRedisValue, ToRedisArgs, Value};
+
+    let this = Process::<String>::this();
+    let mut args = this.to_redis_args();
+    assert_eq!(args.len(), 1);
+
+    let stored = Value::Data(args.remove(0));
+    let read: Process<String> = FromRedisValue::from_redis_value(&stored).unwrap();
+    assert_eq!(read.id(), this.id());
+
+    let garbage: Result<Process<String>, _> =
+        FromRedisValue::from_redis_value(&Value::Data(b"x".to_vec()));
+    assert!(garbage.is_err());
+}
+
+#[cfg(feature = "uuid")]
+#[lunatic::test]
+fn test_uuid_round_trip() {
+    use lunatic_redis::{ErrorKind, FromRedisValue, ToRedisArgs, Value};
+    use uuid::Uuid;
+
+    let text = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+    let id = Uuid::parse_str(text).unwrap();
+
+    let mut args = id.to_redis_args();
+    assert_eq!(args, vec![id.as_bytes().to_vec()]);
+    let stored = Value::Data(args.remove(0));
+    assert_eq!(Uuid::from_redis_value(&stored), Ok(id));
+
+    let stored = Value::Data(text.as_bytes().to_vec());
+    assert_eq!(Uuid::from_redis_value(&stored), Ok(id));
+    let stored = Value::Status(text.to_string());
+    assert_eq!(Uuid::from_redis_value(&stored), Ok(id));
+
+    let err = Uuid::from_redis_value(&Value::Data(b"not a uuid".to_vec())).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TypeError);
+    let err = Uuid::from_redis_value(&Value::Int(1)).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TypeError);
+}
+
+#[cfg(feature = "json")]
+#[lunatic::test]
+fn test_json() {
+    use lunatic_redis::{ErrorKind, FromRedisValue, Json, ToRedisArgs, Value};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct MyStruct {
+        name: String,
+        tags: Vec<u32>,
+    }
+
+    let item = MyStruct {
+        name: "ferris".to_string(),
+        tags: vec![1, 2],
+    };
+    let mut args = Json(&item).to_redis_args();
+    assert_eq!(args, vec![br#"{"name":"ferris","tags":[1,2]}"#.to_vec()]);
+    assert_eq!(Json::encode(&item).unwrap().to_redis_args(), args);
+
+    let stored = Value::Data(args.remove(0));
+    let Json(read): Json<MyStruct> = FromRedisValue::from_redis_value(&stored).unwrap();
+    assert_eq!(read, item);
+
+    // The struct itself round-trips, not only a reference to it.
+    let stored = Value::Data(Json(read).to_redis_args().remove(0));
+    let Json(read): Json<MyStruct> = FromRedisValue::from_redis_value(&stored).unwrap();
+    assert_eq!(read, item);
+
+    let json: serde_json::Value = FromRedisValue::from_redis_value(&stored).unwrap();
+    assert_eq!(json["tags"][1], 2);
+    let json = serde_json::Value::from_redis_value(&Value::Status("[1]".to_string()));
+    assert_eq!(json, Ok(serde_json::json!([1])));
+
+    // JSON object keys have to be strings
+    let mut map = std::collections::HashMap::new();
+    map.insert((1, 2), "pair");
+    let err = Json::encode(&map).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ClientError);
+
+    let err = Json::<MyStruct>::from_redis_value(&Value::Data(b"{".to_vec())).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TypeError);
+    let err = serde_json::Value::from_redis_value(&Value::Int(1)).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TypeError);
+}
+
+#[lunatic::test]
+fn test_scan_options_args() {
+    use lunatic_redis::{ScanOptions, ToRedisArgs};
+
+    assert!(ScanOptions::default().to_redis_args().is_empty());
+    assert_eq!(
+        ScanOptions::default()
+            .pattern("user:*")
+            .count(100)
+            .to_redis_args(),
+        vec![
+            b"MATCH".to_vec(),
+            b"user:*".to_vec(),
+            b"COUNT".to_vec(),
+            b"100".to_vec()
+        ]
+    );
+    assert_eq!(
+        ScanOptions::default().count(10).to_redis_args(),
+        vec![b"COUNT".to_vec(), b"10".to_vec()]
+    );
+    assert_eq!(
+        ScanOptions::default()
+            .pattern("user:*")
+            .key_type("string")
+            .to_redis_args(),
+        vec![
+            b"MATCH".to_vec(),
+            b"user:*".to_vec(),
+            b"TYPE".to_vec(),
+            b"string".to_vec()
+        ]
+    );
+}
+
+#[lunatic::test]
+fn test_scan_iter_follows_cursor() {
+    use lunatic_redis::{cmd, Commands, ScanOptions, Value};
+
+    fn batch(cursor: &str, keys: &[&str]) -> Value {
+        Value::Bulk(vec![
+            Value::Data(cursor.as_bytes().to_vec()),
+            Value::Bulk(
+                keys.iter()
+                    .map(|k| Value::Data(k.as_bytes().to_vec()))
+                    .collect(),
+            ),
+        ])
+    }
+
+    // The second batch is empty, as can happen when MATCH or TYPE filters
+    // out everything the server looked at.
+    let mut con = MockConnection::new(vec![
+        Ok(batch("17", &["user:1", "user:2"])),
+        Ok(batch("42", &[])),
+        Ok(batch("0", &["user:3"])),
+    ]);
+    let opts = ScanOptions::default()
+        .pattern("user:*")
+        .count(2)
+        .key_type("string");
+    let keys: Vec<String> = con.scan_options(opts).unwrap().collect();
+    assert_eq!(keys, vec!["user:1", "user:2", "user:3"]);
+
+    let expected: Vec<Vec<u8>> = ["0", "17", "42"]
+        .iter()
+        .map(|cursor| {
+            cmd("SCAN")
+                .arg(*cursor)
+                .arg("MATCH")
+                .arg("user:*")
+                .arg("COUNT")
+                .arg(2)
+                .arg("TYPE")
+                .arg("string")
+                .get_packed_command()
+        })
+        .collect();
+    assert_eq!(con.sent, expected);
+    // No further request once the cursor is back at 0.
+    assert!(con.replies.is_empty());
+}
+
+#[lunatic::test]
+fn test_query_transaction() {
+    use lunatic_redis::{cmd, pipe, ErrorKind, RedisError, Value};
+
+    let queued = || Ok(Value::Status("QUEUED".into()));
+    let mut con = MockConnection::new(vec![
+        Ok(Value::Okay),
+        queued(),
+        queued(),
+        Ok(Value::Bulk(vec![Value::Okay, Value::Okay])),
+        Ok(Value::Okay),
+        queued(),
+        queued(),
+        Ok(Value::Nil),
+    ]);
+
+    let mut pipeline = pipe();
+    pipeline.atomic().set("key_1", 42).set("key_2", 43);
+    let reply: Option<(String, String)> = pipeline.query_transaction(&mut con).unwrap();
+    assert_eq!(reply, Some(("OK".to_string(), "OK".to_string())));
+
+    let mut queued_cmds = cmd("SET").arg("key_1").arg(42).get_packed_command();
+    queued_cmds.extend(cmd("SET").arg("key_2").arg(43).get_packed_command());
+    assert_eq!(
+        con.sent,
+        vec![
+            cmd("MULTI").get_packed_command(),
+            queued_cmds,
+            cmd("EXEC").get_packed_command()
+        ]
+    );
+
+    // A WATCHed key changed, EXEC replies with nil.
+    let reply: Option<(String, String)> = pipeline.query_transaction(&mut con).unwrap();
+    assert_eq!(reply, None);
+
+    // A command was rejected while queueing, EXEC discards the transaction.
+    let mut con = MockConnection::new(vec![
+        Ok(Value::Okay),
+        Err(RedisError::from((
+            ErrorKind::ResponseError,
+            "An error was signalled by the server",
+            "wrong number of arguments for 'set' command".to_string(),
+        ))),
+        queued(),
+        Err(RedisError::from((
+            ErrorKind::ExecAbortError,
+            "An error was signalled by the server",
+            "Transaction discarded because of previous errors.".to_string(),
+        ))),
+    ]);
+    let err = pipeline
+        .query_transaction::<(String, String)>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ExecAbortError);
+    assert!(err.detail().unwrap().contains("wrong number of arguments"));
+    assert_eq!(con.sent.len(), 3);
+
+    // The server refused MULTI, nothing else may be sent.
+    let mut con = MockConnection::new(vec![Err(RedisError::from((
+        ErrorKind::ResponseError,
+        "An error was signalled by the server",
+        "MULTI calls can not be nested".to_string(),
+    )))]);
+    let err = pipeline
+        .query_transaction::<(String, String)>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ResponseError);
+    assert!(err.detail().unwrap().contains("can not be nested"));
+    assert_eq!(con.sent, vec![cmd("MULTI").get_packed_command()]);
+}
+
+#[cfg(feature = "script")]
+#[lunatic::test]
+fn test_script_invoke_uses_evalsha() {
+    use lunatic_redis::{cmd, ErrorKind, RedisError, Script, Value};
+
+    let code = "return tonumber(ARGV[1]) + tonumber(ARGV[2])";
+    let script = Script::new(code);
+    // Scripts with the same code share the server-side cache entry.
+    assert_eq!(script.get_hash(), Script::new(code).get_hash());
+    assert_eq!(
+        script.get_hash(),
+        "189758df5f16d279fa4852c3ee51f702e4ea0414"
+    );
+
+    let noscript = RedisError::from((
+        ErrorKind::NoScriptError,
+        "An error was signalled by the server",
+        "No matching script. Please use EVAL.".to_string(),
+    ));
+    let mut con = MockConnection::new(vec![
+        Err(noscript),
+        Ok(Value::Data(script.get_hash().as_bytes().to_vec())),
+        Ok(Value::Int(3)),
+        Ok(Value::Int(7)),
+    ]);
+    assert_eq!(script.arg(1).arg(2).invoke(&mut con), Ok(3));
+    assert_eq!(script.arg(3).arg(4).invoke(&mut con), Ok(7));
+
+    let evalsha = |a: i32, b: i32| {
+        cmd("EVALSHA")
+            .arg(script.get_hash())
+            .arg(0)
+            .arg(a)
+            .arg(b)
+            .get_packed_command()
+    };
+    assert_eq!(
+        con.sent,
+        vec![
+            evalsha(1, 2),
+            cmd("SCRIPT").arg("LOAD").arg(code).get_packed_command(),
+            evalsha(1, 2),
+            // The script is cached now, only its hash is sent.
+            evalsha(3, 4),
+        ]
+    );
+
+    let mut con = MockConnection::new(vec![Ok(Value::Data(script.get_hash().as_bytes().to_vec()))]);
+    script.prepare(&mut con).unwrap();
+    assert_eq!(
+        con.sent,
+        vec![cmd("SCRIPT").arg("LOAD").arg(code).get_packed_command()]
+    );
+}